@@ -4,6 +4,13 @@ use std::collections::{HashMap, VecDeque};
 use crate::world::position::Position;
 use super::{message::Message, job::Job};
 use crate::SimulationConfig;
+use crate::world::ownership::{AccessPolicy, Owner, Stockpile};
+use crate::world::resources::ResourceSystem;
+use crate::world::storage::Storage;
+use crate::engine::scheduler::{CalendarRule, ScheduledEvent, SimScheduler, DAWN_TIMER};
+use crate::engine::tick_rates::TickSchedule;
+use crate::engine::time::{DayPhase, SimClock};
+use crate::engine::weather_effects::WeatherModifiers;
 use rand::random;
 
 /// Maximum number of messages to keep in an agent's message queue
@@ -44,6 +51,10 @@ pub struct Agent {
     pub energy: f32,
     /// Age of the agent in seconds
     pub age: f32,
+    /// Skill levels by name, checked by recipes
+    pub skills: HashMap<String, f32>,
+    /// Effects of the weather where the agent stands
+    pub weather: WeatherModifiers,
 }
 
 impl Default for Agent {
@@ -60,6 +71,8 @@ impl Default for Agent {
             velocity: Vec2::ZERO,
             energy: 100.0,
             age: 0.0,
+            skills: HashMap::new(),
+            weather: WeatherModifiers::default(),
         }
     }
 }
@@ -81,9 +94,16 @@ impl Agent {
             velocity: Vec2::ZERO,
             energy: 100.0,
             age: 0.0,
+            skills: HashMap::new(),
+            weather: WeatherModifiers::default(),
         }
     }
 
+    /// Range at which the agent perceives in the current weather
+    pub fn effective_perception_range(&self) -> f32 {
+        self.perception_range * self.weather.perception
+    }
+
     /// Processes a single tick for this agent
     pub fn tick(&mut self) {
         self.tick_count += 1;
@@ -194,41 +214,108 @@ impl Agent {
 }
 
 /// Spawns agents based on the simulation configuration
+///
+/// Each agent also gets a basket stockpile of its own, shared with its kin,
+/// and a timer waking it every dawn.
 pub fn spawn_agents(
     mut commands: Commands,
     config: Res<SimulationConfig>,
+    clock: Res<SimClock>,
+    mut scheduler: ResMut<SimScheduler>,
 ) {
     for _ in 0..config.agent_count {
-        commands.spawn(Agent::default());
+        let agent = Agent::default();
+        let dawn = ScheduledEvent::new(DAWN_TIMER).for_agent(agent.id);
+        scheduler.every(&clock, CalendarRule::Phase(DayPhase::Dawn), dawn);
+        let agent = commands.spawn((agent, ResourceSystem::new(), TickSchedule::new("human"))).id();
+        commands.spawn((
+            ResourceSystem { regeneration_rate: 0.0, ..ResourceSystem::new() },
+            Stockpile::new(Owner::Agent(agent), AccessPolicy::Kin),
+            Storage::new("basket"),
+        ));
     }
     info!("Spawned {} agents", config.agent_count);
 }
 
-/// Updates all agents based on the current time and simulation speed
+/// Moves, ages and tires all agents by one simulation tick
+///
+/// Movement and energy drain are scaled by the weather the agent stands in.
 pub fn update_agents(
-    time: Res<Time>,
-    config: Res<SimulationConfig>,
+    clock: Res<SimClock>,
     mut query: Query<&mut Agent>,
 ) {
-    let dt = time.delta_secs() * config.simulation_speed as f32;
+    let dt = clock.tick_length as f32;
     
     for mut agent in query.iter_mut() {
         // Store velocity in a local variable to avoid borrowing issues
-        let velocity = agent.velocity;
+        let velocity = agent.velocity * agent.weather.movement;
         agent.position += velocity * dt;
         
         // Update agent age
         agent.age += dt;
         
         // Consume energy over time
-        agent.energy -= dt * 0.1;
+        agent.energy -= dt * 0.1 * agent.weather.energy_drain;
         
         // Basic movement behavior
-        if velocity.length() < 1.0 {
+        if agent.velocity.length() < 1.0 {
             agent.velocity = Vec2::new(
                 random::<f32>() * 2.0 - 1.0,
                 random::<f32>() * 2.0 - 1.0,
             );
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+    use crate::engine::pipeline::{Act, TickPipelinePlugin};
+
+    #[test]
+    fn test_weather_slows_and_tires_agents_each_tick() {
+        let mut app = App::new();
+        app.add_plugins(TickPipelinePlugin)
+            .insert_resource(SimClock::with_tick_length(2.0))
+            .add_systems(Act, update_agents);
+
+        let agent = app.world_mut().spawn(Agent {
+            velocity: Vec2::new(3.0, 4.0),
+            weather: WeatherModifiers {
+                movement: 0.5,
+                energy_drain: 2.0,
+                ..default()
+            },
+            ..default()
+        }).id();
+
+        app.world_mut().run_schedule(FixedUpdate);
+        let agent = app.world().get::<Agent>(agent).unwrap();
+        assert_eq!(agent.position, Vec2::new(3.0, 4.0));
+        assert_eq!(agent.age, 2.0);
+        assert!((agent.energy - 99.6).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_spawned_agents_wake_at_dawn() {
+        let mut world = World::new();
+        world.insert_resource(SimulationConfig { agent_count: 3, ..default() });
+        // One tick per hour
+        world.insert_resource(SimClock::with_tick_length(60.0));
+        world.init_resource::<SimScheduler>();
+        world.run_system_once(spawn_agents).unwrap();
+
+        let mut clock = SimClock::with_tick_length(60.0);
+        for _ in 0..5 {
+            clock.advance_tick();
+        }
+        let woken: Vec<_> = world.resource_mut::<SimScheduler>().take_due(&clock).into_iter()
+            .filter(|timer| timer.event.name == DAWN_TIMER)
+            .filter_map(|timer| timer.event.agent)
+            .collect();
+        let mut agents = world.query::<&Agent>();
+        assert_eq!(woken.len(), 3);
+        assert!(agents.iter(&world).all(|agent| woken.contains(&agent.id)));
+    }
+}
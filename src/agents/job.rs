@@ -1,9 +1,17 @@
+use bevy::prelude::Entity;
+use crate::world::quantity::Quantity;
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Job {
     Idle,
     Move { target_x: i32, target_y: i32 },
     Gather { resource_type: String },
     Build { structure_type: String },
+    Produce { recipe_id: String },
+    Withdraw { stockpile: Entity, resource_type: String, amount: Quantity },
+    Deposit { stockpile: Entity, resource_type: String, amount: Quantity },
+    UpgradeStorage { stockpile: Entity },
+    Haul { from: Entity, to: Entity, resource_type: String, amount: Quantity },
     Interact { target_id: String },
 }
 
@@ -14,6 +22,10 @@ impl Job {
             Job::Move { target_x: _, target_y: _ } => false, // Will be implemented with position checking
             Job::Gather { resource_type: _ } => false, // Will be implemented with inventory checking
             Job::Build { structure_type: _ } => false, // Will be implemented with construction checking
+            Job::Produce { recipe_id: _ } => false, // Completed by the production system
+            Job::Withdraw { .. } | Job::Deposit { .. } => false, // Completed by the stockpile system
+            Job::UpgradeStorage { stockpile: _ } => false, // Completed by the production system
+            Job::Haul { .. } => false, // Completed by the haul system
             Job::Interact { target_id: _ } => false, // Will be implemented with interaction checking
         }
     }
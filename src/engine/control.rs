@@ -0,0 +1,405 @@
+use bevy::app::{FixedMain, RunFixedMainLoop, RunFixedMainLoopSystem};
+use bevy::prelude::*;
+use bevy::time::TimeSystem;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use crate::engine::time::SimClock;
+
+/// Slowest and fastest speed multipliers accepted by `SetSpeed`
+const MIN_SPEED: f64 = 0.01;
+const MAX_SPEED: f64 = 1000.0;
+
+/// Wall-clock time per frame spent running ticks at max speed
+const DEFAULT_FRAME_BUDGET: Duration = Duration::from_millis(12);
+
+/// Speed multipliers picked with the number keys 1 to 4
+const KEY_SPEEDS: [(KeyCode, f64); 4] = [
+    (KeyCode::Digit1, 1.0),
+    (KeyCode::Digit2, 2.0),
+    (KeyCode::Digit3, 4.0),
+    (KeyCode::Digit4, 8.0),
+];
+
+/// Title of the simulation window, followed by the simulation status
+pub const WINDOW_TITLE: &str = "Neo Simulation";
+
+/// How the simulation advances
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SimMode {
+    /// Fixed ticks follow real time scaled by the speed multiplier
+    Running,
+    /// No ticks run except requested steps
+    Paused,
+    /// Ticks run back to back for the frame budget, regardless of real time
+    MaxSpeed,
+}
+
+/// A request to change how the simulation advances
+///
+/// This is the one way the console, UI and network control the
+/// simulation: send it as an event, or apply it to `SimulationControl`
+/// directly when holding the resource.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum SimCommand {
+    Pause,
+    Resume,
+    TogglePause,
+    /// Pauses and runs this many ticks
+    Step(u64),
+    /// Sets the multiplier of real time used while running
+    SetSpeed(f64),
+    /// Runs this many ticks at max speed, then pauses
+    FastForward(u64),
+    /// Runs at max speed until told otherwise
+    MaxSpeed,
+}
+
+/// Resource controlling pause, stepping and speed of the simulation
+///
+/// While running, Bevy's fixed timestep loop runs the ticks and the speed
+/// multiplier scales virtual time. When paused, stepping or at max speed,
+/// virtual time is paused and ticks are run directly, so how many run per
+/// frame no longer depends on real time. Every tick advances `SimClock` by
+/// the same amount whatever the mode, so results don't depend on it.
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct SimulationControl {
+    /// Wall-clock time per frame spent running ticks at max speed
+    pub frame_budget: Duration,
+    mode: SimMode,
+    speed: f64,
+    pending_steps: u64,
+    fast_forward: Option<u64>,
+    ticks_last_frame: u64,
+}
+
+impl Default for SimulationControl {
+    fn default() -> Self {
+        Self {
+            frame_budget: DEFAULT_FRAME_BUDGET,
+            mode: SimMode::Running,
+            speed: 1.0,
+            pending_steps: 0,
+            fast_forward: None,
+            ticks_last_frame: 0,
+        }
+    }
+}
+
+impl SimulationControl {
+    /// Creates a control starting at max speed, for headless runs
+    pub fn max_speed() -> Self {
+        Self { mode: SimMode::MaxSpeed, ..Default::default() }
+    }
+
+    pub fn mode(&self) -> SimMode {
+        self.mode
+    }
+
+    pub fn speed(&self) -> f64 {
+        self.speed
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.mode == SimMode::Paused
+    }
+
+    /// Steps still waiting to run
+    pub fn pending_steps(&self) -> u64 {
+        self.pending_steps
+    }
+
+    /// Ticks left in the current fast-forward, if any
+    pub fn fast_forward_remaining(&self) -> Option<u64> {
+        self.fast_forward
+    }
+
+    /// Ticks run directly in the last frame, excluding Bevy's fixed loop
+    pub fn ticks_last_frame(&self) -> u64 {
+        self.ticks_last_frame
+    }
+
+    pub fn pause(&mut self) {
+        self.apply(SimCommand::Pause);
+    }
+
+    pub fn resume(&mut self) {
+        self.apply(SimCommand::Resume);
+    }
+
+    pub fn step(&mut self, ticks: u64) {
+        self.apply(SimCommand::Step(ticks));
+    }
+
+    pub fn set_speed(&mut self, speed: f64) {
+        self.apply(SimCommand::SetSpeed(speed));
+    }
+
+    pub fn fast_forward(&mut self, ticks: u64) {
+        self.apply(SimCommand::FastForward(ticks));
+    }
+
+    /// Applies a command
+    pub fn apply(&mut self, command: SimCommand) {
+        match command {
+            SimCommand::Pause => self.stop(),
+            SimCommand::Resume => self.run(SimMode::Running),
+            SimCommand::TogglePause if self.is_paused() => self.run(SimMode::Running),
+            SimCommand::TogglePause => self.stop(),
+            SimCommand::Step(ticks) => {
+                self.stop();
+                self.pending_steps += ticks;
+            }
+            SimCommand::SetSpeed(speed) if speed.is_finite() => {
+                self.speed = speed.clamp(MIN_SPEED, MAX_SPEED);
+                if self.mode == SimMode::MaxSpeed {
+                    self.run(SimMode::Running);
+                }
+            }
+            SimCommand::SetSpeed(speed) => warn!("Ignoring simulation speed {}", speed),
+            SimCommand::FastForward(ticks) => {
+                self.run(SimMode::MaxSpeed);
+                self.fast_forward = Some(ticks);
+            }
+            SimCommand::MaxSpeed => self.run(SimMode::MaxSpeed),
+        }
+    }
+
+    fn run(&mut self, mode: SimMode) {
+        self.mode = mode;
+        self.pending_steps = 0;
+        self.fast_forward = None;
+    }
+
+    fn stop(&mut self) {
+        self.mode = SimMode::Paused;
+        self.fast_forward = None;
+    }
+
+    /// Takes the next directly run tick, if one is due
+    ///
+    /// `spent` is the wall-clock time already spent on ticks this frame.
+    /// Requested steps always run; max speed runs until the frame budget
+    /// is spent, and a finished fast-forward pauses the simulation.
+    fn take_tick(&mut self, spent: Duration) -> bool {
+        if self.pending_steps > 0 {
+            self.pending_steps -= 1;
+            return true;
+        }
+        if self.mode != SimMode::MaxSpeed || spent >= self.frame_budget {
+            return false;
+        }
+        match self.fast_forward {
+            Some(0) => {
+                self.stop();
+                false
+            }
+            Some(left) => {
+                self.fast_forward = Some(left - 1);
+                true
+            }
+            None => true,
+        }
+    }
+}
+
+/// System that applies `SimCommand` events to `SimulationControl`
+pub fn apply_sim_commands(
+    mut commands: EventReader<SimCommand>,
+    mut control: ResMut<SimulationControl>,
+) {
+    for command in commands.read() {
+        info!("Simulation control: {:?}", command);
+        control.apply(*command);
+    }
+}
+
+/// System that pauses or scales virtual time to match `SimulationControl`
+pub fn sync_virtual_time(
+    control: Res<SimulationControl>,
+    mut time: ResMut<Time<Virtual>>,
+) {
+    if control.mode() == SimMode::Running {
+        time.unpause();
+        time.set_relative_speed_f64(control.speed());
+    } else {
+        time.pause();
+    }
+}
+
+/// System that runs the steps and max speed ticks of this frame
+///
+/// Mirrors Bevy's fixed main loop: each tick advances `Time<Fixed>` by one
+/// timestep and runs `FixedMain` with it as the generic time.
+pub fn run_controlled_ticks(world: &mut World) {
+    let start = Instant::now();
+    let mut ticks = 0;
+    let _ = world.try_schedule_scope(FixedMain, |world, schedule| {
+        while world.resource_mut::<SimulationControl>().take_tick(start.elapsed()) {
+            let timestep = world.resource::<Time<Fixed>>().timestep();
+            world.resource_mut::<Time<Fixed>>().advance_by(timestep);
+            *world.resource_mut::<Time>() = world.resource::<Time<Fixed>>().as_generic();
+            schedule.run(world);
+            ticks += 1;
+        }
+    });
+    *world.resource_mut::<Time>() = world.resource::<Time<Virtual>>().as_generic();
+
+    world.resource_mut::<SimulationControl>().ticks_last_frame = ticks;
+}
+
+/// System controlling the simulation from the keyboard in windowed runs
+///
+/// Space pauses and resumes, period steps a single tick, 1 to 4 pick the
+/// speed and F fast-forwards one simulated day.
+pub fn keyboard_sim_controls(
+    keys: Res<ButtonInput<KeyCode>>,
+    clock: Res<SimClock>,
+    mut control: ResMut<SimulationControl>,
+) {
+    if keys.just_pressed(KeyCode::Space) {
+        if control.is_paused() {
+            control.resume();
+        } else {
+            control.pause();
+        }
+    }
+    if keys.just_pressed(KeyCode::Period) {
+        control.step(1);
+    }
+    for (key, speed) in KEY_SPEEDS {
+        if keys.just_pressed(key) {
+            control.set_speed(speed);
+        }
+    }
+    if keys.just_pressed(KeyCode::KeyF) {
+        let day = (clock.day_length as f64 / clock.tick_length.max(f64::EPSILON)).round() as u64;
+        control.fast_forward(day);
+    }
+}
+
+/// System showing the date and how the simulation advances in the
+/// window title
+pub fn show_sim_status(
+    control: Res<SimulationControl>,
+    clock: Res<SimClock>,
+    mut windows: Query<&mut Window>,
+) {
+    let status = match control.mode() {
+        SimMode::Paused if control.pending_steps() > 0 => format!("stepping, {} ticks left", control.pending_steps()),
+        SimMode::Paused => "paused".to_string(),
+        SimMode::Running => format!("{}x", control.speed()),
+        SimMode::MaxSpeed => match control.fast_forward_remaining() {
+            Some(left) => format!("fast-forward, {} ticks left", left),
+            None => format!("max speed, {} ticks per frame", control.ticks_last_frame()),
+        },
+    };
+    let title = format!(
+        "{} - year {}, {:?} day {} {:02}:{:02} ({:?}) - {}",
+        WINDOW_TITLE,
+        clock.year() + 1,
+        clock.season(),
+        clock.date().day_of_year + 1,
+        clock.hour(),
+        clock.minute(),
+        clock.phase(),
+        status
+    );
+    for mut window in windows.iter_mut() {
+        if window.title != title {
+            window.title.clone_from(&title);
+        }
+    }
+}
+
+/// Plugin adding runtime control of the simulation
+pub struct SimControlPlugin;
+
+impl Plugin for SimControlPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_event::<SimCommand>()
+            .init_resource::<SimulationControl>()
+            .add_systems(First, (apply_sim_commands, sync_virtual_time).chain().before(TimeSystem))
+            .add_systems(RunFixedMainLoop, run_controlled_ticks.in_set(RunFixedMainLoopSystem::FixedMainLoop));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+
+    #[test]
+    fn test_commands_change_mode() {
+        let mut control = SimulationControl::default();
+        control.set_speed(10.0);
+        control.step(3);
+        assert!(control.is_paused());
+        assert_eq!(control.speed(), 10.0);
+        assert_eq!(control.pending_steps(), 3);
+
+        control.apply(SimCommand::TogglePause);
+        assert_eq!(control.mode(), SimMode::Running);
+        assert_eq!(control.pending_steps(), 0);
+
+        control.fast_forward(2);
+        assert!(control.take_tick(Duration::ZERO));
+        assert!(control.take_tick(Duration::ZERO));
+        assert!(!control.take_tick(Duration::ZERO));
+        assert!(control.is_paused());
+
+        control.apply(SimCommand::MaxSpeed);
+        assert!(!control.take_tick(control.frame_budget));
+        control.set_speed(f64::NAN);
+        assert_eq!(control.speed(), 10.0);
+    }
+
+    #[derive(Resource, Default)]
+    struct Ticks(u32);
+
+    #[test]
+    fn test_steps_run_fixed_ticks_while_paused() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugins(SimControlPlugin)
+            .init_resource::<Ticks>()
+            .add_systems(FixedUpdate, |mut ticks: ResMut<Ticks>| ticks.0 += 1);
+
+        app.world_mut().send_event(SimCommand::Step(3));
+        app.update();
+        app.update();
+        assert_eq!(app.world().resource::<Ticks>().0, 3);
+        assert!(app.world().resource::<SimulationControl>().is_paused());
+        assert_eq!(app.world().resource::<Time<Fixed>>().elapsed(), Time::<Fixed>::default().timestep() * 3);
+    }
+
+    #[test]
+    fn test_keys_control_the_simulation() {
+        let mut world = World::new();
+        world.init_resource::<SimulationControl>();
+        world.insert_resource(SimClock::default());
+        world.init_resource::<ButtonInput<KeyCode>>();
+        let window = world.spawn(Window::default()).id();
+        let press = |world: &mut World, key: KeyCode| {
+            let mut keys = world.resource_mut::<ButtonInput<KeyCode>>();
+            keys.clear();
+            keys.release_all();
+            keys.press(key);
+            world.run_system_once(keyboard_sim_controls).unwrap();
+            world.run_system_once(show_sim_status).unwrap();
+        };
+
+        press(&mut world, KeyCode::Space);
+        assert!(world.resource::<SimulationControl>().is_paused());
+        assert!(world.get::<Window>(window).unwrap().title.ends_with("(Night) - paused"));
+
+        press(&mut world, KeyCode::Digit3);
+        assert_eq!(world.resource::<SimulationControl>().speed(), 4.0);
+        press(&mut world, KeyCode::Space);
+        assert_eq!(world.get::<Window>(window).unwrap().title, "Neo Simulation - year 1, Spring day 1 00:00 (Night) - 4x");
+
+        // A day is 1440 seconds of 1/60 second ticks
+        press(&mut world, KeyCode::KeyF);
+        assert_eq!(world.resource::<SimulationControl>().fast_forward_remaining(), Some(86_400));
+    }
+}
@@ -0,0 +1,436 @@
+use bevy::prelude::*;
+use std::collections::{HashMap, VecDeque};
+use crate::agents::agent::Agent;
+use crate::engine::weather::WeatherSystem;
+use crate::engine::weather_grid::WeatherGrid;
+use crate::world::chunk::ChunkCoord;
+
+/// Name of the agent skill used when reading forecasts
+pub const FORECASTING_SKILL: &str = "forecasting";
+
+/// Forecasting skill level at which an agent reads forecasts perfectly
+const MASTER_FORECASTING: f32 = 5.0;
+
+/// Memory key under which agents keep their outlook for the next day
+pub const OUTLOOK_MEMORY: &str = "weather_outlook";
+
+/// Precipitation in mm/hour that counts as rain
+const RAIN_THRESHOLD: f32 = 0.1;
+
+/// Verified forecasts needed before measured errors replace the configured spread
+const MIN_VERIFIED: u32 = 10;
+
+/// Most forecast points kept waiting for verification
+const MAX_PENDING: usize = 10_000;
+
+/// Typical forecast error of each weather variable, one standard deviation
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ForecastSpread {
+    pub temperature: f32,
+    pub humidity: f32,
+    pub precipitation: f32,
+    pub wind_speed: f32,
+}
+
+impl ForecastSpread {
+    fn scaled(self, factor: f32) -> Self {
+        Self {
+            temperature: self.temperature * factor,
+            humidity: self.humidity * factor,
+            precipitation: self.precipitation * factor,
+            wind_speed: self.wind_speed * factor,
+        }
+    }
+
+    fn plus(self, other: Self) -> Self {
+        Self {
+            temperature: self.temperature + other.temperature,
+            humidity: self.humidity + other.humidity,
+            precipitation: self.precipitation + other.precipitation,
+            wind_speed: self.wind_speed + other.wind_speed,
+        }
+    }
+}
+
+/// Configuration of the weather forecaster
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ForecastConfig {
+    /// How far ahead forecasts reach, in days
+    pub horizon_days: f64,
+    /// Days between forecast points
+    pub interval_days: f64,
+    /// Days between new forecasts
+    pub refresh_days: f64,
+    /// Spread of a forecast for the present moment
+    pub initial_spread: ForecastSpread,
+    /// Spread added per day of lead time
+    pub spread_per_day: ForecastSpread,
+}
+
+impl Default for ForecastConfig {
+    fn default() -> Self {
+        Self {
+            horizon_days: 3.0,
+            interval_days: 0.25,
+            refresh_days: 0.25,
+            initial_spread: ForecastSpread {
+                temperature: 0.5,
+                humidity: 0.02,
+                precipitation: 0.2,
+                wind_speed: 0.3,
+            },
+            spread_per_day: ForecastSpread {
+                temperature: 1.5,
+                humidity: 0.05,
+                precipitation: 1.0,
+                wind_speed: 1.0,
+            },
+        }
+    }
+}
+
+/// Expected weather in a region at one time, with its uncertainty
+#[derive(Debug, Clone, PartialEq)]
+pub struct ForecastPoint {
+    /// Simulation seconds the point is for
+    pub time: f64,
+    /// Simulation seconds between issuing the forecast and `time`
+    pub lead: f64,
+    pub expected: WeatherSystem,
+    pub spread: ForecastSpread,
+}
+
+impl ForecastPoint {
+    /// Probability of at least light rain
+    pub fn chance_of_rain(&self) -> f32 {
+        if self.spread.precipitation <= 0.0 {
+            return if self.expected.precipitation > RAIN_THRESHOLD { 1.0 } else { 0.0 };
+        }
+        normal_cdf((self.expected.precipitation - RAIN_THRESHOLD) / self.spread.precipitation)
+    }
+
+    /// Likely temperature range, one spread either side
+    pub fn temperature_range(&self) -> (f32, f32) {
+        (
+            self.expected.temperature - self.spread.temperature,
+            self.expected.temperature + self.spread.temperature,
+        )
+    }
+}
+
+/// Logistic approximation of the standard normal distribution function
+fn normal_cdf(x: f32) -> f32 {
+    1.0 / (1.0 + (-1.702 * x).exp())
+}
+
+/// Forecast for every simulated region, issued at one moment
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Forecast {
+    /// Simulation seconds at which it was issued
+    pub issued_at: f64,
+    regions: HashMap<ChunkCoord, Vec<ForecastPoint>>,
+}
+
+impl Forecast {
+    /// Forecast points for a region, nearest first
+    pub fn region(&self, region: ChunkCoord) -> &[ForecastPoint] {
+        self.regions.get(&region).map_or(&[], Vec::as_slice)
+    }
+
+    /// The point for a region closest to a time
+    pub fn at(&self, region: ChunkCoord, time: f64) -> Option<&ForecastPoint> {
+        self.region(region)
+            .iter()
+            .min_by(|a, b| (a.time - time).abs().total_cmp(&(b.time - time).abs()))
+    }
+}
+
+/// Accumulated errors of verified forecasts at one lead time
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct LeadVerification {
+    /// Lead time in simulation seconds
+    pub lead: f64,
+    pub count: u32,
+    /// Sums of absolute errors
+    pub total_error: ForecastSpread,
+}
+
+impl LeadVerification {
+    /// Mean absolute error of each variable
+    pub fn mean_error(&self) -> ForecastSpread {
+        self.total_error.scaled(1.0 / self.count.max(1) as f32)
+    }
+}
+
+/// A forecast point waiting for its time to come
+#[derive(Debug, Clone)]
+struct PendingVerification {
+    region: ChunkCoord,
+    lead_index: usize,
+    time: f64,
+    expected: WeatherSystem,
+}
+
+/// Resource issuing weather forecasts and checking them against the
+/// weather that actually happened
+///
+/// Each forecast runs a clone of the `WeatherGrid` ahead with its own
+/// random numbers, so it follows the same physics without knowing the
+/// real weather's future noise. Spread grows with lead time; once enough
+/// forecasts have been verified, the measured error at each lead time is
+/// used when it is larger than the configured spread.
+#[derive(Debug, Default, Resource)]
+pub struct WeatherForecaster {
+    pub config: ForecastConfig,
+    forecast: Option<Forecast>,
+    pending: VecDeque<PendingVerification>,
+    verification: Vec<LeadVerification>,
+    issued: u64,
+}
+
+impl WeatherForecaster {
+    /// The latest forecast, if any has been issued
+    pub fn forecast(&self) -> Option<&Forecast> {
+        self.forecast.as_ref()
+    }
+
+    /// The latest forecast point for a region closest to a time
+    pub fn at(&self, region: ChunkCoord, time: f64) -> Option<&ForecastPoint> {
+        self.forecast.as_ref()?.at(region, time)
+    }
+
+    /// The latest forecast point for a world position closest to a time
+    pub fn at_position(&self, grid: &WeatherGrid, position: Vec2, time: f64) -> Option<&ForecastPoint> {
+        self.at(grid.region_at(position), time)
+    }
+
+    /// Errors of verified forecasts by lead time, shortest lead first
+    #[cfg(test)]
+    pub fn verification(&self) -> &[LeadVerification] {
+        &self.verification
+    }
+
+    /// Spread expected at a lead time, with `day` the length of a day in
+    /// simulation seconds
+    pub fn spread(&self, lead: f64, day: f64) -> ForecastSpread {
+        let configured = self
+            .config
+            .initial_spread
+            .plus(self.config.spread_per_day.scaled((lead / day.max(1.0)) as f32));
+        let index = (lead / (self.config.interval_days * day).max(1.0)).round() as usize;
+        match self.verification.get(index) {
+            Some(verified) if verified.count >= MIN_VERIFIED => {
+                let measured = verified.mean_error();
+                ForecastSpread {
+                    temperature: configured.temperature.max(measured.temperature),
+                    humidity: configured.humidity.max(measured.humidity),
+                    precipitation: configured.precipitation.max(measured.precipitation),
+                    wind_speed: configured.wind_speed.max(measured.wind_speed),
+                }
+            }
+            _ => configured,
+        }
+    }
+
+    /// Runs the model ahead from the grid's current state and stores the result
+    pub fn issue(&mut self, grid: &WeatherGrid) -> &Forecast {
+        let issued_at = grid.elapsed();
+        let day = grid.clock().day_length as f64;
+        let mut model = grid.clone();
+        model.reseed(0x5eed_f0ca ^ self.issued);
+        self.issued += 1;
+
+        let interval = (self.config.interval_days * day).max(model.config.step as f64);
+        let points = (self.config.horizon_days * day / interval).floor() as usize;
+        let mut regions: HashMap<ChunkCoord, Vec<ForecastPoint>> = HashMap::new();
+        for index in 1..=points {
+            model.run_for(interval);
+            let lead = model.elapsed() - issued_at;
+            let spread = self.spread(lead, day);
+            for (region, expected) in model.cells() {
+                regions.entry(region).or_default().push(ForecastPoint {
+                    time: model.elapsed(),
+                    lead,
+                    expected: expected.clone(),
+                    spread,
+                });
+                if self.pending.len() < MAX_PENDING {
+                    self.pending.push_back(PendingVerification {
+                        region,
+                        lead_index: index,
+                        time: model.elapsed(),
+                        expected: expected.clone(),
+                    });
+                }
+            }
+        }
+
+        self.forecast.insert(Forecast { issued_at, regions })
+    }
+
+    /// Compares forecast points whose time has come with the grid
+    pub fn verify(&mut self, grid: &WeatherGrid) {
+        let now = grid.elapsed();
+        let interval = self.config.interval_days * grid.clock().day_length as f64;
+        let mut remaining = VecDeque::with_capacity(self.pending.len());
+        for pending in self.pending.drain(..) {
+            if pending.time > now {
+                remaining.push_back(pending);
+                continue;
+            }
+            let Some(actual) = grid.cell(pending.region) else {
+                continue;
+            };
+            if self.verification.len() <= pending.lead_index {
+                self.verification.resize_with(pending.lead_index + 1, Default::default);
+            }
+            let stats = &mut self.verification[pending.lead_index];
+            stats.lead = pending.lead_index as f64 * interval;
+            stats.count += 1;
+            stats.total_error = stats.total_error.plus(ForecastSpread {
+                temperature: (actual.temperature - pending.expected.temperature).abs(),
+                humidity: (actual.humidity - pending.expected.humidity).abs(),
+                precipitation: (actual.precipitation - pending.expected.precipitation).abs(),
+                wind_speed: (actual.wind_speed - pending.expected.wind_speed).abs(),
+            });
+        }
+        self.pending = remaining;
+    }
+
+    /// The forecast as an agent understands it
+    ///
+    /// Agents without the forecasting skill expect the weather to stay as
+    /// it is now; with practice their expectation approaches the model's
+    /// and their uncertainty shrinks to the model's spread.
+    pub fn for_agent(&self, agent: &Agent, grid: &WeatherGrid, time: f64) -> Option<ForecastPoint> {
+        let current = grid.at_position(agent.position)?;
+        let point = self.at_position(grid, agent.position, time)?;
+        let skill = (agent.skills.get(FORECASTING_SKILL).copied().unwrap_or(0.0) / MASTER_FORECASTING).clamp(0.0, 1.0);
+        Some(ForecastPoint {
+            expected: blend(current, &point.expected, skill),
+            spread: point.spread.scaled(2.0 - skill),
+            ..point.clone()
+        })
+    }
+}
+
+/// Interpolates between two sets of conditions
+fn blend(from: &WeatherSystem, to: &WeatherSystem, t: f32) -> WeatherSystem {
+    let lerp = |a: f32, b: f32| a + (b - a) * t;
+    let mut weather = WeatherSystem {
+        temperature: lerp(from.temperature, to.temperature),
+        humidity: lerp(from.humidity, to.humidity),
+        pressure: lerp(from.pressure, to.pressure),
+        precipitation: lerp(from.precipitation, to.precipitation),
+        cloud_cover: lerp(from.cloud_cover, to.cloud_cover),
+        ..Default::default()
+    };
+    weather.set_wind(from.wind().lerp(to.wind(), t));
+    weather
+}
+
+/// System that verifies past forecasts and issues new ones as the
+/// weather advances
+pub fn update_forecast(grid: Res<WeatherGrid>, mut forecaster: ResMut<WeatherForecaster>) {
+    forecaster.verify(&grid);
+    let due = forecaster
+        .forecast()
+        .is_none_or(|forecast| {
+            grid.elapsed() - forecast.issued_at >= forecaster.config.refresh_days * grid.clock().day_length as f64
+        });
+    if due {
+        forecaster.issue(&grid);
+    }
+}
+
+/// System where agents read the forecast for the next day into memory
+///
+/// What they remember depends on their forecasting skill, so unskilled
+/// agents plan with little more than today's weather.
+pub fn remember_weather_outlook(grid: Res<WeatherGrid>, forecaster: Res<WeatherForecaster>, mut agents: Query<&mut Agent>) {
+    let tomorrow = grid.elapsed() + grid.clock().day_length as f64;
+    for mut agent in agents.iter_mut() {
+        let Some(outlook) = forecaster.for_agent(&agent, &grid, tomorrow) else {
+            continue;
+        };
+        let (low, high) = outlook.temperature_range();
+        let note = format!(
+            "Tomorrow: {:.0}% chance of rain, {:.0} to {:.0}°C",
+            outlook.chance_of_rain() * 100.0,
+            low,
+            high
+        );
+        if agent.memory.get(OUTLOOK_MEMORY) != Some(&note) {
+            agent.memory.insert(OUTLOOK_MEMORY.to_string(), note);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+    use crate::engine::weather_grid::WeatherGridConfig;
+
+    #[test]
+    fn test_forecast_runs_ahead_without_touching_the_grid() {
+        let mut grid = WeatherGrid::new(WeatherGridConfig::default(), 5);
+        grid.sync((0..3).map(|x| ChunkCoord::new(x * 4, 0)));
+        let before: Vec<WeatherSystem> = grid.cells().map(|(_, cell)| cell.clone()).collect();
+
+        let mut forecaster = WeatherForecaster::default();
+        let forecast = forecaster.issue(&grid).clone();
+
+        let after: Vec<WeatherSystem> = grid.cells().map(|(_, cell)| cell.clone()).collect();
+        assert_eq!(before, after);
+        assert_eq!(forecast.regions.len(), 3);
+
+        let points = forecast.region(ChunkCoord::new(1, 0));
+        assert_eq!(points.len(), 12);
+        assert!(points.windows(2).all(|pair| pair[0].lead < pair[1].lead));
+        assert!(points[11].spread.temperature > points[0].spread.temperature);
+        assert_eq!(forecast.at(ChunkCoord::new(1, 0), 400.0).unwrap().time, 360.0);
+
+        // The real weather follows the same physics with different noise
+        grid.run_for(360.0);
+        forecaster.verify(&grid);
+        assert_eq!(forecaster.verification()[1].count, 3);
+        assert!(forecaster.verification()[1].mean_error().temperature < 2.0);
+    }
+
+    #[test]
+    fn test_agent_skill_sharpens_forecasts() {
+        let grid = WeatherGrid::default();
+        let mut forecaster = WeatherForecaster::default();
+        forecaster.issue(&grid);
+        let time = 2.0 * grid.clock().day_length as f64;
+        let model = forecaster.at(ChunkCoord::new(0, 0), time).unwrap().clone();
+
+        let mut agent = Agent::default();
+        let novice = forecaster.for_agent(&agent, &grid, time).unwrap();
+        assert_eq!(novice.expected.temperature, grid.cell(ChunkCoord::new(0, 0)).unwrap().temperature);
+        assert_eq!(novice.spread.temperature, model.spread.temperature * 2.0);
+
+        agent.skills.insert(FORECASTING_SKILL.to_string(), MASTER_FORECASTING);
+        let expert = forecaster.for_agent(&agent, &grid, time).unwrap();
+        assert_eq!(expert.spread, model.spread);
+        assert!((expert.expected.temperature - model.expected.temperature).abs() < 1e-4);
+        assert!((expert.expected.precipitation - model.expected.precipitation).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_agents_remember_the_outlook() {
+        let mut world = World::new();
+        world.init_resource::<WeatherGrid>();
+        world.init_resource::<WeatherForecaster>();
+        let agent = world.spawn(Agent::default()).id();
+
+        world.run_system_once(remember_weather_outlook).unwrap();
+        assert!(!world.get::<Agent>(agent).unwrap().memory.contains_key(OUTLOOK_MEMORY));
+
+        world.run_system_once(update_forecast).unwrap();
+        world.run_system_once(remember_weather_outlook).unwrap();
+        let note = &world.get::<Agent>(agent).unwrap().memory[OUTLOOK_MEMORY];
+        assert!(note.starts_with("Tomorrow: ") && note.contains("chance of rain"));
+    }
+}
@@ -1,9 +1,18 @@
 pub mod tick;
+pub mod tick_rates;
 pub mod time;
+pub mod control;
+pub mod scheduler;
+pub mod pipeline;
 pub mod weather;
+pub mod weather_grid;
+pub mod storms;
+pub mod forecast;
+pub mod weather_effects;
+pub mod weather_history;
+pub mod weather_scenario;
 pub mod memory;
 
 // Re-export commonly used types
-pub use time::update_time_system;
 pub use weather::WeatherPlugin;
 pub use tick::clear_agent_tick_events;
@@ -0,0 +1,106 @@
+use bevy::ecs::schedule::{LogLevel, ScheduleBuildSettings, ScheduleLabel};
+use bevy::prelude::*;
+use crate::engine::time::{advance_clock, SimClock};
+
+/// Start of a tick: advances the clock, fires timers and brings the
+/// world and its environment up to the new time
+#[derive(ScheduleLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PreTick;
+
+/// Systems at the start of `PreTick` that advance the clock and fire
+/// timers; other `PreTick` systems run after them
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AdvanceTime;
+
+/// Agents observe the world as it is at the start of the tick
+#[derive(ScheduleLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Perception;
+
+/// Agents decide what to do
+#[derive(ScheduleLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Decide;
+
+/// Agents carry out their jobs
+#[derive(ScheduleLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Act;
+
+/// Outcomes that depend on everyone's actions: flows, trades, regrowth
+/// and water
+#[derive(ScheduleLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Resolve;
+
+/// End of a tick: records history and reports what changed
+#[derive(ScheduleLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PostTick;
+
+/// Runs a full simulation tick, one phase after another
+///
+/// Runs in `FixedUpdate`, so the simulation advances in fixed steps and
+/// `Res<Time>` holds the fixed timestep inside every phase; results don't
+/// depend on the frame rate. Rendering and other per-frame work stays in
+/// `Update`.
+pub fn run_tick_pipeline(world: &mut World) {
+    world.run_schedule(PreTick);
+    world.run_schedule(Perception);
+    world.run_schedule(Decide);
+    world.run_schedule(Act);
+    world.run_schedule(Resolve);
+    world.run_schedule(PostTick);
+}
+
+/// Plugin running the simulation's tick phases in `FixedUpdate`
+///
+/// Systems are added to a phase with `app.add_systems(Act, ...)`. Debug
+/// builds warn about systems in the same phase that conflict without a
+/// defined order, since their order could change between runs.
+pub struct TickPipelinePlugin;
+
+impl Plugin for TickPipelinePlugin {
+    fn build(&self, app: &mut App) {
+        for label in [PreTick.intern(), Perception.intern(), Decide.intern(), Act.intern(), Resolve.intern(), PostTick.intern()] {
+            let mut schedule = Schedule::new(label);
+            if cfg!(debug_assertions) {
+                schedule.set_build_settings(ScheduleBuildSettings {
+                    ambiguity_detection: LogLevel::Warn,
+                    ..default()
+                });
+            }
+            app.add_schedule(schedule);
+        }
+
+        app
+            .init_resource::<SimClock>()
+            .add_systems(PreTick, advance_clock.in_set(AdvanceTime))
+            .add_systems(FixedUpdate, run_tick_pipeline);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Resource, Default)]
+    struct Phases(Vec<(u64, &'static str)>);
+
+    fn record(phase: &'static str) -> impl FnMut(Res<SimClock>, ResMut<Phases>) {
+        move |clock, mut phases| phases.0.push((clock.tick(), phase))
+    }
+
+    #[test]
+    fn test_phases_run_in_order_once_per_tick() {
+        let mut app = App::new();
+        app.add_plugins(TickPipelinePlugin)
+            .init_resource::<Phases>()
+            .add_systems(PostTick, record("post"))
+            .add_systems(Act, record("act"))
+            .add_systems(PreTick, record("pre").after(AdvanceTime))
+            .add_systems(Decide, record("decide"));
+
+        app.world_mut().run_schedule(FixedUpdate);
+        app.world_mut().run_schedule(FixedUpdate);
+        assert_eq!(
+            app.world().resource::<Phases>().0,
+            [(1, "pre"), (1, "decide"), (1, "act"), (1, "post"), (2, "pre"), (2, "decide"), (2, "act"), (2, "post")],
+        );
+    }
+}
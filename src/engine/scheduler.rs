@@ -0,0 +1,372 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BinaryHeap, HashMap};
+use uuid::Uuid;
+use crate::engine::pipeline::{AdvanceTime, PreTick};
+use crate::engine::time::{advance_clock, DayPhase, Season, SimClock};
+
+/// Name of the timer waking each agent at dawn
+pub const DAWN_TIMER: &str = "dawn";
+
+/// Name of the timers marking the start of each season
+pub const SEASON_TIMER: &str = "season_start";
+
+/// Identifies a timer in the `SimScheduler`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct TimerId(pub u64);
+
+/// What a timer announces when it fires
+///
+/// Timers carry data rather than closures so they can be saved; code
+/// reacts to them by reading `TimerFired` or registering a callback for
+/// the name in `TimerCallbacks`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScheduledEvent {
+    pub name: String,
+    /// Agent the timer belongs to, if any
+    pub agent: Option<Uuid>,
+}
+
+impl ScheduledEvent {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self { name: name.into(), agent: None }
+    }
+
+    pub fn for_agent(mut self, agent: Uuid) -> Self {
+        self.agent = Some(agent);
+        self
+    }
+}
+
+/// A recurring point in the calendar
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum CalendarRule {
+    /// Every day at an hour, fractions allowed
+    Daily { hour: f32 },
+    /// Every day at the start of a phase, e.g. dawn
+    Phase(DayPhase),
+    /// Every year on a day, fractions allowed
+    Yearly { day_of_year: f32 },
+    /// Every year at the start of a season; spring and autumn start at the
+    /// equinoxes, summer and winter at the solstices
+    SeasonStart(Season),
+}
+
+impl CalendarRule {
+    /// Day, counted from the calendar's epoch, of the first occurrence
+    /// strictly after `days`
+    pub fn next_after(&self, clock: &SimClock, days: f64) -> f64 {
+        let year = clock.days_per_year.max(1) as f64;
+        let (offset, period) = match *self {
+            Self::Daily { hour } => (hour as f64 / 24.0, 1.0),
+            Self::Phase(phase) => (phase.start_hour() as f64 / 24.0, 1.0),
+            Self::Yearly { day_of_year } => (day_of_year as f64, year),
+            Self::SeasonStart(season) => (season.index() as f64 * year / 4.0, year),
+        };
+        offset + (((days - offset) / period).floor() + 1.0) * period
+    }
+}
+
+/// How a timer repeats after firing
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Repeat {
+    Never,
+    /// Again this many ticks later
+    Ticks(u64),
+    /// Again at the next occurrence of the rule
+    Calendar(CalendarRule),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct ScheduledTimer {
+    event: ScheduledEvent,
+    due: u64,
+    repeat: Repeat,
+    /// Matches the queue entry that is current for this timer
+    sequence: u64,
+}
+
+/// Entry of the scheduler's queue, ordered so the earliest due is popped
+/// first and timers due on the same tick fire in the order they were queued
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct QueueEntry {
+    due: u64,
+    sequence: u64,
+    id: TimerId,
+}
+
+impl Ord for QueueEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (other.due, other.sequence).cmp(&(self.due, self.sequence))
+    }
+}
+
+impl PartialOrd for QueueEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Resource scheduling events at future simulation ticks
+///
+/// Timers are kept in a priority queue keyed by the tick they are due, so
+/// checking for due timers only looks at the ones that fire. Times given in
+/// seconds or calendar terms are converted to ticks with the `SimClock`
+/// when a timer is queued. Cancelled timers are dropped from the queue
+/// lazily when they reach its front. The whole scheduler is serialisable
+/// so timers survive save and load.
+#[derive(Resource, Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SimScheduler {
+    timers: BTreeMap<TimerId, ScheduledTimer>,
+    queue: BinaryHeap<QueueEntry>,
+    next_id: u64,
+    next_sequence: u64,
+}
+
+impl SimScheduler {
+    // One-shot and tick-based timers, and cancelling, are for scripted
+    // events; nothing in the tree queues those yet, the tests do
+    /// Fires once at a tick, or on the next check if it has passed
+    #[allow(dead_code)]
+    pub fn at_tick(&mut self, tick: u64, event: ScheduledEvent) -> TimerId {
+        self.insert(event, tick, Repeat::Never)
+    }
+
+    /// Fires once after some simulation seconds
+    #[allow(dead_code)]
+    pub fn after(&mut self, clock: &SimClock, seconds: f64, event: ScheduledEvent) -> TimerId {
+        self.insert(event, ticks_after(clock, seconds), Repeat::Never)
+    }
+
+    /// Fires every `ticks` ticks, starting `ticks` from now
+    #[allow(dead_code)]
+    pub fn every_ticks(&mut self, clock: &SimClock, ticks: u64, event: ScheduledEvent) -> TimerId {
+        let ticks = ticks.max(1);
+        self.insert(event, clock.tick() + ticks, Repeat::Ticks(ticks))
+    }
+
+    /// Fires at every occurrence of a calendar rule
+    pub fn every(&mut self, clock: &SimClock, rule: CalendarRule, event: ScheduledEvent) -> TimerId {
+        self.insert(event, next_occurrence(clock, rule), Repeat::Calendar(rule))
+    }
+
+    /// Cancels a timer, returning whether it was still scheduled
+    #[allow(dead_code)]
+    pub fn cancel(&mut self, id: TimerId) -> bool {
+        self.timers.remove(&id).is_some()
+    }
+
+    /// Tick a timer is next due, if it is scheduled
+    #[cfg(test)]
+    pub fn due_at(&self, id: TimerId) -> Option<u64> {
+        self.timers.get(&id).map(|timer| timer.due)
+    }
+
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.timers.len()
+    }
+
+    /// Removes and returns the timers due by the clock's tick, earliest
+    /// first, queueing recurring ones again
+    pub fn take_due(&mut self, clock: &SimClock) -> Vec<TimerFired> {
+        let mut fired = Vec::new();
+        while self.queue.peek().is_some_and(|entry| entry.due <= clock.tick()) {
+            let Some(entry) = self.queue.pop() else {
+                break;
+            };
+            let Some(timer) = self.timers.get(&entry.id) else {
+                continue;
+            };
+            if timer.sequence != entry.sequence {
+                continue;
+            }
+
+            fired.push(TimerFired { id: entry.id, tick: clock.tick(), event: timer.event.clone() });
+            let next = match timer.repeat {
+                Repeat::Never => None,
+                Repeat::Ticks(ticks) => Some(entry.due + ticks),
+                Repeat::Calendar(rule) => Some(next_occurrence(clock, rule)),
+            };
+            match next {
+                Some(due) => self.queue_timer(entry.id, due),
+                None => {
+                    self.timers.remove(&entry.id);
+                }
+            }
+        }
+        fired
+    }
+
+    fn insert(&mut self, event: ScheduledEvent, due: u64, repeat: Repeat) -> TimerId {
+        let id = TimerId(self.next_id);
+        self.next_id += 1;
+        self.timers.insert(id, ScheduledTimer { event, due, repeat, sequence: 0 });
+        self.queue_timer(id, due);
+        id
+    }
+
+    fn queue_timer(&mut self, id: TimerId, due: u64) {
+        let sequence = self.next_sequence;
+        self.next_sequence += 1;
+        if let Some(timer) = self.timers.get_mut(&id) {
+            timer.due = due;
+            timer.sequence = sequence;
+            self.queue.push(QueueEntry { due, sequence, id });
+        }
+    }
+}
+
+/// Tick at which `seconds` of simulation time from now have passed
+fn ticks_after(clock: &SimClock, seconds: f64) -> u64 {
+    if clock.tick_length <= 0.0 {
+        return clock.tick();
+    }
+    clock.tick() + (seconds.max(0.0) / clock.tick_length).ceil() as u64
+}
+
+/// First tick at or after the next occurrence of a calendar rule
+///
+/// Looks from half a tick ahead so an occurrence that is firing now isn't
+/// found again through rounding.
+fn next_occurrence(clock: &SimClock, rule: CalendarRule) -> u64 {
+    let now = clock.days_at(clock.elapsed());
+    let half_tick = clock.tick_length / 2.0 / clock.day_length.max(f32::EPSILON) as f64;
+    let days = rule.next_after(clock, now + half_tick) - now;
+    ticks_after(clock, days * clock.day_length as f64).max(clock.tick() + 1)
+}
+
+/// Event sent when a scheduled timer fires
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct TimerFired {
+    pub id: TimerId,
+    /// Tick at which the timer fired
+    pub tick: u64,
+    pub event: ScheduledEvent,
+}
+
+type TimerCallback = Box<dyn Fn(&mut World, &TimerFired) + Send + Sync>;
+
+/// Resource holding callbacks run when timers with a given name fire
+///
+/// Callbacks are code, so they aren't saved with the scheduler; register
+/// them at startup and they apply to loaded timers as well.
+#[derive(Resource, Default)]
+pub struct TimerCallbacks {
+    callbacks: HashMap<String, Vec<TimerCallback>>,
+}
+
+impl TimerCallbacks {
+    /// Runs `callback` whenever a timer named `name` fires
+    pub fn on(&mut self, name: impl Into<String>, callback: impl Fn(&mut World, &TimerFired) + Send + Sync + 'static) {
+        self.callbacks.entry(name.into()).or_default().push(Box::new(callback));
+    }
+}
+
+/// System that fires the timers due this tick
+///
+/// Sends `TimerFired` for each and runs the callbacks registered for its
+/// name, in the order the timers were due.
+pub fn run_scheduler(world: &mut World) {
+    let fired = world.resource_scope(|world, mut scheduler: Mut<SimScheduler>| {
+        scheduler.take_due(world.resource::<SimClock>())
+    });
+    if fired.is_empty() {
+        return;
+    }
+
+    world.resource_scope(|world, callbacks: Mut<TimerCallbacks>| {
+        for timer in &fired {
+            for callback in callbacks.callbacks.get(&timer.event.name).into_iter().flatten() {
+                callback(world, timer);
+            }
+        }
+    });
+    world.send_event_batch(fired);
+}
+
+/// Startup system queueing the season timers and logging each new season
+pub fn schedule_seasons(
+    clock: Res<SimClock>,
+    mut scheduler: ResMut<SimScheduler>,
+    mut callbacks: ResMut<TimerCallbacks>,
+) {
+    for season in Season::ALL {
+        scheduler.every(&clock, CalendarRule::SeasonStart(season), ScheduledEvent::new(SEASON_TIMER));
+    }
+    callbacks.on(SEASON_TIMER, |world, _| {
+        let clock = world.resource::<SimClock>();
+        info!("{:?} begins in year {}", clock.season(), clock.year() + 1);
+    });
+}
+
+/// Plugin firing scheduled timers right after the clock advances
+pub struct SchedulerPlugin;
+
+impl Plugin for SchedulerPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_event::<TimerFired>()
+            .init_resource::<SimClock>()
+            .init_resource::<SimScheduler>()
+            .init_resource::<TimerCallbacks>()
+            .add_systems(Startup, schedule_seasons)
+            .add_systems(PreTick, run_scheduler.after(advance_clock).in_set(AdvanceTime));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clock_at(ticks: u64) -> SimClock {
+        let mut clock = SimClock::with_tick_length(60.0);
+        for _ in 0..ticks {
+            clock.advance_tick();
+        }
+        clock
+    }
+
+    #[test]
+    fn test_timers_fire_in_order_and_repeat() {
+        let clock = clock_at(0);
+        let mut scheduler = SimScheduler::default();
+        let late = scheduler.at_tick(5, ScheduledEvent::new("late"));
+        scheduler.after(&clock, 120.0, ScheduledEvent::new("soon"));
+        let repeating = scheduler.every_ticks(&clock, 2, ScheduledEvent::new("repeat"));
+        let cancelled = scheduler.at_tick(1, ScheduledEvent::new("cancelled"));
+        assert!(scheduler.cancel(cancelled));
+
+        let names = |fired: Vec<TimerFired>| fired.into_iter().map(|timer| timer.event.name).collect::<Vec<_>>();
+        assert!(scheduler.take_due(&clock_at(1)).is_empty());
+        assert_eq!(names(scheduler.take_due(&clock_at(2))), ["soon", "repeat"]);
+        // Missed repeats catch up
+        assert_eq!(names(scheduler.take_due(&clock_at(6))), ["repeat", "late", "repeat"]);
+        assert_eq!(scheduler.due_at(late), None);
+        assert_eq!(scheduler.due_at(repeating), Some(8));
+        assert_eq!(scheduler.len(), 1);
+    }
+
+    #[test]
+    fn test_calendar_timers_and_save_load() {
+        // One tick per hour
+        let mut clock = SimClock::with_tick_length(60.0);
+        let mut scheduler = SimScheduler::default();
+        let dawn = scheduler.every(&clock, CalendarRule::Phase(DayPhase::Dawn), ScheduledEvent::new("dawn"));
+        let equinox = scheduler.every(&clock, CalendarRule::SeasonStart(Season::Spring), ScheduledEvent::new("equinox"));
+        assert_eq!(scheduler.due_at(dawn), Some(5));
+        assert_eq!(scheduler.due_at(equinox), Some(clock.days_per_year as u64 * 24));
+
+        let saved = ron::to_string(&scheduler).unwrap();
+        let mut loaded: SimScheduler = ron::from_str(&saved).unwrap();
+
+        for _ in 0..5 {
+            clock.advance_tick();
+        }
+        let fired = loaded.take_due(&clock);
+        assert_eq!(fired.len(), 1);
+        assert_eq!(fired[0].id, dawn);
+        assert_eq!(loaded.due_at(dawn), Some(29));
+    }
+}
@@ -0,0 +1,478 @@
+use bevy::prelude::*;
+use rand::Rng;
+use crate::engine::weather::WeatherSystem;
+use crate::engine::weather_grid::WeatherGrid;
+use crate::world::chunk::ChunkCoord;
+
+/// Drift of pressure systems with the prevailing westerlies, in regions per second
+const PREVAILING_DRIFT: Vec2 = Vec2::new(1.0 / 120.0, 0.0);
+/// Most pressure systems alive at once
+const MAX_PRESSURE_SYSTEMS: usize = 6;
+/// Pressure systems spawned per simulated day
+const PRESSURE_SYSTEMS_PER_DAY: f32 = 1.0;
+/// Temperature difference in Celsius between neighbouring cells that makes a front
+const FRONT_GRADIENT: f32 = 4.0;
+
+/// Score a matching cell needs for a new storm to form
+const FORMATION_SCORE: f32 = 0.2;
+/// Intensity at which a storm is mature
+const MATURE_INTENSITY: f32 = 0.5;
+/// Fraction of its peak below which a mature storm starts dissipating
+const DISSIPATION_FRACTION: f32 = 0.6;
+/// Intensity below which a storm has ended
+const END_INTENSITY: f32 = 0.05;
+/// Furthest a storm can move between checks, in regions
+const TRACK_RADIUS: i32 = 2;
+
+/// Names given to storms in order, cycling with a numeric suffix
+const STORM_NAMES: [&str; 26] = [
+    "Ada", "Bram", "Cora", "Dov", "Elin", "Finn", "Gia", "Hugo", "Iris", "Jonas", "Kira", "Leif", "Mara",
+    "Nils", "Opal", "Piet", "Quin", "Runa", "Sven", "Tova", "Ulf", "Vera", "Wren", "Xavi", "Yara", "Zeno",
+];
+
+/// Whether a pressure system is a high or a low
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PressureKind {
+    /// Anticyclone: settled, dry weather
+    High,
+    /// Depression: wind, cloud and rain
+    Low,
+}
+
+/// A moving area of high or low pressure
+///
+/// Pressure systems are part of the weather model: they pull the pressure
+/// of the cells beneath them up or down, which in turn drives the wind.
+/// Lows draw in moisture. They drift east with the prevailing westerlies,
+/// are steered by the wind, and strengthen and fade over their lifetime.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PressureSystem {
+    pub kind: PressureKind,
+    /// Centre in region coordinates
+    pub center: Vec2,
+    /// Pressure anomaly at the centre at full strength, in hPa
+    pub peak_strength: f32,
+    /// Distance in regions at which the anomaly falls to 1/e
+    pub radius: f32,
+    /// Simulation seconds since it formed
+    pub age: f32,
+    /// Simulation seconds it lives for
+    pub lifetime: f32,
+}
+
+impl PressureSystem {
+    /// Current strength, rising over the first quarter of the lifetime and
+    /// fading over the last
+    pub fn strength(&self) -> f32 {
+        let progress = (self.age / self.lifetime.max(1.0)).clamp(0.0, 1.0);
+        let envelope = (progress * 4.0).min(1.0).min((1.0 - progress) * 4.0);
+        self.peak_strength * envelope
+    }
+
+    /// Pressure anomaly in hPa at a region
+    pub fn anomaly(&self, region: ChunkCoord) -> f32 {
+        let distance = Vec2::new(region.x as f32, region.y as f32).distance(self.center);
+        let sign = match self.kind {
+            PressureKind::High => 1.0,
+            PressureKind::Low => -1.0,
+        };
+        sign * self.strength() * (-(distance / self.radius.max(0.1)).powi(2)).exp()
+    }
+
+    pub fn has_ended(&self) -> bool {
+        self.age >= self.lifetime
+    }
+}
+
+/// Spawns, moves and ages the pressure systems of a weather grid
+///
+/// `day` is the length of a day in simulation seconds and `steering`
+/// returns the wind in regions per second at a point.
+pub fn advance_pressure_systems(
+    systems: &mut Vec<PressureSystem>,
+    regions: &[ChunkCoord],
+    dt: f32,
+    day: f32,
+    rng: &mut impl Rng,
+    steering: impl Fn(Vec2) -> Vec2,
+) {
+    for system in systems.iter_mut() {
+        system.center += (PREVAILING_DRIFT + steering(system.center)) * dt;
+        system.age += dt;
+    }
+    systems.retain(|system| !system.has_ended());
+
+    if systems.len() < MAX_PRESSURE_SYSTEMS && !regions.is_empty() && rng.gen::<f32>() < PRESSURE_SYSTEMS_PER_DAY * dt / day {
+        let region = regions[rng.gen_range(0..regions.len())];
+        systems.push(PressureSystem {
+            kind: if rng.gen::<bool>() { PressureKind::High } else { PressureKind::Low },
+            center: Vec2::new(region.x as f32, region.y as f32) + Vec2::new(rng.gen::<f32>() - 0.5, rng.gen::<f32>() - 0.5),
+            peak_strength: rng.gen_range(8.0..25.0),
+            radius: rng.gen_range(1.5..4.0),
+            age: 0.0,
+            lifetime: rng.gen_range(2.0..6.0) * day,
+        });
+    }
+}
+
+/// Whether the cold or the warm air mass is advancing across a front
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FrontKind {
+    Cold,
+    Warm,
+}
+
+/// Boundary between neighbouring cells of very different temperature
+///
+/// Air is lifted where air masses meet, so fronts bring cloud and rain to
+/// both cells.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Front {
+    pub kind: FrontKind,
+    /// Cell on the warm side
+    pub warm: ChunkCoord,
+    /// Cell on the cold side
+    pub cold: ChunkCoord,
+    /// Temperature difference across the front in Celsius
+    pub strength: f32,
+}
+
+/// Finds the fronts between neighbouring cells
+///
+/// A front is cold when the wind across it blows from the cold side.
+pub fn find_fronts<'a>(cells: impl Iterator<Item = (ChunkCoord, &'a WeatherSystem)>, lookup: impl Fn(ChunkCoord) -> Option<&'a WeatherSystem>) -> Vec<Front> {
+    let mut fronts = Vec::new();
+    for (region, cell) in cells {
+        for neighbor in [ChunkCoord::new(region.x + 1, region.y), ChunkCoord::new(region.x, region.y + 1)] {
+            let Some(other) = lookup(neighbor) else {
+                continue;
+            };
+            let difference = cell.temperature - other.temperature;
+            if difference.abs() < FRONT_GRADIENT {
+                continue;
+            }
+            let ((warm, warm_cell), (cold, cold_cell)) = if difference > 0.0 {
+                ((region, cell), (neighbor, other))
+            } else {
+                ((neighbor, other), (region, cell))
+            };
+            let towards_warm = Vec2::new((warm.x - cold.x) as f32, (warm.y - cold.y) as f32);
+            let wind = (warm_cell.wind() + cold_cell.wind()) / 2.0;
+            fronts.push(Front {
+                kind: if wind.dot(towards_warm) > 0.0 { FrontKind::Cold } else { FrontKind::Warm },
+                warm,
+                cold,
+                strength: difference.abs(),
+            });
+        }
+    }
+    fronts
+}
+
+/// Kind of extreme weather a storm represents
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum StormKind {
+    Thunderstorm,
+    Blizzard,
+    Hurricane,
+    Heatwave,
+    Drought,
+}
+
+impl StormKind {
+    pub const ALL: [StormKind; 5] = [
+        StormKind::Thunderstorm,
+        StormKind::Blizzard,
+        StormKind::Hurricane,
+        StormKind::Heatwave,
+        StormKind::Drought,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            StormKind::Thunderstorm => "Thunderstorm",
+            StormKind::Blizzard => "Blizzard",
+            StormKind::Hurricane => "Hurricane",
+            StormKind::Heatwave => "Heatwave",
+            StormKind::Drought => "Drought",
+        }
+    }
+
+    /// How strongly a cell shows this kind of weather, from 0.0 to 1.0
+    ///
+    /// `anomaly` is how much warmer the cell is than its climate.
+    pub fn score(self, weather: &WeatherSystem, anomaly: f32) -> f32 {
+        let score = match self {
+            StormKind::Thunderstorm if weather.temperature > 12.0 => (weather.precipitation - 3.0) / 10.0,
+            StormKind::Blizzard if weather.temperature < 0.0 && weather.precipitation > 1.0 => {
+                (weather.wind_speed - 6.0) / 10.0 + (weather.precipitation - 1.0) / 8.0
+            }
+            StormKind::Hurricane if weather.temperature > 24.0 && weather.wind_speed > 12.0 => {
+                (990.0 - weather.pressure) / 40.0 + (weather.wind_speed - 12.0) / 16.0
+            }
+            StormKind::Heatwave => (anomaly - 5.0) / 10.0,
+            StormKind::Drought if weather.precipitation < 0.1 => (0.3 - weather.humidity) / 0.2,
+            _ => 0.0,
+        };
+        score.clamp(0.0, 1.0)
+    }
+}
+
+/// Stage in a storm's lifecycle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StormStage {
+    Forming,
+    Mature,
+    Dissipating,
+    Ended,
+}
+
+/// Component for a named spell of extreme weather
+///
+/// Storms are found in the weather grid rather than simulated separately:
+/// each check they follow the strongest matching cell near their last
+/// position, and their intensity is how strongly that cell matches.
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct Storm {
+    pub name: String,
+    pub kind: StormKind,
+    pub stage: StormStage,
+    /// 0.0 to 1.0
+    pub intensity: f32,
+    pub peak_intensity: f32,
+    /// Regions the storm has passed through, oldest first
+    pub track: Vec<ChunkCoord>,
+    /// Simulation seconds at which it formed
+    pub formed_at: f64,
+}
+
+impl Storm {
+    /// Region the storm is over
+    pub fn region(&self) -> ChunkCoord {
+        *self.track.last().expect("storms are created with a position")
+    }
+}
+
+/// Event fired when a storm forms, changes stage or ends
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct StormEvent {
+    pub storm: Entity,
+    pub name: String,
+    pub kind: StormKind,
+    pub stage: StormStage,
+    pub intensity: f32,
+    pub region: ChunkCoord,
+}
+
+/// Resource tracking when storms were last checked and naming new ones
+#[derive(Resource, Debug, Default)]
+pub struct StormTracker {
+    last_checked: Option<f64>,
+    named: usize,
+}
+
+impl StormTracker {
+    fn next_name(&mut self, kind: StormKind) -> String {
+        let name = STORM_NAMES[self.named % STORM_NAMES.len()];
+        let cycle = self.named / STORM_NAMES.len();
+        self.named += 1;
+        if cycle == 0 {
+            format!("{} {}", kind.label(), name)
+        } else {
+            format!("{} {} {}", kind.label(), name, cycle + 1)
+        }
+    }
+}
+
+fn within_track_radius(a: ChunkCoord, b: ChunkCoord) -> bool {
+    (a.x - b.x).abs() <= TRACK_RADIUS && (a.y - b.y).abs() <= TRACK_RADIUS
+}
+
+/// System that forms, tracks and ends storms after each weather step
+pub fn storm_system(
+    mut commands: Commands,
+    grid: Res<WeatherGrid>,
+    mut tracker: ResMut<StormTracker>,
+    mut storms: Query<(Entity, &mut Storm)>,
+    mut events: EventWriter<StormEvent>,
+) {
+    if tracker.last_checked.is_some_and(|checked| checked >= grid.elapsed()) {
+        return;
+    }
+    tracker.last_checked = Some(grid.elapsed());
+
+    let mut storms: Vec<(Entity, Mut<Storm>)> = storms.iter_mut().collect();
+    storms.sort_by_key(|(entity, _)| *entity);
+
+    for kind in StormKind::ALL {
+        let mut candidates: Vec<(ChunkCoord, f32)> = grid
+            .cells()
+            .map(|(region, weather)| (region, kind.score(weather, weather.temperature - grid.base_temperature(region))))
+            .filter(|(_, score)| *score > 0.0)
+            .collect();
+
+        // Existing storms follow the strongest matching cell nearby
+        for (entity, storm) in storms.iter_mut().filter(|(_, storm)| storm.kind == kind) {
+            let previous_stage = storm.stage;
+            let best = candidates
+                .iter()
+                .filter(|(region, _)| within_track_radius(*region, storm.region()))
+                .max_by(|a, b| a.1.total_cmp(&b.1))
+                .copied();
+            match best {
+                Some((region, score)) => {
+                    candidates.retain(|(candidate, _)| !within_track_radius(*candidate, region));
+                    if region != storm.region() {
+                        storm.track.push(region);
+                    }
+                    storm.intensity = score;
+                    storm.peak_intensity = storm.peak_intensity.max(score);
+                }
+                None => storm.intensity *= 0.5,
+            }
+
+            storm.stage = if storm.intensity < END_INTENSITY {
+                StormStage::Ended
+            } else {
+                match storm.stage {
+                    StormStage::Forming if storm.intensity >= MATURE_INTENSITY => StormStage::Mature,
+                    StormStage::Forming | StormStage::Mature if storm.intensity < storm.peak_intensity * DISSIPATION_FRACTION => {
+                        StormStage::Dissipating
+                    }
+                    stage => stage,
+                }
+            };
+            if storm.stage != previous_stage {
+                if storm.stage == StormStage::Mature {
+                    info!("{} has reached full strength over region ({}, {})", storm.name, storm.region().x, storm.region().y);
+                }
+                events.send(StormEvent {
+                    storm: *entity,
+                    name: storm.name.clone(),
+                    kind,
+                    stage: storm.stage,
+                    intensity: storm.intensity,
+                    region: storm.region(),
+                });
+            }
+            if storm.stage == StormStage::Ended {
+                commands.entity(*entity).despawn();
+            }
+        }
+
+        // Strong matches away from any storm form new ones
+        candidates.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        while let Some(&(region, score)) = candidates.first() {
+            if score < FORMATION_SCORE {
+                break;
+            }
+            candidates.retain(|(candidate, _)| !within_track_radius(*candidate, region));
+            let storm = Storm {
+                name: tracker.next_name(kind),
+                kind,
+                stage: if score >= MATURE_INTENSITY { StormStage::Mature } else { StormStage::Forming },
+                intensity: score,
+                peak_intensity: score,
+                track: vec![region],
+                formed_at: grid.elapsed(),
+            };
+            info!("{} is forming over region ({}, {})", storm.name, region.x, region.y);
+            let event = StormEvent {
+                storm: Entity::PLACEHOLDER,
+                name: storm.name.clone(),
+                kind,
+                stage: storm.stage,
+                intensity: score,
+                region,
+            };
+            let entity = commands.spawn(storm).id();
+            events.send(StormEvent { storm: entity, ..event });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::time::DAY_LENGTH;
+    use bevy::ecs::system::RunSystemOnce;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_pressure_systems_drift_and_fade() {
+        let mut low = PressureSystem {
+            kind: PressureKind::Low,
+            center: Vec2::ZERO,
+            peak_strength: 20.0,
+            radius: 2.0,
+            age: 0.0,
+            lifetime: 4000.0,
+        };
+        assert_eq!(low.strength(), 0.0);
+        low.age = 2000.0;
+        assert_eq!(low.anomaly(ChunkCoord::new(0, 0)), -20.0);
+        assert!(low.anomaly(ChunkCoord::new(3, 0)) > -5.0);
+
+        let mut systems = vec![low];
+        let mut rng = StdRng::seed_from_u64(1);
+        advance_pressure_systems(&mut systems, &[], 1200.0, DAY_LENGTH, &mut rng, |_| Vec2::ZERO);
+        assert!(systems[0].center.x > 9.0);
+        advance_pressure_systems(&mut systems, &[], 1200.0, DAY_LENGTH, &mut rng, |_| Vec2::ZERO);
+        assert!(systems.is_empty());
+    }
+
+    #[test]
+    fn test_fronts_between_air_masses() {
+        let mut warm = WeatherSystem { temperature: 20.0, ..Default::default() };
+        let cold = WeatherSystem { temperature: 10.0, ..Default::default() };
+        // Wind from the cold cell in the west towards the warm one
+        warm.set_wind(Vec2::new(8.0, 0.0));
+        let cells = [(ChunkCoord::new(0, 0), &cold), (ChunkCoord::new(1, 0), &warm)];
+
+        let fronts = find_fronts(cells.into_iter(), |region| cells.iter().find(|(r, _)| *r == region).map(|(_, cell)| *cell));
+        assert_eq!(fronts.len(), 1);
+        assert_eq!(fronts[0].kind, FrontKind::Cold);
+        assert_eq!(fronts[0].warm, ChunkCoord::new(1, 0));
+        assert_eq!(fronts[0].strength, 10.0);
+    }
+
+    #[test]
+    fn test_storm_lifecycle() {
+        let mut world = World::new();
+        world.insert_resource(WeatherGrid::default());
+        world.init_resource::<StormTracker>();
+        world.init_resource::<Events<StormEvent>>();
+        let region = ChunkCoord::new(0, 0);
+
+        let check = |world: &mut World, precipitation: f32| {
+            let mut grid = world.resource_mut::<WeatherGrid>();
+            grid.step(1.0);
+            let cell = grid.cell_mut(region).unwrap();
+            // Warm enough for thunder, not warm enough for a heatwave
+            cell.temperature = 13.0;
+            cell.precipitation = precipitation;
+            world.run_system_once(storm_system).unwrap();
+            let mut events = world.resource_mut::<Events<StormEvent>>();
+            let stages: Vec<StormStage> = events.drain().map(|event| event.stage).collect();
+            stages
+        };
+
+        assert_eq!(check(&mut world, 6.0), vec![StormStage::Forming]);
+        let mut storms = world.query::<&Storm>();
+        let storm = storms.single(&world);
+        assert_eq!(storm.name, "Thunderstorm Ada");
+        assert_eq!(storm.kind, StormKind::Thunderstorm);
+
+        assert_eq!(check(&mut world, 10.0), vec![StormStage::Mature]);
+        assert_eq!(check(&mut world, 6.0), vec![StormStage::Dissipating]);
+        assert_eq!(check(&mut world, 0.0), Vec::<StormStage>::new());
+        assert_eq!(check(&mut world, 0.0), Vec::<StormStage>::new());
+        let mut remaining = Vec::new();
+        for _ in 0..4 {
+            remaining.extend(check(&mut world, 0.0));
+        }
+        assert_eq!(remaining, vec![StormStage::Ended]);
+        assert_eq!(world.query::<&Storm>().iter(&world).count(), 0);
+    }
+}
@@ -2,6 +2,8 @@ use bevy::prelude::*;
 use uuid::Uuid;
 use crate::agents::agent::Agent;
 use crate::agents::message::Message;
+use crate::engine::tick_rates::{is_idle, tick_offset, TickRates, TickSchedule};
+use crate::engine::time::SimClock;
 use std::time::Instant;
 
 /// Event fired when an agent completes a tick
@@ -16,16 +18,26 @@ pub struct AgentTickCompleted {
 /// System that processes agent ticks and message passing
 /// 
 /// This system:
-/// 1. Processes all agent ticks
+/// 1. Processes the ticks of agents due this simulation tick
 /// 2. Collects messages to be sent between agents
 /// 3. Delivers messages to recipient agents
 pub fn agent_tick_system(
     mut query: Query<(Entity, &mut Agent), With<Agent>>,
-    time: Res<Time<Fixed>>,
+    mut schedules: Query<&mut TickSchedule>,
+    clock: Res<SimClock>,
+    rates: Res<TickRates>,
     mut tick_events: EventWriter<AgentTickCompleted>,
 ) {
-    // First, process all agent ticks
+    // First, process the ticks of agents that are due
     for (entity, mut agent) in query.iter_mut() {
+        if let Ok(mut schedule) = schedules.get_mut(entity) {
+            if !schedule.is_due(clock.tick()) {
+                continue;
+            }
+            let interval = rates.interval(&schedule.category, is_idle(&agent));
+            schedule.ticked(clock.tick(), interval, tick_offset(agent.id));
+        }
+
         let start_time = Instant::now();
         
         // Use catch_unwind to prevent a single agent's tick from crashing the entire system
@@ -59,7 +71,7 @@ pub fn agent_tick_system(
     }
 
     // Phase 1: Collect all messages to be sent
-    let messages_to_send = generate_messages_for_all_agents(&query.to_readonly(), time.elapsed_secs());
+    let messages_to_send = generate_messages_for_all_agents(&query.to_readonly(), clock.elapsed() as f32);
     
     // Phase 2: Deliver all messages
     for (recipient_entity, message) in messages_to_send {
@@ -119,8 +131,7 @@ pub fn clear_agent_tick_events(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use bevy::prelude::*;
-    use crate::agents::agent::Agent;
+    use crate::engine::pipeline::{Decide, TickPipelinePlugin};
 
     #[test]
     fn test_agent_message_delivery() {
@@ -130,28 +141,59 @@ mod tests {
         // Add required plugins and resources
         app.add_plugins(MinimalPlugins);
         app.add_event::<AgentTickCompleted>();
-        app.insert_resource(Time::<Fixed>::from_hz(60.0));
+        app.init_resource::<SimClock>();
+        app.init_resource::<TickRates>();
+        app.add_systems(Update, agent_tick_system);
         
         // Spawn two agents
-        let agent1_entity = app.world.spawn(Agent::default()).id();
-        let agent2_entity = app.world.spawn(Agent::default()).id();
+        let agent1_entity = app.world_mut().spawn(Agent::default()).id();
+        let agent2_entity = app.world_mut().spawn(Agent::default()).id();
         
-        // Run the tick system
-        app.update();
+        // Run the tick system until both agents reach their 100th tick
+        for _ in 0..100 {
+            app.update();
+        }
         
         // Check that messages were delivered
-        let agent1 = app.world.get::<Agent>(agent1_entity).unwrap();
-        let agent2 = app.world.get::<Agent>(agent2_entity).unwrap();
+        let agent1 = app.world().get::<Agent>(agent1_entity).unwrap();
+        let agent2 = app.world().get::<Agent>(agent2_entity).unwrap();
         
         // At least one agent should have received a message
-        assert!(agent1.message_queue.len() > 0 || agent2.message_queue.len() > 0, 
+        assert!(!agent1.message_queue.is_empty() || !agent2.message_queue.is_empty(),
                 "No messages were delivered to either agent");
         
         // Check that tick events were sent
-        let mut tick_events = app.world.resource_mut::<Events<AgentTickCompleted>>();
-        let mut reader = tick_events.get_reader();
-        let events: Vec<&AgentTickCompleted> = reader.read(&tick_events).collect();
+        let tick_events = app.world().resource::<Events<AgentTickCompleted>>();
+        let mut reader = tick_events.get_cursor();
+        let events: Vec<&AgentTickCompleted> = reader.read(tick_events).collect();
         
         assert_eq!(events.len(), 2, "Expected 2 tick events, got {}", events.len());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_agents_tick_only_when_due() {
+        let mut app = App::new();
+        app.add_plugins(TickPipelinePlugin)
+            .add_event::<AgentTickCompleted>()
+            .insert_resource(TickRates {
+                categories: [("plant".to_string(), 4)].into(),
+                ..default()
+            })
+            .add_systems(Decide, agent_tick_system);
+
+        let plant = Agent {
+            id: Uuid::from_u64_pair(0, 0),
+            ..default()
+        };
+        let plant = app.world_mut().spawn((plant, TickSchedule::new("plant"))).id();
+        let human = app.world_mut().spawn((Agent::default(), TickSchedule::new("human"))).id();
+
+        for _ in 0..12 {
+            app.world_mut().run_schedule(FixedUpdate);
+        }
+
+        // The plant ticks on the first tick, then on every fourth one
+        assert_eq!(app.world().get::<Agent>(plant).unwrap().tick_count, 4);
+        assert_eq!(app.world().get::<Agent>(human).unwrap().tick_count, 12);
+    }
+}
@@ -0,0 +1,210 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+use crate::agents::agent::Agent;
+use crate::agents::job::Job;
+use crate::engine::scheduler::TimerFired;
+
+/// Path of the tick rates file, relative to the working directory
+pub const TICK_RATES_PATH: &str = "assets/data/tick_rates.ron";
+
+/// Built-in copy of the tick rates, used when the file can't be read
+const DEFAULT_TICK_RATES: &str = include_str!("../../assets/data/tick_rates.ron");
+
+/// Resource holding how often agents of each category tick
+///
+/// The default ticks every agent on every tick.
+#[derive(Debug, Clone, PartialEq, Resource, Deserialize)]
+pub struct TickRates {
+    /// Ticks between agent ticks for categories not listed
+    pub default_interval: u32,
+    /// Factor applied to the interval of idle agents
+    pub idle_multiplier: u32,
+    /// Ticks between agent ticks by category
+    #[serde(default)]
+    pub categories: HashMap<String, u32>,
+}
+
+impl Default for TickRates {
+    fn default() -> Self {
+        Self {
+            default_interval: 1,
+            idle_multiplier: 1,
+            categories: HashMap::new(),
+        }
+    }
+}
+
+impl TickRates {
+    pub fn from_ron_str(source: &str) -> Result<Self, String> {
+        ron::from_str(source).map_err(|err| format!("invalid tick rates: {}", err))
+    }
+
+    /// Loads tick rates from a RON file
+    pub fn load(path: &str) -> Result<Self, String> {
+        let source = std::fs::read_to_string(path)
+            .map_err(|err| format!("failed to read {}: {}", path, err))?;
+        Self::from_ron_str(&source)
+    }
+
+    /// Ticks between agent ticks for a category, at least 1
+    pub fn interval(&self, category: &str, idle: bool) -> u64 {
+        let interval = self.categories.get(category).copied().unwrap_or(self.default_interval).max(1) as u64;
+        if idle {
+            interval * self.idle_multiplier.max(1) as u64
+        } else {
+            interval
+        }
+    }
+}
+
+/// Component deciding on which simulation ticks an agent ticks
+///
+/// Agents without one tick on every tick.
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct TickSchedule {
+    /// Category looked up in `TickRates`
+    pub category: String,
+    next_tick: u64,
+    woken: bool,
+}
+
+impl TickSchedule {
+    pub fn new(category: impl Into<String>) -> Self {
+        Self {
+            category: category.into(),
+            next_tick: 0,
+            woken: false,
+        }
+    }
+
+    /// Whether the agent ticks on this simulation tick
+    pub fn is_due(&self, tick: u64) -> bool {
+        self.woken || tick >= self.next_tick
+    }
+
+    /// Makes the agent tick on the next simulation tick
+    pub fn wake(&mut self) {
+        self.woken = true;
+    }
+
+    /// Records a tick and picks the next one
+    ///
+    /// `offset` spreads agents across the ticks of their interval: the
+    /// agent ticks when the simulation tick plus its offset is a multiple of
+    /// the interval, so waking early doesn't move it to another bucket.
+    pub fn ticked(&mut self, tick: u64, interval: u64, offset: u64) {
+        let interval = interval.max(1);
+        self.woken = false;
+        // Offsets are full random `u64`s, so reduce before adding
+        self.next_tick = tick + interval - (tick % interval + offset % interval) % interval;
+    }
+}
+
+/// Bucket offset of an agent, stable for its lifetime and across saves
+pub fn tick_offset(id: Uuid) -> u64 {
+    id.as_u64_pair().1
+}
+
+/// Whether an agent counts as idle for its tick rate
+pub fn is_idle(agent: &Agent) -> bool {
+    matches!(agent.current_job, None | Some(Job::Idle))
+}
+
+/// Event asking for an agent to tick on the next simulation tick
+#[derive(Event, Debug, Clone, Copy)]
+pub struct WakeAgent(pub Entity);
+
+/// System loading tick rates from `TICK_RATES_PATH`
+///
+/// Falls back to the built-in copy when the file can't be read, and to
+/// ticking every agent on every tick when neither is valid.
+pub fn load_tick_rates(mut rates: ResMut<TickRates>) {
+    let loaded = TickRates::load(TICK_RATES_PATH).or_else(|err| {
+        warn!("Using built-in tick rates: {}", err);
+        TickRates::from_ron_str(DEFAULT_TICK_RATES)
+    });
+
+    match loaded {
+        Ok(loaded) => {
+            info!("Loaded tick rates for {} agent categories", loaded.categories.len());
+            *rates = loaded;
+        }
+        Err(err) => {
+            error!("No usable tick rates: {}", err);
+            *rates = TickRates::default();
+        }
+    }
+}
+
+/// System waking agents named by `WakeAgent` events or by timers that fired
+/// for them
+pub fn wake_agents(
+    mut wake_events: EventReader<WakeAgent>,
+    mut timers: EventReader<TimerFired>,
+    mut query: Query<(&Agent, &mut TickSchedule)>,
+) {
+    for WakeAgent(entity) in wake_events.read() {
+        if let Ok((_, mut schedule)) = query.get_mut(*entity) {
+            schedule.wake();
+        }
+    }
+
+    let agents: HashSet<Uuid> = timers.read().filter_map(|timer| timer.event.agent).collect();
+    if agents.is_empty() {
+        return;
+    }
+    for (agent, mut schedule) in query.iter_mut() {
+        if agents.contains(&agent.id) {
+            schedule.wake();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tick_rates_load_and_scale_idle_agents() {
+        let rates = TickRates::from_ron_str(DEFAULT_TICK_RATES).unwrap();
+        assert_eq!(rates.interval("human", false), 1);
+        assert_eq!(rates.interval("plant", false), 60);
+        assert_eq!(rates.interval("animal", true), 4 * rates.idle_multiplier as u64);
+        assert_eq!(rates.interval("unknown", false), rates.default_interval as u64);
+        assert_eq!(TickRates::default().interval("plant", true), 1);
+    }
+
+    #[test]
+    fn test_schedules_spread_across_buckets_and_wake() {
+        let mut per_tick = [0; 4];
+        for offset in 0..40 {
+            let mut schedule = TickSchedule::new("animal");
+            for tick in 0..12 {
+                if schedule.is_due(tick) {
+                    schedule.ticked(tick, 4, offset);
+                    if tick >= 4 {
+                        per_tick[(tick % 4) as usize] += 1;
+                    }
+                }
+            }
+        }
+        // Once settled, every tick runs a quarter of the agents
+        assert_eq!(per_tick, [20, 20, 20, 20]);
+
+        let mut schedule = TickSchedule::new("plant");
+        schedule.ticked(0, 60, 0);
+        assert!(!schedule.is_due(1));
+        schedule.wake();
+        assert!(schedule.is_due(1));
+        schedule.ticked(1, 60, 0);
+        assert!(!schedule.is_due(59));
+        assert!(schedule.is_due(60));
+
+        // Offsets anywhere in the `u64` range pick a bucket without overflowing
+        let mut schedule = TickSchedule::new("animal");
+        schedule.ticked(5, 4, u64::MAX);
+        assert_eq!(schedule.next_tick, 9);
+    }
+}
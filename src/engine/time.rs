@@ -1,36 +1,316 @@
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
-/// System for managing simulation time
-#[derive(Debug, Clone, Component)]
-pub struct TimeSystem {
-    pub current_time: f32,
+/// Days in a simulated year
+pub const DAYS_PER_YEAR: u32 = 120;
+
+/// Axial tilt giving the seasons, in degrees
+pub const AXIAL_TILT: f32 = 23.44;
+
+/// Fraction of the year at which spring begins
+const SPRING_EQUINOX: f32 = 0.0;
+
+/// Quarter of the year
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Season {
+    Spring,
+    Summer,
+    Autumn,
+    Winter,
+}
+
+impl Season {
+    pub const ALL: [Season; 4] = [Season::Spring, Season::Summer, Season::Autumn, Season::Winter];
+
+    /// Returns the season at a fraction of the year, starting with spring
+    pub fn from_year_fraction(fraction: f32) -> Self {
+        Self::ALL[((fraction.rem_euclid(1.0) * 4.0) as usize).min(3)]
+    }
+
+    /// Index in `Season::ALL`
+    pub fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Part of the day by the clock
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DayPhase {
+    Night,
+    Dawn,
+    Day,
+    Dusk,
+}
+
+impl DayPhase {
+    /// Returns the phase at a fraction of the day, 0.0 at midnight
+    pub fn from_time_of_day(time_of_day: f32) -> Self {
+        match time_of_day.rem_euclid(1.0) * 24.0 {
+            hour if hour < 5.0 => Self::Night,
+            hour if hour < 7.0 => Self::Dawn,
+            hour if hour < 18.0 => Self::Day,
+            hour if hour < 20.0 => Self::Dusk,
+            _ => Self::Night,
+        }
+    }
+
+    /// Hour of the day at which the phase begins
+    pub fn start_hour(self) -> f32 {
+        match self {
+            Self::Night => 20.0,
+            Self::Dawn => 5.0,
+            Self::Day => 7.0,
+            Self::Dusk => 18.0,
+        }
+    }
+}
+
+/// A point in simulation time expressed in calendar terms
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CalendarDate {
+    /// Years since the simulation started, from 0
+    pub year: u32,
+    /// Day within the year, from 0
+    pub day_of_year: u32,
+    /// Fraction of the day passed, 0.0 at midnight
+    pub time_of_day: f32,
+    /// Fraction of the year passed, 0.0 at the start of spring
+    pub year_fraction: f32,
+    /// Season in the northern hemisphere; reversed south of the equator
+    pub season: Season,
+}
+
+impl CalendarDate {
+    /// Hour of the day, from 0
+    pub fn hour(&self) -> u32 {
+        ((self.time_of_day * 24.0) as u32).min(23)
+    }
+
+    /// Minute of the hour, from 0
+    pub fn minute(&self) -> u32 {
+        ((self.time_of_day * 24.0 * 60.0) as u32 % 60).min(59)
+    }
+
+    /// Part of the day by the clock
+    pub fn phase(&self) -> DayPhase {
+        DayPhase::from_time_of_day(self.time_of_day)
+    }
+
+    /// Latitude in degrees where the sun is overhead at noon
+    pub fn solar_declination(&self) -> f32 {
+        AXIAL_TILT * ((self.year_fraction - SPRING_EQUINOX) * std::f32::consts::TAU).sin()
+    }
+
+    /// Season at a latitude in degrees
+    pub fn season_at(&self, latitude: f32) -> Season {
+        if latitude < 0.0 {
+            Season::from_year_fraction(self.year_fraction + 0.5)
+        } else {
+            self.season
+        }
+    }
+
+    /// Fraction of the day the sun is up at a latitude in degrees
+    ///
+    /// Polar days and nights give 1.0 and 0.0.
+    pub fn daylight_fraction(&self, latitude: f32) -> f32 {
+        let latitude = latitude.clamp(-90.0, 90.0).to_radians();
+        let declination = self.solar_declination().to_radians();
+        let cos_hour_angle = (-latitude.tan() * declination.tan()).clamp(-1.0, 1.0);
+        cos_hour_angle.acos() / std::f32::consts::PI
+    }
+}
+
+/// Default simulation seconds in a day, one per minute of the day
+pub const DAY_LENGTH: f32 = 24.0 * 60.0;
+
+/// Resource holding the simulation clock
+///
+/// The single source of simulation time: counts fixed ticks and the
+/// simulation seconds they add up to, and converts them into minutes,
+/// hours, days, seasons and years. Every system that needs to know when
+/// it is reads this instead of frame time. It is serialisable, so saves
+/// restore the clock exactly.
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SimClock {
+    /// Simulation seconds added by each tick
+    pub tick_length: f64,
+    /// Simulation seconds in a day
     pub day_length: f32,
-    pub time_scale: f32,
+    pub days_per_year: u32,
+    /// Day of the year the simulation starts on
+    pub start_day: u32,
+    tick: u64,
+    elapsed: f64,
 }
 
-impl Default for TimeSystem {
+impl Default for SimClock {
     fn default() -> Self {
         Self {
-            current_time: 0.0,
-            day_length: 24.0 * 60.0, // 24 hours in minutes
-            time_scale: 1.0,
+            tick_length: 1.0 / 60.0,
+            day_length: DAY_LENGTH,
+            days_per_year: DAYS_PER_YEAR,
+            start_day: 0,
+            tick: 0,
+            elapsed: 0.0,
         }
     }
 }
 
-/// System for updating the time
-pub fn update_time_system(
-    time: Res<Time>,
-    mut query: Query<&mut TimeSystem>,
-) {
-    let delta = time.delta_secs();
-    
-    for mut time_system in query.iter_mut() {
-        time_system.current_time += delta * time_system.time_scale;
-        
-        // Wrap around at day length
-        if time_system.current_time >= time_system.day_length {
-            time_system.current_time -= time_system.day_length;
+impl SimClock {
+    /// A clock whose ticks each add `tick_length` simulation seconds
+    pub fn with_tick_length(tick_length: f64) -> Self {
+        Self {
+            tick_length: tick_length.max(0.0),
+            ..Default::default()
         }
     }
-} 
\ No newline at end of file
+
+    /// Ticks since startup
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// Simulation seconds since startup
+    pub fn elapsed(&self) -> f64 {
+        self.elapsed
+    }
+
+    /// Advances the clock by one tick
+    pub fn advance_tick(&mut self) {
+        self.tick += 1;
+        self.elapsed += self.tick_length;
+    }
+
+    /// Moves simulation time forward without counting a tick
+    #[cfg(test)]
+    pub fn advance(&mut self, seconds: f64) {
+        self.elapsed += seconds.max(0.0);
+    }
+
+    /// Simulation seconds in an hour
+    pub fn hour_length(&self) -> f64 {
+        self.day_length as f64 / 24.0
+    }
+
+    /// Simulation seconds in a year
+    #[cfg(test)]
+    pub fn year_length(&self) -> f64 {
+        self.day_length as f64 * self.days_per_year.max(1) as f64
+    }
+
+    /// Converts simulation seconds since startup into a date
+    pub fn date_at(&self, elapsed: f64) -> CalendarDate {
+        let day_length = self.day_length.max(f32::EPSILON) as f64;
+        let days_per_year = self.days_per_year.max(1) as u64;
+        let days = elapsed.max(0.0) / day_length + self.start_day as f64;
+        let whole_days = days.floor() as u64;
+        let day_of_year = (whole_days % days_per_year) as u32;
+        let time_of_day = days.fract() as f32;
+        let year_fraction = (day_of_year as f32 + time_of_day) / days_per_year as f32;
+        CalendarDate {
+            year: (whole_days / days_per_year) as u32,
+            day_of_year,
+            time_of_day,
+            year_fraction,
+            season: Season::from_year_fraction(year_fraction),
+        }
+    }
+
+    /// Days counted from the calendar's epoch, with the fraction of the
+    /// current day
+    pub fn days_at(&self, elapsed: f64) -> f64 {
+        self.start_day as f64 + elapsed.max(0.0) / self.day_length.max(f32::EPSILON) as f64
+    }
+
+    /// The current date
+    pub fn date(&self) -> CalendarDate {
+        self.date_at(self.elapsed)
+    }
+
+    /// Whole days since the simulation started
+    pub fn day(&self) -> u64 {
+        (self.elapsed.max(0.0) / self.day_length.max(f32::EPSILON) as f64) as u64
+    }
+
+    pub fn minute(&self) -> u32 {
+        self.date().minute()
+    }
+
+    pub fn hour(&self) -> u32 {
+        self.date().hour()
+    }
+
+    pub fn season(&self) -> Season {
+        self.date().season
+    }
+
+    pub fn year(&self) -> u32 {
+        self.date().year
+    }
+
+    pub fn phase(&self) -> DayPhase {
+        self.date().phase()
+    }
+}
+
+/// System advancing the clock by one tick, run at the start of every
+/// fixed step
+pub fn advance_clock(mut clock: ResMut<SimClock>) {
+    clock.advance_tick();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_calendar_dates_and_seasons() {
+        let mut calendar = SimClock {
+            day_length: 100.0,
+            days_per_year: 8,
+            ..Default::default()
+        };
+        assert_eq!(calendar.date().season, Season::Spring);
+
+        calendar.advance(250.0);
+        let date = calendar.date();
+        assert_eq!((date.year, date.day_of_year), (0, 2));
+        assert!((date.time_of_day - 0.5).abs() < 1e-6);
+        assert_eq!(date.season, Season::Summer);
+        assert_eq!(date.season_at(-30.0), Season::Winter);
+
+        calendar.advance(700.0);
+        assert_eq!((calendar.date().year, calendar.date().day_of_year), (1, 1));
+    }
+
+    #[test]
+    fn test_daylight_varies_with_latitude_and_season() {
+        let calendar = SimClock::default();
+        let equinox = calendar.date_at(0.0);
+        let midsummer = calendar.date_at(calendar.year_length() * 0.25);
+
+        assert!((equinox.daylight_fraction(50.0) - 0.5).abs() < 1e-3);
+        assert!((midsummer.daylight_fraction(0.0) - 0.5).abs() < 1e-3);
+        assert!(midsummer.daylight_fraction(50.0) > 0.6);
+        assert!(midsummer.daylight_fraction(-50.0) < 0.4);
+        assert_eq!(midsummer.daylight_fraction(80.0), 1.0);
+    }
+
+    #[test]
+    fn test_clock_ticks_and_round_trips() {
+        let mut clock = SimClock::with_tick_length(30.0);
+        for _ in 0..(DAY_LENGTH as usize / 30 + 13) {
+            clock.advance_tick();
+        }
+        assert_eq!(clock.tick(), 61);
+        assert_eq!(clock.day(), 1);
+        assert_eq!((clock.hour(), clock.minute()), (6, 30));
+        assert_eq!(clock.phase(), DayPhase::Dawn);
+        assert_eq!(clock.season(), Season::Spring);
+
+        let saved = ron::to_string(&clock).unwrap();
+        let restored: SimClock = ron::from_str(&saved).unwrap();
+        assert_eq!(restored, clock);
+    }
+}
@@ -1,26 +1,52 @@
 use bevy::prelude::*;
-use rand::random;
+use crate::engine::forecast::{remember_weather_outlook, update_forecast, WeatherForecaster};
+use crate::engine::weather_effects::{apply_weather_effects, load_weather_rules, WeatherRules};
+use crate::engine::weather_history::{record_weather_history, remember_recent_weather, WeatherHistory};
+use crate::engine::weather_scenario::{load_weather_scenarios, remove_finished_scenarios, WeatherScenarios};
+use crate::engine::storms::{storm_system, StormEvent, StormTracker};
+use crate::engine::pipeline::{AdvanceTime, Perception, PostTick, PreTick};
+use crate::engine::time::SimClock;
+use crate::engine::weather_grid::{seed_weather_grid, sync_weather_grid, WeatherGrid, STANDARD_PRESSURE};
+use crate::world::chunk::ChunkCoord;
+use crate::world::resources::{load_resource_registry, ResourceRegistry};
 
-// Constants for weather calculations
-const SECONDS_PER_DAY: f32 = 24.0 * 60.0 * 60.0;
 const TAU: f32 = std::f32::consts::PI * 2.0;
 
 // Weather event for notifying systems of significant weather changes
 #[derive(Event, Debug)]
 pub struct WeatherChanged {
+    /// Weather grid region the change happened in
+    pub region: ChunkCoord,
     pub temperature: f32,
     pub humidity: f32,
+    pub pressure: f32,
     pub wind_speed: f32,
     pub wind_direction: f32,
     pub precipitation: f32,
     pub cloud_cover: f32,
 }
 
-/// System for managing weather in the simulation
-#[derive(Debug, Clone, Component)]
+impl WeatherChanged {
+    pub fn new(region: ChunkCoord, weather: &WeatherSystem) -> Self {
+        Self {
+            region,
+            temperature: weather.temperature,
+            humidity: weather.humidity,
+            pressure: weather.pressure,
+            wind_speed: weather.wind_speed,
+            wind_direction: weather.wind_direction,
+            precipitation: weather.precipitation,
+            cloud_cover: weather.cloud_cover,
+        }
+    }
+}
+
+/// Weather conditions in one cell of the `WeatherGrid`
+#[derive(Debug, Clone, PartialEq)]
 pub struct WeatherSystem {
     pub temperature: f32,     // Celsius
     pub humidity: f32,        // 0.0 to 1.0
+    pub pressure: f32,        // hPa
     pub wind_speed: f32,      // m/s
     pub wind_direction: f32,  // radians
     pub precipitation: f32,   // mm/hour
@@ -32,6 +58,7 @@ impl Default for WeatherSystem {
         Self {
             temperature: 20.0, // Celsius
             humidity: 0.5,     // 0.0 to 1.0
+            pressure: STANDARD_PRESSURE, // hPa
             wind_speed: 0.0,   // m/s
             wind_direction: 0.0, // radians
             precipitation: 0.0,  // mm/hour
@@ -40,6 +67,32 @@ impl Default for WeatherSystem {
     }
 }
 
+impl WeatherSystem {
+    /// Wind as a vector in m/s, pointing where it blows to
+    pub fn wind(&self) -> Vec2 {
+        Vec2::from_angle(self.wind_direction) * self.wind_speed
+    }
+
+    pub fn set_wind(&mut self, wind: Vec2) {
+        self.wind_speed = wind.length();
+        self.wind_direction = if self.wind_speed > 0.0 {
+            wind.y.atan2(wind.x).rem_euclid(TAU)
+        } else {
+            0.0
+        };
+    }
+
+    /// Whether the change to `other` is large enough to report
+    pub fn differs_significantly(&self, other: &WeatherSystem) -> bool {
+        (self.temperature - other.temperature).abs() > 2.0
+            || (self.humidity - other.humidity).abs() > 0.1
+            || (self.pressure - other.pressure).abs() > 3.0
+            || (self.wind_speed - other.wind_speed).abs() > 1.0
+            || (self.precipitation - other.precipitation).abs() > 1.0
+            || (self.cloud_cover - other.cloud_cover).abs() > 0.1
+    }
+}
+
 /// Plugin for the weather system
 pub struct WeatherPlugin;
 
@@ -47,87 +100,54 @@ impl Plugin for WeatherPlugin {
     fn build(&self, app: &mut App) {
         app
             .add_event::<WeatherChanged>()
-            .add_systems(Startup, spawn_weather_system)
-            .add_systems(Update, (
-                update_weather_system, 
+            .add_event::<StormEvent>()
+            .init_resource::<StormTracker>()
+            .init_resource::<SimClock>()
+            .init_resource::<WeatherGrid>()
+            .init_resource::<WeatherForecaster>()
+            .init_resource::<ResourceRegistry>()
+            .init_resource::<WeatherRules>()
+            .init_resource::<WeatherHistory>()
+            .init_resource::<WeatherScenarios>()
+            .add_systems(PreStartup, (load_weather_rules.after(load_resource_registry), load_weather_scenarios))
+            .add_systems(Startup, seed_weather_grid)
+            .add_systems(PreTick, (
+                sync_weather_grid,
+                remove_finished_scenarios,
+                update_weather_system,
+                storm_system,
+            ).chain().after(AdvanceTime))
+            .add_systems(Perception, (
+                apply_weather_effects,
+                update_forecast,
+                remember_weather_outlook,
+                remember_recent_weather,
+            ).chain())
+            .add_systems(PostTick, (
+                record_weather_history,
                 process_weather_changes,
                 clear_weather_events,
-            ));
+            ).chain());
     }
 }
 
-/// Spawns the initial weather system
-fn spawn_weather_system(mut commands: Commands) {
-    commands.spawn(WeatherSystem::default());
-}
-
 /// System for updating weather
+///
+/// Hands changed scenarios to the weather grid, advances it to the
+/// clock's time and reports cells whose conditions changed
+/// significantly.
 pub fn update_weather_system(
-    time: Res<Time>,
-    mut query: Query<&mut WeatherSystem>,
+    clock: Res<SimClock>,
+    scenarios: Res<WeatherScenarios>,
+    mut grid: ResMut<WeatherGrid>,
     mut events: EventWriter<WeatherChanged>,
 ) {
-    let delta = time.delta_secs();
-    
-    for mut weather in query.iter_mut() {
-        // Store old values to detect significant changes
-        let old_temp = weather.temperature;
-        let old_humidity = weather.humidity;
-        let old_wind_speed = weather.wind_speed;
-        let old_precipitation = weather.precipitation;
-        let old_cloud_cover = weather.cloud_cover;
-        
-        // Simple weather simulation
-        // Temperature varies with time of day
-        let time_of_day = (time.elapsed_secs() % SECONDS_PER_DAY) / SECONDS_PER_DAY;
-        let base_temp = 15.0 + 10.0 * (time_of_day * TAU).sin();
-        
-        // Add some noise to temperature
-        weather.temperature = base_temp + (random::<f32>() - 0.5) * 2.0;
-        
-        // Wind changes slowly
-        weather.wind_speed += (random::<f32>() - 0.5) * delta * 0.1;
-        weather.wind_speed = weather.wind_speed.clamp(0.0, 20.0);
-        
-        weather.wind_direction += (random::<f32>() - 0.5) * delta * 0.1;
-        if weather.wind_direction > TAU {
-            weather.wind_direction -= TAU;
-        }
-        
-        // Humidity and precipitation
-        weather.humidity += (random::<f32>() - 0.5) * delta * 0.01;
-        weather.humidity = weather.humidity.clamp(0.0, 1.0);
-        
-        // Cloud cover is influenced by humidity and wind
-        weather.cloud_cover += (weather.humidity - 0.5) * delta * 0.01;
-        weather.cloud_cover = weather.cloud_cover.clamp(0.0, 1.0);
-        
-        // More likely to rain when humidity is high and cloud cover is significant
-        if weather.humidity > 0.8 && weather.cloud_cover > 0.6 && random::<f32>() < 0.1 {
-            weather.precipitation = random::<f32>() * 10.0;
-        } else {
-            weather.precipitation *= 0.95; // Gradually decrease precipitation
-        }
-        
-        // Check for significant changes to emit events
-        let temp_change = (weather.temperature - old_temp).abs();
-        let humidity_change = (weather.humidity - old_humidity).abs();
-        let wind_change = (weather.wind_speed - old_wind_speed).abs();
-        let precip_change = (weather.precipitation - old_precipitation).abs();
-        let cloud_change = (weather.cloud_cover - old_cloud_cover).abs();
-        
-        // Emit event if any significant change occurred
-        if temp_change > 2.0 || humidity_change > 0.1 || wind_change > 1.0 || 
-           precip_change > 1.0 || cloud_change > 0.1 {
-            events.send(WeatherChanged {
-                temperature: weather.temperature,
-                humidity: weather.humidity,
-                wind_speed: weather.wind_speed,
-                wind_direction: weather.wind_direction,
-                precipitation: weather.precipitation,
-                cloud_cover: weather.cloud_cover,
-            });
-        }
+    if scenarios.is_changed() {
+        grid.set_scenarios(&scenarios);
+    }
+    grid.advance(&clock);
+    for (region, weather) in grid.take_changes() {
+        events.send(WeatherChanged::new(region, &weather));
     }
 }
 
@@ -139,8 +159,9 @@ pub fn process_weather_changes(
         // This is a placeholder for future UI updates, logging, or other systems
         // that need to react to weather changes
         info!(
-            "Weather changed: Temp={:.1}°C, Humidity={:.2}, Wind={:.1}m/s, Rain={:.1}mm/hr, Clouds={:.2}",
-            event.temperature, event.humidity, event.wind_speed, event.precipitation, event.cloud_cover
+            "Weather changed in region ({}, {}): Temp={:.1}°C, Humidity={:.2}, Pressure={:.0}hPa, Wind={:.1}m/s, Rain={:.1}mm/hr, Clouds={:.2}",
+            event.region.x, event.region.y, event.temperature, event.humidity, event.pressure,
+            event.wind_speed, event.precipitation, event.cloud_cover
         );
     }
 }
@@ -156,7 +177,8 @@ pub fn clear_weather_events(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use bevy::prelude::*;
+    use bevy::ecs::system::RunSystemOnce;
+    use crate::engine::weather_grid::WeatherGridConfig;
 
     #[test]
     fn test_weather_system_default() {
@@ -172,20 +194,21 @@ mod tests {
     
     #[test]
     fn test_weather_clamping() {
-        let mut app = App::new();
-        app.add_plugins(WeatherPlugin);
+        let mut grid = WeatherGrid::new(WeatherGridConfig::default(), 42);
+        grid.sync((-2..3).flat_map(|x| (-2..3).map(move |y| ChunkCoord::new(x * 4, y * 4))));
+        grid.cell_mut(ChunkCoord::new(0, 0)).unwrap().humidity = 1.0;
         
-        // Run the system for a few frames to ensure values stay within bounds
-        for _ in 0..10 {
-            app.update();
-            
-            let weather = app.world.query::<&WeatherSystem>().single();
-            
+        // Run the model for two days to ensure values stay within bounds
+        for _ in 0..(2 * 24 * 60) {
+            grid.step(grid.config.step);
+        }
+
+        for (_, weather) in grid.cells() {
             // Check that values are properly clamped
             assert!(weather.humidity >= 0.0 && weather.humidity <= 1.0, 
                 "Humidity should be clamped between 0.0 and 1.0, got {}", weather.humidity);
             
-            assert!(weather.wind_speed >= 0.0 && weather.wind_speed <= 20.0, 
+            assert!(weather.wind_speed >= 0.0 && weather.wind_speed <= 20.0 + 1e-3, 
                 "Wind speed should be clamped between 0.0 and 20.0, got {}", weather.wind_speed);
             
             assert!(weather.wind_direction >= 0.0 && weather.wind_direction < TAU, 
@@ -196,25 +219,30 @@ mod tests {
             
             assert!(weather.precipitation >= 0.0, 
                 "Precipitation should never be negative, got {}", weather.precipitation);
+
+            assert!((900.0..1100.0).contains(&weather.pressure),
+                "Pressure should stay near sea level, got {}", weather.pressure);
         }
     }
     
     #[test]
     fn test_weather_events() {
         let mut app = App::new();
+        app.add_plugins(MinimalPlugins);
         app.add_plugins(WeatherPlugin);
+        app.update();
+
+        // A sudden change in one region is reported for that region only
+        let region = ChunkCoord::new(0, 0);
+        app.world_mut().resource_mut::<WeatherGrid>().cell_mut(region).unwrap().temperature = 35.0;
+        app.world_mut().run_system_once(update_weather_system).unwrap();
         
-        // Run the system for a few frames to generate some events
-        for _ in 0..10 {
-            app.update();
-        }
-        
-        // Check that events were generated
-        let mut events = app.world.resource_mut::<Events<WeatherChanged>>();
-        let mut reader = events.get_reader();
-        let events: Vec<&WeatherChanged> = reader.read(&events).collect();
+        let events = app.world().resource::<Events<WeatherChanged>>();
+        let mut reader = events.get_cursor();
+        let events: Vec<&WeatherChanged> = reader.read(events).collect();
         
-        // We should have at least one weather change event
-        assert!(!events.is_empty(), "No weather change events were generated");
+        assert_eq!(events.len(), 1, "Expected one weather change event, got {:?}", events);
+        assert_eq!(events[0].region, region);
+        assert_eq!(events[0].temperature, 35.0);
     }
-} 
\ No newline at end of file
+}
@@ -0,0 +1,380 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+use crate::agents::agent::Agent;
+use crate::engine::time::SimClock;
+use crate::engine::weather::WeatherSystem;
+use crate::engine::weather_grid::WeatherGrid;
+use crate::world::deposits::TileDeposits;
+use crate::world::resources::{ResourceId, ResourceRegistry};
+
+/// Path of the weather rules loaded at startup
+pub const WEATHER_RULES_PATH: &str = "assets/data/weather_effects.ron";
+
+/// Built-in copy of the weather rules, used when the file can't be read
+const DEFAULT_WEATHER_RULES: &str = include_str!("../../assets/data/weather_effects.ron");
+
+/// Inclusive range a weather variable must lie in, open where unset
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct WeatherRange {
+    #[serde(default = "open_min")]
+    pub min: f32,
+    #[serde(default = "open_max")]
+    pub max: f32,
+}
+
+fn open_min() -> f32 {
+    f32::NEG_INFINITY
+}
+
+fn open_max() -> f32 {
+    f32::INFINITY
+}
+
+impl Default for WeatherRange {
+    fn default() -> Self {
+        Self { min: open_min(), max: open_max() }
+    }
+}
+
+impl WeatherRange {
+    pub fn contains(&self, value: f32) -> bool {
+        (self.min..=self.max).contains(&value)
+    }
+}
+
+/// Weather a rule applies in; variables left out match anything
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+pub struct WeatherCondition {
+    #[serde(default)]
+    pub temperature: WeatherRange,
+    #[serde(default)]
+    pub humidity: WeatherRange,
+    #[serde(default)]
+    pub precipitation: WeatherRange,
+    #[serde(default)]
+    pub wind_speed: WeatherRange,
+    #[serde(default)]
+    pub cloud_cover: WeatherRange,
+}
+
+impl WeatherCondition {
+    pub fn matches(&self, weather: &WeatherSystem) -> bool {
+        self.temperature.contains(weather.temperature)
+            && self.humidity.contains(weather.humidity)
+            && self.precipitation.contains(weather.precipitation)
+            && self.wind_speed.contains(weather.wind_speed)
+            && self.cloud_cover.contains(weather.cloud_cover)
+    }
+}
+
+/// Effect as written in data files, naming resources by registry key
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub enum WeatherEffectDefinition {
+    Movement(f32),
+    EnergyDrain(f32),
+    Perception(f32),
+    Refill { resource: String, rate: f32 },
+    Damage { resource: String, rate: f32 },
+}
+
+/// Effect of weather on agents or the resource deposits beneath it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WeatherEffect {
+    /// Multiplies how far agents move
+    Movement(f32),
+    /// Multiplies how fast agents use energy
+    EnergyDrain(f32),
+    /// Multiplies how far agents perceive
+    Perception(f32),
+    /// Units added to deposits per second per mm/hour of precipitation
+    Refill { resource: ResourceId, rate: f32 },
+    /// Fraction of deposits lost per second
+    Damage { resource: ResourceId, rate: f32 },
+}
+
+/// Rule as written in data files
+#[derive(Debug, Clone, Deserialize)]
+pub struct WeatherRuleDefinition {
+    pub name: String,
+    #[serde(default)]
+    pub when: WeatherCondition,
+    pub effects: Vec<WeatherEffectDefinition>,
+}
+
+/// Rule with its resources resolved against the registry
+#[derive(Debug, Clone, PartialEq)]
+pub struct WeatherRule {
+    pub name: String,
+    pub when: WeatherCondition,
+    pub effects: Vec<WeatherEffect>,
+}
+
+impl WeatherRule {
+    /// Resolves a rule definition against the resource registry
+    pub fn resolve(definition: WeatherRuleDefinition, registry: &ResourceRegistry) -> Result<Self, String> {
+        let lookup = |key: &str| {
+            registry
+                .id(key)
+                .ok_or_else(|| format!("weather rule '{}' uses unknown resource '{}'", definition.name, key))
+        };
+
+        let effects = definition
+            .effects
+            .iter()
+            .map(|effect| {
+                Ok(match effect {
+                    WeatherEffectDefinition::Movement(factor) => WeatherEffect::Movement(factor.max(0.0)),
+                    WeatherEffectDefinition::EnergyDrain(factor) => WeatherEffect::EnergyDrain(factor.max(0.0)),
+                    WeatherEffectDefinition::Perception(factor) => WeatherEffect::Perception(factor.max(0.0)),
+                    WeatherEffectDefinition::Refill { resource, rate } => WeatherEffect::Refill {
+                        resource: lookup(resource)?,
+                        rate: rate.max(0.0),
+                    },
+                    WeatherEffectDefinition::Damage { resource, rate } => WeatherEffect::Damage {
+                        resource: lookup(resource)?,
+                        rate: rate.clamp(0.0, 1.0),
+                    },
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        Ok(Self {
+            name: definition.name,
+            when: definition.when,
+            effects,
+        })
+    }
+}
+
+/// Combined weather multipliers on an agent, all 1.0 in calm weather
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WeatherModifiers {
+    pub movement: f32,
+    pub energy_drain: f32,
+    pub perception: f32,
+}
+
+impl Default for WeatherModifiers {
+    fn default() -> Self {
+        Self {
+            movement: 1.0,
+            energy_drain: 1.0,
+            perception: 1.0,
+        }
+    }
+}
+
+/// Resource holding the rules that turn weather into gameplay effects
+#[derive(Debug, Clone, Default, Resource)]
+pub struct WeatherRules {
+    rules: Vec<WeatherRule>,
+}
+
+impl WeatherRules {
+    /// Parses a RON list of weather rules
+    pub fn from_ron_str(source: &str, registry: &ResourceRegistry) -> Result<Self, String> {
+        let definitions: Vec<WeatherRuleDefinition> = ron::from_str(source)
+            .map_err(|err| format!("invalid weather rules: {}", err))?;
+
+        let rules = definitions
+            .into_iter()
+            .map(|definition| WeatherRule::resolve(definition, registry))
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(Self { rules })
+    }
+
+    /// Loads weather rules from a RON file
+    pub fn load(path: &str, registry: &ResourceRegistry) -> Result<Self, String> {
+        let source = std::fs::read_to_string(path)
+            .map_err(|err| format!("failed to read {}: {}", path, err))?;
+        Self::from_ron_str(&source, registry)
+    }
+
+    /// Iterates over the rules that apply in some weather
+    pub fn matching<'a>(&'a self, weather: &'a WeatherSystem) -> impl Iterator<Item = &'a WeatherRule> {
+        self.rules.iter().filter(|rule| rule.when.matches(weather))
+    }
+
+    /// Combines the agent effects of every rule applying in some weather
+    pub fn modifiers(&self, weather: &WeatherSystem) -> WeatherModifiers {
+        let mut modifiers = WeatherModifiers::default();
+        for effect in self.matching(weather).flat_map(|rule| rule.effects.iter()) {
+            match *effect {
+                WeatherEffect::Movement(factor) => modifiers.movement *= factor,
+                WeatherEffect::EnergyDrain(factor) => modifiers.energy_drain *= factor,
+                WeatherEffect::Perception(factor) => modifiers.perception *= factor,
+                _ => {}
+            }
+        }
+        modifiers
+    }
+
+    /// Refills and damages the deposits of a tile for `delta` seconds of weather
+    ///
+    /// Amounts too small for one tick carry over in the deposits, so short
+    /// ticks add up to the same change as one long one.
+    pub fn apply_to_deposits(&self, weather: &WeatherSystem, deposits: &mut TileDeposits, delta: f32) {
+        for effect in self.matching(weather).flat_map(|rule| rule.effects.iter()) {
+            match *effect {
+                WeatherEffect::Refill { resource, rate } => {
+                    deposits.refill(resource, rate as f64 * weather.precipitation as f64 * delta as f64);
+                }
+                WeatherEffect::Damage { resource, rate } => {
+                    for deposit in deposits.deposits.iter_mut().filter(|d| d.resource_type == resource) {
+                        deposit.damage(rate * delta);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+}
+
+/// Loads weather rules from the definitions file, falling back to the built-in set
+pub fn load_weather_rules(
+    registry: Res<ResourceRegistry>,
+    mut rules: ResMut<WeatherRules>,
+) {
+    let loaded = WeatherRules::load(WEATHER_RULES_PATH, &registry).or_else(|err| {
+        warn!("Using built-in weather rules: {}", err);
+        WeatherRules::from_ron_str(DEFAULT_WEATHER_RULES, &registry)
+    });
+
+    match loaded {
+        Ok(loaded) => {
+            if loaded.is_empty() {
+                warn!("No weather rules defined; weather won't affect agents or deposits");
+            } else {
+                info!("Loaded {} weather rules", loaded.len());
+            }
+            *rules = loaded;
+        }
+        Err(err) => {
+            error!("No usable weather rules: {}", err);
+            *rules = WeatherRules::default();
+        }
+    }
+}
+
+/// System applying the weather rules to agents and tile deposits
+///
+/// Agents get the modifiers of the weather cell they stand in; deposits
+/// are refilled or damaged by the weather over their tile.
+pub fn apply_weather_effects(
+    clock: Res<SimClock>,
+    grid: Res<WeatherGrid>,
+    rules: Res<WeatherRules>,
+    mut agents: Query<&mut Agent>,
+    mut tiles: Query<(&Transform, &mut TileDeposits)>,
+) {
+    for mut agent in agents.iter_mut() {
+        let modifiers = grid
+            .at_position(agent.position)
+            .map_or_else(WeatherModifiers::default, |weather| rules.modifiers(weather));
+        if agent.weather != modifiers {
+            agent.weather = modifiers;
+        }
+    }
+
+    let delta = clock.tick_length as f32;
+    if delta <= 0.0 {
+        return;
+    }
+    for (transform, mut deposits) in tiles.iter_mut() {
+        if let Some(weather) = grid.at_position(transform.translation.truncate()) {
+            rules.apply_to_deposits(weather, &mut deposits, delta);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::chunk::{Biome, Stratum, Tile, TileCoord};
+    use crate::world::hydrology::WaterState;
+    use crate::world::quantity::Quantity;
+
+    fn weather(temperature: f32, humidity: f32, precipitation: f32, wind_speed: f32) -> WeatherSystem {
+        WeatherSystem {
+            temperature,
+            humidity,
+            precipitation,
+            wind_speed,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_builtin_rules_modify_agents() {
+        let registry = ResourceRegistry::default();
+        let rules = WeatherRules::from_ron_str(DEFAULT_WEATHER_RULES, &registry).unwrap();
+
+        assert_eq!(rules.modifiers(&weather(18.0, 0.5, 0.0, 2.0)), WeatherModifiers::default());
+
+        let rain = rules.modifiers(&weather(12.0, 0.9, 2.0, 4.0));
+        let snow = rules.modifiers(&weather(-3.0, 0.9, 2.0, 4.0));
+        assert!(rain.movement < 1.0);
+        assert!(snow.movement < rain.movement);
+        assert!(snow.energy_drain > 1.0);
+
+        assert!(rules.modifiers(&weather(35.0, 0.3, 0.0, 2.0)).energy_drain > 1.0);
+        assert!(rules.modifiers(&weather(10.0, 0.98, 0.0, 1.0)).perception < 0.5);
+
+        let unknown = r#"[(name: "acid rain", effects: [Damage(resource: "crops", rate: 0.1)])]"#;
+        assert!(WeatherRules::from_ron_str(unknown, &registry).is_err());
+    }
+
+    #[test]
+    fn test_rain_refills_and_frost_damages_deposits() {
+        let registry = ResourceRegistry::default();
+        let rules = WeatherRules::from_ron_str(DEFAULT_WEATHER_RULES, &registry).unwrap();
+        let water = registry.id("water").unwrap();
+        let food = registry.id("food").unwrap();
+
+        let lake = Tile {
+            coord: TileCoord::new(0, 0),
+            biome: Biome::Lake,
+            height: 0.0,
+            stratum: Stratum::Soil,
+            water: WaterState::Normal,
+        };
+        let mut deposits = TileDeposits::for_tile(&lake, &registry);
+        let full = deposits.get(water);
+        deposits.gather(water, Quantity::from_units(100));
+
+        // Ten seconds of real ticks; rain refills 2 units a second
+        let delta = SimClock::default().tick_length as f32;
+        let ticks = (10.0 / delta).round() as i32;
+        let rain = weather(12.0, 0.9, 4.0, 2.0);
+        for _ in 0..ticks {
+            rules.apply_to_deposits(&rain, &mut deposits, delta);
+        }
+        let refilled = deposits.get(water) - (full - Quantity::from_units(100));
+        assert!((refilled - Quantity::from_units(20)).abs() <= Quantity::from_millis(1), "refilled {}", refilled);
+
+        // Frost takes 0.2% a second, a fraction of a milliunit a tick
+        let field = Tile { biome: Biome::Plains, ..lake };
+        let mut deposits = TileDeposits::for_tile(&field, &registry);
+        let harvest = deposits.get(food);
+        let (mild, frost) = (weather(12.0, 0.5, 0.0, 2.0), weather(-4.0, 0.5, 0.0, 2.0));
+        for _ in 0..ticks {
+            rules.apply_to_deposits(&mild, &mut deposits, delta);
+        }
+        assert_eq!(deposits.get(food), harvest);
+        for _ in 0..ticks {
+            rules.apply_to_deposits(&frost, &mut deposits, delta);
+        }
+        let expected = harvest.to_f64() * (1.0 - (1.0 - 0.002 * delta as f64).powi(ticks));
+        let lost = (harvest - deposits.get(food)).to_f64();
+        assert!(expected > 0.01);
+        assert!((lost - expected).abs() <= 0.002, "lost {} instead of {}", lost, expected);
+    }
+}
@@ -0,0 +1,587 @@
+use bevy::prelude::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::HashMap;
+use crate::engine::storms::{advance_pressure_systems, find_fronts, Front, PressureSystem};
+use crate::engine::time::{SimClock, CalendarDate, AXIAL_TILT};
+use crate::engine::weather::WeatherSystem;
+use crate::engine::weather_scenario::WeatherScenarios;
+use crate::world::chunk::{ChunkCoord, LoadedChunks, WorldSeed};
+
+const TAU: f32 = std::f32::consts::PI * 2.0;
+
+/// Sea-level pressure the atmosphere relaxes towards, in hPa
+pub const STANDARD_PRESSURE: f32 = 1013.0;
+/// Fastest wind the model allows, in m/s
+const MAX_WIND_SPEED: f32 = 20.0;
+/// Wind gained per second for each hPa of pressure difference between neighbours
+const PRESSURE_WIND_FACTOR: f32 = 0.002;
+/// Fraction of wind lost to friction per second
+const WIND_DAMPING: f32 = 0.0005;
+/// Fraction of the gap to the daily base temperature closed per second
+const TEMPERATURE_RELAXATION: f32 = 0.0005;
+/// Fraction of the gap to standard pressure closed per second
+const PRESSURE_RELAXATION: f32 = 0.0002;
+/// Pressure drop in hPa per degree a cell is warmer than its base temperature
+const THERMAL_LOW: f32 = 0.8;
+/// Fraction of the gap to equilibrium humidity closed per second
+const EVAPORATION_RATE: f32 = 0.0002;
+/// Fraction of the gap to the humidity-driven cloud cover closed per second
+const CLOUD_FORMATION_RATE: f32 = 0.001;
+/// Rain in mm/hour for each unit of humidity above saturation, under full cloud
+const RAIN_FACTOR: f32 = 50.0;
+/// Humidity rained out per second for each mm/hour of precipitation
+const RAIN_DRYING: f32 = 0.00002;
+/// Humidity at which saturated air under heavy cloud starts to rain
+const SATURATION: f32 = 0.8;
+/// Drop in the humidity needed for rain per degree of temperature difference across a front
+const FRONT_LIFT: f32 = 0.01;
+/// Humidity drawn in per second for each hPa of low pressure
+const LOW_MOISTURE: f32 = 0.00001;
+
+/// Seasonal and latitudinal climate the weather grid relaxes towards
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Climate {
+    /// Latitude of the origin region, in degrees
+    pub origin_latitude: f32,
+    /// Degrees of latitude gained per region northwards
+    pub degrees_per_region: f32,
+    /// Annual mean temperature at the equator, in Celsius
+    pub equator_temperature: f32,
+    /// Drop in annual mean temperature per degree of latitude
+    pub latitude_gradient: f32,
+    /// Difference between midsummer and the annual mean at 45 degrees
+    pub seasonal_amplitude: f32,
+    /// Difference between mid-afternoon and the daily mean
+    pub daily_amplitude: f32,
+    /// Humidity the surface evaporates or condenses towards in each
+    /// season, in `Season::ALL` order
+    pub seasonal_humidity: [f32; 4],
+}
+
+impl Default for Climate {
+    fn default() -> Self {
+        Self {
+            origin_latitude: 45.0,
+            degrees_per_region: 0.5,
+            equator_temperature: 27.0,
+            latitude_gradient: 0.35,
+            seasonal_amplitude: 12.0,
+            daily_amplitude: 6.0,
+            // Wet springs and autumns, dry summers, damp winters
+            seasonal_humidity: [0.65, 0.5, 0.62, 0.7],
+        }
+    }
+}
+
+impl Climate {
+    /// Latitude of a region in degrees
+    pub fn latitude(&self, region: ChunkCoord) -> f32 {
+        (self.origin_latitude + region.y as f32 * self.degrees_per_region).clamp(-90.0, 90.0)
+    }
+
+    /// Temperature a region relaxes towards on a date
+    ///
+    /// Combines the annual mean for the latitude, the seasonal swing, which
+    /// grows towards the poles and is reversed south of the equator, and a
+    /// daily cycle peaking mid-afternoon that is stronger on long days.
+    pub fn base_temperature(&self, region: ChunkCoord, date: &CalendarDate) -> f32 {
+        let latitude = self.latitude(region);
+        let annual = self.equator_temperature - self.latitude_gradient * latitude.abs();
+        let seasonal = self.seasonal_amplitude
+            * (date.solar_declination() / AXIAL_TILT)
+            * (latitude / 45.0).clamp(-2.0, 2.0);
+        let daily = self.daily_amplitude
+            * (0.5 + date.daylight_fraction(latitude))
+            * ((date.time_of_day - 0.375) * TAU).sin();
+        annual + seasonal + daily
+    }
+
+    /// Humidity a region evaporates towards on a date
+    pub fn equilibrium_humidity(&self, region: ChunkCoord, date: &CalendarDate) -> f32 {
+        let season = date.season_at(self.latitude(region));
+        self.seasonal_humidity[season.index()]
+    }
+}
+
+/// Configuration of the weather grid
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WeatherGridConfig {
+    /// Side length of a weather cell, in chunks
+    pub region_size: i32,
+    /// Side length of a chunk, in world units
+    pub chunk_size: f32,
+    /// Distance across a cell in metres, used to advect with the wind
+    pub cell_width: f32,
+    /// Simulation seconds per model step
+    pub step: f32,
+    /// Fraction of the difference to the neighbours' mean exchanged per second
+    pub exchange_rate: f32,
+    /// Most steps run in one frame; time beyond that is dropped
+    pub max_steps_per_frame: u32,
+    pub climate: Climate,
+}
+
+impl Default for WeatherGridConfig {
+    fn default() -> Self {
+        Self {
+            region_size: 4,
+            chunk_size: 16.0,
+            cell_width: 10_000.0,
+            step: 60.0,
+            exchange_rate: 0.0005,
+            max_steps_per_frame: 10,
+            climate: Climate::default(),
+        }
+    }
+}
+
+/// Coarse grid of weather cells covering the loaded chunks
+///
+/// Each cell covers `region_size` × `region_size` chunks and is identified
+/// by its region coordinate, the `ChunkCoord` of the chunks divided by the
+/// region size. Every step the cells exchange heat and moisture with their
+/// neighbours, wind follows pressure differences, and temperature,
+/// humidity and cloud are carried downwind. Moving `PressureSystem`s
+/// raise or lower the pressure beneath them, and `Front`s where warm and
+/// cold air meet make rain more likely. Cells appear when chunks in
+/// their region load and disappear when the last one unloads; the region
+/// around the origin, where agents start, is always simulated.
+///
+/// The model follows the simulation `SimClock`: temperature and humidity
+/// relax towards the `Climate` of each cell's latitude for the date. It
+/// owns its random number generator and keeps its own copy of the
+/// clock, so a cloned grid evolves exactly like the original.
+///
+/// `WeatherScenarios` are laid over the cells after every step and lifted
+/// off again before the next, so the natural model runs on undisturbed
+/// and the scripted conditions only show in what the cells report.
+#[derive(Debug, Clone, Resource)]
+pub struct WeatherGrid {
+    pub config: WeatherGridConfig,
+    cells: HashMap<ChunkCoord, WeatherSystem>,
+    /// Conditions last reported through `WeatherChanged`
+    reported: HashMap<ChunkCoord, WeatherSystem>,
+    rng: StdRng,
+    pressure_systems: Vec<PressureSystem>,
+    /// Fronts found after the last step
+    fronts: Vec<Front>,
+    /// Clock the model's dates are computed with
+    clock: SimClock,
+    /// Simulation seconds the model has advanced
+    elapsed: f64,
+    /// Scripted scenarios laid over the cells after every step
+    scenarios: WeatherScenarios,
+    /// Natural conditions of the cells a scenario currently adjusts
+    natural: HashMap<ChunkCoord, WeatherSystem>,
+}
+
+impl Default for WeatherGrid {
+    fn default() -> Self {
+        Self::new(WeatherGridConfig::default(), 0)
+    }
+}
+
+impl WeatherGrid {
+    pub fn new(config: WeatherGridConfig, seed: u64) -> Self {
+        let mut grid = Self {
+            config,
+            cells: HashMap::new(),
+            reported: HashMap::new(),
+            rng: StdRng::seed_from_u64(seed),
+            pressure_systems: Vec::new(),
+            fronts: Vec::new(),
+            clock: SimClock::default(),
+            elapsed: 0.0,
+            scenarios: WeatherScenarios::default(),
+            natural: HashMap::new(),
+        };
+        grid.sync(std::iter::empty());
+        grid
+    }
+
+    /// Returns the region a chunk belongs to
+    pub fn region_of(&self, chunk: ChunkCoord) -> ChunkCoord {
+        let size = self.config.region_size.max(1);
+        ChunkCoord::new(chunk.x.div_euclid(size), chunk.y.div_euclid(size))
+    }
+
+    /// Returns the region containing a world position
+    pub fn region_at(&self, position: Vec2) -> ChunkCoord {
+        let chunk = (position / self.config.chunk_size).floor();
+        self.region_of(ChunkCoord::new(chunk.x as i32, chunk.y as i32))
+    }
+
+    pub fn cell(&self, region: ChunkCoord) -> Option<&WeatherSystem> {
+        self.cells.get(&region)
+    }
+
+    #[cfg(test)]
+    pub fn cell_mut(&mut self, region: ChunkCoord) -> Option<&mut WeatherSystem> {
+        self.cells.get_mut(&region)
+    }
+
+    /// Local conditions at a world position, if its region is simulated
+    pub fn at_position(&self, position: Vec2) -> Option<&WeatherSystem> {
+        self.cell(self.region_at(position))
+    }
+
+    /// Iterates over all cells in region order
+    pub fn cells(&self) -> impl Iterator<Item = (ChunkCoord, &WeatherSystem)> {
+        self.regions().into_iter().map(|region| (region, &self.cells[&region]))
+    }
+
+    /// Simulation seconds the model has advanced
+    pub fn elapsed(&self) -> f64 {
+        self.elapsed
+    }
+
+    /// Clock the model's dates are computed with
+    pub fn clock(&self) -> &SimClock {
+        &self.clock
+    }
+
+    /// Date the model has advanced to
+    pub fn date(&self) -> CalendarDate {
+        self.clock.date_at(self.elapsed)
+    }
+
+    /// Regions in a stable order, so steps are reproducible
+    fn regions(&self) -> Vec<ChunkCoord> {
+        let mut regions: Vec<ChunkCoord> = self.cells.keys().copied().collect();
+        regions.sort();
+        regions
+    }
+
+    /// Adds cells for the regions of the given chunks and removes the rest
+    ///
+    /// New cells start from the mean of their simulated neighbours.
+    pub fn sync(&mut self, loaded: impl Iterator<Item = ChunkCoord>) {
+        let mut wanted: Vec<ChunkCoord> = loaded.map(|chunk| self.region_of(chunk)).collect();
+        wanted.push(self.region_of(ChunkCoord::new(0, 0)));
+        wanted.sort();
+        wanted.dedup();
+
+        self.cells.retain(|region, _| wanted.binary_search(region).is_ok());
+        self.reported.retain(|region, _| wanted.binary_search(region).is_ok());
+        self.natural.retain(|region, _| wanted.binary_search(region).is_ok());
+        for region in wanted {
+            if self.cells.contains_key(&region) {
+                continue;
+            }
+            let neighbors: Vec<&WeatherSystem> = neighbor_regions(region)
+                .iter()
+                .filter_map(|neighbor| self.cells.get(neighbor))
+                .collect();
+            let cell = mean_weather(&neighbors).unwrap_or_default();
+            self.reported.insert(region, cell.clone());
+            self.cells.insert(region, cell);
+        }
+    }
+
+    /// Advances the model in whole steps up to the clock's time
+    pub fn advance(&mut self, clock: &SimClock) {
+        self.clock.clone_from(clock);
+        let step = self.config.step.max(f32::EPSILON);
+        let mut steps = 0;
+        while self.elapsed + step as f64 <= clock.elapsed() {
+            if steps == self.config.max_steps_per_frame {
+                self.elapsed = clock.elapsed();
+                break;
+            }
+            self.step(step);
+            steps += 1;
+        }
+    }
+
+    /// Runs the model ahead by `seconds` in whole steps, ignoring the
+    /// per-frame limit
+    pub fn run_for(&mut self, seconds: f64) {
+        let step = self.config.step.max(f32::EPSILON);
+        let target = self.elapsed + seconds;
+        while self.elapsed + step as f64 <= target {
+            self.step(step);
+        }
+    }
+
+    /// Replaces the random number generator, e.g. so a forecast run on a
+    /// clone does not see the real weather's future noise
+    pub fn reseed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    /// Temperature a cell relaxes towards at the model's current date
+    pub fn base_temperature(&self, region: ChunkCoord) -> f32 {
+        self.config.climate.base_temperature(region, &self.date())
+    }
+
+    /// Natural conditions of a cell, without the adjustments of active
+    /// scenarios
+    #[cfg(test)]
+    pub fn natural(&self, region: ChunkCoord) -> Option<&WeatherSystem> {
+        self.natural.get(&region).or_else(|| self.cells.get(&region))
+    }
+
+    /// Replaces the scenarios laid over the model, taking effect at once
+    pub fn set_scenarios(&mut self, scenarios: &WeatherScenarios) {
+        self.remove_overlay();
+        self.scenarios.clone_from(scenarios);
+        self.apply_overlay();
+    }
+
+    /// Restores the natural conditions of cells adjusted by scenarios
+    fn remove_overlay(&mut self) {
+        for (region, natural) in self.natural.drain() {
+            if let Some(cell) = self.cells.get_mut(&region) {
+                *cell = natural;
+            }
+        }
+    }
+
+    /// Adjusts cells by the scenarios active now, remembering their
+    /// natural conditions
+    fn apply_overlay(&mut self) {
+        if self.scenarios.is_empty() {
+            return;
+        }
+        for (region, cell) in self.cells.iter_mut() {
+            let natural = cell.clone();
+            let latitude = self.config.climate.latitude(*region);
+            if self.scenarios.apply(*region, latitude, &self.clock, self.elapsed, cell) {
+                self.natural.insert(*region, natural);
+            }
+        }
+    }
+
+    /// Runs one model step of `dt` seconds over every cell
+    pub fn step(&mut self, dt: f32) {
+        self.remove_overlay();
+        self.elapsed += dt as f64;
+        let date = self.date();
+        let climate = self.config.climate;
+        let previous = self.cells.clone();
+        let cell_width = self.config.cell_width.max(1.0);
+        let courant = dt / cell_width;
+        let exchange = (self.config.exchange_rate * dt).min(1.0);
+        let regions = self.regions();
+
+        // Pressure systems are steered by the wind beneath them
+        advance_pressure_systems(&mut self.pressure_systems, &regions, dt, self.clock.day_length, &mut self.rng, |center| {
+            let below = ChunkCoord::new(center.x.round() as i32, center.y.round() as i32);
+            previous.get(&below).map_or(Vec2::ZERO, |cell| cell.wind() / cell_width)
+        });
+        let mut lift: HashMap<ChunkCoord, f32> = HashMap::new();
+        for front in &self.fronts {
+            for side in [front.warm, front.cold] {
+                let strength = lift.entry(side).or_default();
+                *strength = strength.max(front.strength);
+            }
+        }
+
+        for region in regions {
+            let cell = &previous[&region];
+            let [east, west, north, south] = neighbor_regions(region).map(|neighbor| previous.get(&neighbor).unwrap_or(cell));
+            let neighbors = [east, west, north, south];
+
+            // Wind blows from high to low pressure and slows by friction
+            let gradient = Vec2::new(east.pressure - west.pressure, north.pressure - south.pressure) / 2.0;
+            let gust = Vec2::new(self.rng.gen::<f32>() - 0.5, self.rng.gen::<f32>() - 0.5) * 0.01 * dt;
+            let mut wind = cell.wind() * (1.0 - WIND_DAMPING * dt).max(0.0) - gradient * PRESSURE_WIND_FACTOR * dt + gust;
+            wind = wind.clamp_length_max(MAX_WIND_SPEED);
+
+            // Carry properties downwind from the upwind neighbours, then mix
+            // with all neighbours
+            let upwind_x = if wind.x > 0.0 { west } else { east };
+            let upwind_y = if wind.y > 0.0 { south } else { north };
+            let (cx, cy) = ((wind.x.abs() * courant).min(0.5), (wind.y.abs() * courant).min(0.5));
+            let transport = |value: fn(&WeatherSystem) -> f32| {
+                let own = value(cell);
+                let advected = own + cx * (value(upwind_x) - own) + cy * (value(upwind_y) - own);
+                let mean = neighbors.iter().map(|neighbor| value(neighbor)).sum::<f32>() / 4.0;
+                advected + exchange * (mean - advected)
+            };
+            let mut temperature = transport(|weather| weather.temperature);
+            let mut humidity = transport(|weather| weather.humidity);
+            let mut cloud_cover = transport(|weather| weather.cloud_cover);
+            let mut pressure = transport(|weather| weather.pressure);
+
+            // Local forcing: the climate for the date, thermal lows and
+            // evaporation
+            let base = climate.base_temperature(region, &date);
+            temperature += (base - temperature) * (TEMPERATURE_RELAXATION * dt).min(1.0)
+                + (self.rng.gen::<f32>() - 0.5) * 0.02 * dt.sqrt();
+            let anomaly: f32 = self.pressure_systems.iter().map(|system| system.anomaly(region)).sum();
+            let target_pressure = STANDARD_PRESSURE - (temperature - base) * THERMAL_LOW + anomaly;
+            pressure += (target_pressure - pressure) * (PRESSURE_RELAXATION * dt).min(1.0)
+                + (self.rng.gen::<f32>() - 0.5) * 0.1 * dt.sqrt();
+            humidity += (climate.equilibrium_humidity(region, &date) - humidity) * (EVAPORATION_RATE * dt).min(1.0)
+                + (self.rng.gen::<f32>() - 0.5) * 0.002 * dt.sqrt()
+                - anomaly.min(0.0) * LOW_MOISTURE * dt;
+            humidity = humidity.clamp(0.0, 1.0);
+            let target_cloud = ((humidity - 0.4) / 0.5).clamp(0.0, 1.0);
+            cloud_cover += (target_cloud - cloud_cover) * (CLOUD_FORMATION_RATE * dt).min(1.0);
+            cloud_cover = cloud_cover.clamp(0.0, 1.0);
+
+            // Saturated air under heavy cloud rains itself out, sooner
+            // where it is lifted over a front
+            let saturation = (SATURATION - FRONT_LIFT * lift.get(&region).copied().unwrap_or(0.0)).max(0.65);
+            let precipitation = if humidity > saturation && cloud_cover > 0.6 {
+                (humidity - saturation) * RAIN_FACTOR * cloud_cover
+            } else {
+                cell.precipitation * 0.95f32.powf(dt)
+            };
+            humidity = (humidity - precipitation * RAIN_DRYING * dt).clamp(0.0, 1.0);
+
+            let next = self.cells.get_mut(&region).unwrap();
+            next.temperature = temperature;
+            next.humidity = humidity;
+            next.pressure = pressure;
+            next.cloud_cover = cloud_cover;
+            next.precipitation = precipitation.max(0.0);
+            next.set_wind(wind);
+        }
+
+        let cells = &self.cells;
+        self.fronts = find_fronts(self.cells(), |region| cells.get(&region));
+        self.apply_overlay();
+    }
+
+    /// Returns the cells whose conditions changed significantly since they
+    /// were last returned, in region order
+    pub fn take_changes(&mut self) -> Vec<(ChunkCoord, WeatherSystem)> {
+        let mut changes = Vec::new();
+        for region in self.regions() {
+            let cell = &self.cells[&region];
+            let significant = self
+                .reported
+                .get(&region)
+                .is_none_or(|reported| reported.differs_significantly(cell));
+            if significant {
+                self.reported.insert(region, cell.clone());
+                changes.push((region, cell.clone()));
+            }
+        }
+        changes
+    }
+}
+
+/// Regions east, west, north and south of a region
+fn neighbor_regions(region: ChunkCoord) -> [ChunkCoord; 4] {
+    [
+        ChunkCoord::new(region.x + 1, region.y),
+        ChunkCoord::new(region.x - 1, region.y),
+        ChunkCoord::new(region.x, region.y + 1),
+        ChunkCoord::new(region.x, region.y - 1),
+    ]
+}
+
+/// Averages the conditions of several cells
+fn mean_weather(cells: &[&WeatherSystem]) -> Option<WeatherSystem> {
+    if cells.is_empty() {
+        return None;
+    }
+    let count = cells.len() as f32;
+    let mean = |value: fn(&WeatherSystem) -> f32| cells.iter().map(|cell| value(cell)).sum::<f32>() / count;
+    let mut weather = WeatherSystem {
+        temperature: mean(|cell| cell.temperature),
+        humidity: mean(|cell| cell.humidity),
+        pressure: mean(|cell| cell.pressure),
+        precipitation: mean(|cell| cell.precipitation),
+        cloud_cover: mean(|cell| cell.cloud_cover),
+        ..Default::default()
+    };
+    let wind = cells.iter().map(|cell| cell.wind()).sum::<Vec2>() / count;
+    weather.set_wind(wind);
+    Some(weather)
+}
+
+/// Seeds the weather model from the world seed
+pub fn seed_weather_grid(seed: Option<Res<WorldSeed>>, mut grid: ResMut<WeatherGrid>) {
+    if let Some(seed) = seed {
+        *grid = WeatherGrid::new(grid.config, seed.0 as u64);
+    }
+}
+
+/// Keeps the weather grid's cells in line with the loaded chunks
+pub fn sync_weather_grid(loaded: Option<Res<LoadedChunks>>, mut grid: ResMut<WeatherGrid>) {
+    let Some(loaded) = loaded else {
+        return;
+    };
+    if loaded.is_changed() {
+        grid.sync(loaded.chunks.keys().copied());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_regions_cover_negative_chunks() {
+        let grid = WeatherGrid::default();
+        assert_eq!(grid.region_of(ChunkCoord::new(3, 4)), ChunkCoord::new(0, 1));
+        assert_eq!(grid.region_of(ChunkCoord::new(-1, -4)), ChunkCoord::new(-1, -1));
+        assert_eq!(grid.region_at(Vec2::new(-0.5, 65.0)), ChunkCoord::new(-1, 1));
+        assert!(grid.at_position(Vec2::ZERO).is_some());
+    }
+
+    #[test]
+    fn test_sync_follows_loaded_chunks() {
+        let mut grid = WeatherGrid::default();
+        grid.cell_mut(ChunkCoord::new(0, 0)).unwrap().temperature = 30.0;
+
+        grid.sync([ChunkCoord::new(4, 0), ChunkCoord::new(5, 1)].into_iter());
+        assert_eq!(grid.cells().count(), 2);
+        // The new cell starts from its neighbour
+        assert_eq!(grid.cell(ChunkCoord::new(1, 0)).unwrap().temperature, 30.0);
+
+        grid.sync(std::iter::empty());
+        assert_eq!(grid.cells().map(|(region, _)| region).collect::<Vec<_>>(), vec![ChunkCoord::new(0, 0)]);
+    }
+
+    #[test]
+    fn test_wind_carries_humidity_downwind() {
+        let mut grid = WeatherGrid::new(WeatherGridConfig { exchange_rate: 0.0, ..Default::default() }, 7);
+        grid.sync((0..3).map(|x| ChunkCoord::new(x * 4, 0)));
+        for (x, humidity) in [(0, 0.9), (1, 0.3), (2, 0.3)] {
+            let cell = grid.cell_mut(ChunkCoord::new(x, 0)).unwrap();
+            cell.humidity = humidity;
+            cell.set_wind(Vec2::new(10.0, 0.0));
+        }
+
+        grid.step(60.0);
+        let middle = grid.cell(ChunkCoord::new(1, 0)).unwrap().humidity;
+        let east = grid.cell(ChunkCoord::new(2, 0)).unwrap().humidity;
+        assert!(middle > 0.3 + 0.02, "humidity should arrive from the west, got {}", middle);
+        assert!(east < middle);
+    }
+
+    #[test]
+    fn test_cloned_grid_evolves_identically() {
+        let mut grid = WeatherGrid::new(WeatherGridConfig::default(), 3);
+        grid.sync((0..4).map(|x| ChunkCoord::new(x * 4, x * 4)));
+        let mut copy = grid.clone();
+        let mut clock = SimClock::default();
+        clock.advance(3600.0);
+        grid.advance(&clock);
+        copy.advance(&clock);
+        let cells: Vec<_> = grid.cells().map(|(region, cell)| (region, cell.clone())).collect();
+        let copied: Vec<_> = copy.cells().map(|(region, cell)| (region, cell.clone())).collect();
+        assert_eq!(cells, copied);
+    }
+
+    #[test]
+    fn test_climate_follows_seasons_and_latitude() {
+        let climate = Climate::default();
+        let clock = SimClock::default();
+        let afternoon = |season: f64| clock.date_at(clock.year_length() * season + clock.day_length as f64 * 0.625);
+        let (summer, winter) = (afternoon(0.25), afternoon(0.75));
+        let (home, north, south) = (ChunkCoord::new(0, 0), ChunkCoord::new(0, 40), ChunkCoord::new(0, -180));
+
+        assert!(climate.base_temperature(home, &summer) > climate.base_temperature(home, &winter) + 15.0);
+        assert!(climate.base_temperature(north, &summer) < climate.base_temperature(home, &summer));
+        // Seasons are reversed south of the equator
+        assert!(climate.base_temperature(south, &summer) < climate.base_temperature(south, &winter));
+        assert!(climate.equilibrium_humidity(home, &summer) < climate.equilibrium_humidity(home, &winter));
+    }
+}
@@ -0,0 +1,469 @@
+use bevy::prelude::*;
+use std::collections::{HashMap, VecDeque};
+use crate::agents::agent::Agent;
+use crate::engine::time::{CalendarDate, Season};
+use crate::engine::weather::WeatherSystem;
+use crate::engine::weather_grid::WeatherGrid;
+use crate::world::chunk::ChunkCoord;
+
+/// Memory key under which agents keep how the recent weather compares
+/// with the season
+pub const RECENT_WEATHER_MEMORY: &str = "recent_weather";
+
+/// Completed days agents look back on when judging the weather
+const RECENT_DAYS: usize = 7;
+
+/// Completed days of a season needed before its normals are reported
+const MIN_NORMAL_DAYS: usize = 5;
+
+/// Standard errors from normal at which weather counts as unusual
+const UNUSUAL_SCORE: f32 = 1.5;
+
+/// Configuration of the weather history
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WeatherHistoryConfig {
+    /// Simulation seconds between samples
+    pub sample_interval: f64,
+    /// Samples kept per region
+    pub samples: usize,
+    /// Daily aggregates kept per region
+    pub days: usize,
+}
+
+impl Default for WeatherHistoryConfig {
+    fn default() -> Self {
+        Self {
+            // An hour of the default clock
+            sample_interval: 60.0,
+            samples: 168,
+            days: 360,
+        }
+    }
+}
+
+/// Conditions in a region at one moment
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WeatherSample {
+    /// Simulation seconds since startup
+    pub time: f64,
+    pub temperature: f32,
+    pub humidity: f32,
+    pub pressure: f32,
+    pub precipitation: f32,
+    pub wind_speed: f32,
+}
+
+impl WeatherSample {
+    pub fn new(time: f64, weather: &WeatherSystem) -> Self {
+        Self {
+            time,
+            temperature: weather.temperature,
+            humidity: weather.humidity,
+            pressure: weather.pressure,
+            precipitation: weather.precipitation,
+            wind_speed: weather.wind_speed,
+        }
+    }
+}
+
+/// Weather of one calendar day in a region
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DailyWeather {
+    pub year: u32,
+    pub day_of_year: u32,
+    /// Season at the region's latitude
+    pub season: Season,
+    pub samples: u32,
+    pub mean_temperature: f32,
+    pub min_temperature: f32,
+    pub max_temperature: f32,
+    pub mean_humidity: f32,
+    /// Rain over the day in mm
+    pub precipitation: f32,
+    pub max_wind_speed: f32,
+}
+
+impl DailyWeather {
+    fn new(date: &CalendarDate, season: Season) -> Self {
+        Self {
+            year: date.year,
+            day_of_year: date.day_of_year,
+            season,
+            samples: 0,
+            mean_temperature: 0.0,
+            min_temperature: f32::INFINITY,
+            max_temperature: f32::NEG_INFINITY,
+            mean_humidity: 0.0,
+            precipitation: 0.0,
+            max_wind_speed: 0.0,
+        }
+    }
+
+    fn is_date(&self, date: &CalendarDate) -> bool {
+        self.year == date.year && self.day_of_year == date.day_of_year
+    }
+
+    /// Adds a sample covering `hours` of the day
+    fn add(&mut self, sample: &WeatherSample, hours: f32) {
+        self.samples += 1;
+        let weight = 1.0 / self.samples as f32;
+        self.mean_temperature += (sample.temperature - self.mean_temperature) * weight;
+        self.mean_humidity += (sample.humidity - self.mean_humidity) * weight;
+        self.min_temperature = self.min_temperature.min(sample.temperature);
+        self.max_temperature = self.max_temperature.max(sample.temperature);
+        self.precipitation += sample.precipitation * hours;
+        self.max_wind_speed = self.max_wind_speed.max(sample.wind_speed);
+    }
+}
+
+/// Climate normals and extremes summarising a set of days
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClimateNormals {
+    pub days: usize,
+    pub mean_temperature: f32,
+    /// Standard deviation of the daily mean temperature
+    pub temperature_deviation: f32,
+    /// Mean rain per day in mm
+    pub mean_precipitation: f32,
+    /// Standard deviation of the daily rain
+    pub precipitation_deviation: f32,
+    pub mean_humidity: f32,
+    pub record_high: f32,
+    pub record_low: f32,
+    /// Most rain on a single day
+    pub wettest_day: f32,
+    /// Share of days with at least 1 mm of rain
+    pub rainy_days: f32,
+}
+
+impl ClimateNormals {
+    /// Summarises a set of days, `None` if it is empty
+    pub fn from_days<'a>(days: impl IntoIterator<Item = &'a DailyWeather>) -> Option<Self> {
+        let days: Vec<&DailyWeather> = days.into_iter().collect();
+        if days.is_empty() {
+            return None;
+        }
+        let count = days.len() as f32;
+        let mean = |value: fn(&DailyWeather) -> f32| days.iter().map(|day| value(day)).sum::<f32>() / count;
+        let deviation = |value: fn(&DailyWeather) -> f32, mean: f32| {
+            (days.iter().map(|day| (value(day) - mean).powi(2)).sum::<f32>() / count).sqrt()
+        };
+
+        let mean_temperature = mean(|day| day.mean_temperature);
+        let mean_precipitation = mean(|day| day.precipitation);
+        Some(Self {
+            days: days.len(),
+            mean_temperature,
+            temperature_deviation: deviation(|day| day.mean_temperature, mean_temperature),
+            mean_precipitation,
+            precipitation_deviation: deviation(|day| day.precipitation, mean_precipitation),
+            mean_humidity: mean(|day| day.mean_humidity),
+            record_high: days.iter().map(|day| day.max_temperature).fold(f32::NEG_INFINITY, f32::max),
+            record_low: days.iter().map(|day| day.min_temperature).fold(f32::INFINITY, f32::min),
+            wettest_day: days.iter().map(|day| day.precipitation).fold(0.0, f32::max),
+            rainy_days: days.iter().filter(|day| day.precipitation >= 1.0).count() as f32 / count,
+        })
+    }
+}
+
+/// How recent days differ from the climate normals of their season
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WeatherAnomaly {
+    pub days: usize,
+    /// Mean daily temperature above normal, in °C
+    pub temperature: f32,
+    /// Mean daily rain above normal, in mm
+    pub precipitation: f32,
+    /// `temperature` in standard errors of a mean over `days`
+    pub temperature_score: f32,
+    /// `precipitation` in standard errors of a mean over `days`
+    pub precipitation_score: f32,
+}
+
+impl WeatherAnomaly {
+    pub fn is_unusually_warm(&self) -> bool {
+        self.temperature_score > UNUSUAL_SCORE
+    }
+
+    pub fn is_unusually_cold(&self) -> bool {
+        self.temperature_score < -UNUSUAL_SCORE
+    }
+
+    pub fn is_unusually_wet(&self) -> bool {
+        self.precipitation_score > UNUSUAL_SCORE
+    }
+
+    pub fn is_unusually_dry(&self) -> bool {
+        self.precipitation_score < -UNUSUAL_SCORE
+    }
+
+    /// Plain description for agents and prompts
+    pub fn describe(&self) -> String {
+        let mut unusual = Vec::new();
+        if self.is_unusually_warm() {
+            unusual.push("warm");
+        } else if self.is_unusually_cold() {
+            unusual.push("cold");
+        }
+        if self.is_unusually_wet() {
+            unusual.push("wet");
+        } else if self.is_unusually_dry() {
+            unusual.push("dry");
+        }
+
+        if unusual.is_empty() {
+            format!("The last {} days have been normal for the season.", self.days)
+        } else {
+            format!(
+                "The last {} days have been unusually {} for the season ({:+.1}°C, {:+.1} mm of rain a day).",
+                self.days,
+                unusual.join(" and "),
+                self.temperature,
+                self.precipitation
+            )
+        }
+    }
+}
+
+/// Recent samples and daily aggregates of one region
+#[derive(Debug, Clone, Default)]
+pub struct RegionHistory {
+    samples: VecDeque<WeatherSample>,
+    days: VecDeque<DailyWeather>,
+    /// Day still being aggregated
+    today: Option<DailyWeather>,
+}
+
+impl RegionHistory {
+    /// Most recent samples, oldest first
+    #[cfg(test)]
+    pub fn samples(&self) -> impl Iterator<Item = &WeatherSample> {
+        self.samples.iter()
+    }
+
+    /// Completed days, oldest first
+    #[cfg(test)]
+    pub fn days(&self) -> impl Iterator<Item = &DailyWeather> {
+        self.days.iter()
+    }
+
+    /// Records a sample covering `hours` on a date
+    pub fn record(&mut self, config: &WeatherHistoryConfig, sample: WeatherSample, date: &CalendarDate, season: Season, hours: f32) {
+        if self.today.is_some_and(|today| !today.is_date(date)) {
+            self.days.extend(self.today.take());
+            while self.days.len() > config.days {
+                self.days.pop_front();
+            }
+        }
+        self.today
+            .get_or_insert_with(|| DailyWeather::new(date, season))
+            .add(&sample, hours);
+
+        self.samples.push_back(sample);
+        while self.samples.len() > config.samples {
+            self.samples.pop_front();
+        }
+    }
+
+    /// Normals over every kept day, or the days of one season
+    ///
+    /// `None` until enough days have been completed.
+    pub fn normals(&self, season: Option<Season>) -> Option<ClimateNormals> {
+        let days: Vec<&DailyWeather> = self
+            .days
+            .iter()
+            .filter(|day| season.is_none_or(|season| day.season == season))
+            .collect();
+        if days.len() < MIN_NORMAL_DAYS {
+            return None;
+        }
+        ClimateNormals::from_days(days)
+    }
+
+    /// Compares the last `days` completed days with the normals of their seasons
+    pub fn anomaly(&self, days: usize) -> Option<WeatherAnomaly> {
+        let recent: Vec<&DailyWeather> = self.days.iter().rev().take(days).collect();
+        if recent.is_empty() {
+            return None;
+        }
+
+        let mut normals: HashMap<Season, ClimateNormals> = HashMap::new();
+        let (mut temperature, mut precipitation) = (0.0, 0.0);
+        let (mut temperature_variance, mut precipitation_variance) = (0.0, 0.0);
+        for day in &recent {
+            let normal = match normals.get(&day.season) {
+                Some(normal) => *normal,
+                None => {
+                    let normal = self.normals(Some(day.season))?;
+                    normals.insert(day.season, normal);
+                    normal
+                }
+            };
+            temperature += day.mean_temperature - normal.mean_temperature;
+            precipitation += day.precipitation - normal.mean_precipitation;
+            temperature_variance += normal.temperature_deviation.powi(2);
+            precipitation_variance += normal.precipitation_deviation.powi(2);
+        }
+
+        let count = recent.len() as f32;
+        let score = |sum: f32, variance: f32| if variance > f32::EPSILON { sum / variance.sqrt() } else { 0.0 };
+        Some(WeatherAnomaly {
+            days: recent.len(),
+            temperature: temperature / count,
+            precipitation: precipitation / count,
+            temperature_score: score(temperature, temperature_variance),
+            precipitation_score: score(precipitation, precipitation_variance),
+        })
+    }
+}
+
+/// Resource keeping the past weather of every simulated region
+///
+/// Samples the `WeatherGrid` at a fixed interval into a ring buffer per
+/// region and folds them into daily aggregates, from which climate
+/// normals, extremes and anomalies are computed. Regions keep their
+/// history when they stop being simulated.
+#[derive(Debug, Default, Resource)]
+pub struct WeatherHistory {
+    pub config: WeatherHistoryConfig,
+    regions: HashMap<ChunkCoord, RegionHistory>,
+    last_sample: Option<f64>,
+}
+
+impl WeatherHistory {
+    pub fn region(&self, region: ChunkCoord) -> Option<&RegionHistory> {
+        self.regions.get(&region)
+    }
+
+    /// History of the region containing a world position
+    pub fn at_position(&self, grid: &WeatherGrid, position: Vec2) -> Option<&RegionHistory> {
+        self.region(grid.region_at(position))
+    }
+
+    /// Samples every cell of the grid once the sample interval has passed
+    pub fn record(&mut self, grid: &WeatherGrid) {
+        let now = grid.elapsed();
+        let since = match self.last_sample {
+            Some(last) if now - last < self.config.sample_interval => return,
+            Some(last) => now - last,
+            None => self.config.sample_interval,
+        };
+        self.last_sample = Some(now);
+
+        let hour = grid.clock().hour_length().max(f64::EPSILON);
+        let hours = (since / hour) as f32;
+        let date = grid.date();
+        for (region, weather) in grid.cells() {
+            let season = date.season_at(grid.config.climate.latitude(region));
+            self.regions
+                .entry(region)
+                .or_default()
+                .record(&self.config, WeatherSample::new(now, weather), &date, season, hours);
+        }
+    }
+}
+
+/// System sampling the weather into the history
+pub fn record_weather_history(grid: Res<WeatherGrid>, mut history: ResMut<WeatherHistory>) {
+    history.record(&grid);
+}
+
+/// System where agents note whether the last week was unusual for the
+/// season where they stand
+pub fn remember_recent_weather(grid: Res<WeatherGrid>, history: Res<WeatherHistory>, mut agents: Query<&mut Agent>) {
+    for mut agent in agents.iter_mut() {
+        let Some(anomaly) = history
+            .at_position(&grid, agent.position)
+            .and_then(|region| region.anomaly(RECENT_DAYS))
+        else {
+            continue;
+        };
+        let note = anomaly.describe();
+        if agent.memory.get(RECENT_WEATHER_MEMORY) != Some(&note) {
+            agent.memory.insert(RECENT_WEATHER_MEMORY.to_string(), note);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+    use crate::engine::time::SimClock;
+
+    #[test]
+    fn test_history_keeps_samples_and_daily_normals() {
+        let mut grid = WeatherGrid::default();
+        let mut history = WeatherHistory::default();
+        let day = grid.clock().day_length as f64;
+        // Twenty days of spring
+        for _ in 0..(20 * 24) {
+            grid.run_for(history.config.sample_interval);
+            history.record(&grid);
+        }
+
+        let origin = history.region(ChunkCoord::new(0, 0)).unwrap();
+        assert_eq!(origin.samples().count(), history.config.samples);
+        assert_eq!(origin.days().count(), 20);
+        let first = origin.days().next().unwrap();
+        assert!(first.min_temperature <= first.mean_temperature && first.mean_temperature <= first.max_temperature);
+
+        // The model keeps to the climate it is driven by
+        let normals = origin.normals(Some(Season::Spring)).unwrap();
+        assert!(origin.normals(Some(Season::Summer)).is_none());
+        let expected = (0..20)
+            .map(|d| (0..24).map(|h| grid.config.climate.base_temperature(
+                ChunkCoord::new(0, 0),
+                &grid.clock().date_at(d as f64 * day + h as f64 * day / 24.0),
+            )).sum::<f32>() / 24.0)
+            .sum::<f32>() / 20.0;
+        assert!((normals.mean_temperature - expected).abs() < 3.0);
+        assert!(normals.record_low <= normals.mean_temperature && normals.mean_temperature <= normals.record_high);
+    }
+
+    #[test]
+    fn test_dry_spell_is_an_anomaly() {
+        let config = WeatherHistoryConfig::default();
+        let clock = SimClock::default();
+        let mut region = RegionHistory::default();
+        let hour = clock.hour_length();
+        // Rainy spring days followed by a dry week
+        for h in 0..(30 * 24) {
+            let time = h as f64 * hour;
+            let weather = WeatherSystem {
+                temperature: 15.0 + (h % 24) as f32 * 0.1,
+                precipitation: if h < 23 * 24 { 1.0 } else { 0.0 },
+                ..Default::default()
+            };
+            let date = clock.date_at(time);
+            region.record(&config, WeatherSample::new(time, &weather), &date, date.season, 1.0);
+        }
+
+        let anomaly = region.anomaly(6).unwrap();
+        assert!(anomaly.is_unusually_dry());
+        assert!(!anomaly.is_unusually_warm() && !anomaly.is_unusually_cold());
+        assert!(anomaly.describe().contains("unusually dry"));
+
+        let wet = region.anomaly(29).unwrap();
+        assert!(!wet.is_unusually_dry());
+    }
+
+    #[test]
+    fn test_agents_remember_how_the_week_compares() {
+        let mut grid = WeatherGrid::default();
+        let mut history = WeatherHistory::default();
+        for _ in 0..(8 * 24) {
+            grid.run_for(history.config.sample_interval);
+            history.record(&grid);
+        }
+
+        let mut world = World::new();
+        world.insert_resource(grid);
+        world.insert_resource(history);
+        let agent = world.spawn(Agent::default()).id();
+        world.run_system_once(remember_recent_weather).unwrap();
+
+        let note = &world.get::<Agent>(agent).unwrap().memory[RECENT_WEATHER_MEMORY];
+        assert!(note.starts_with("The last 7 days have been"));
+    }
+}
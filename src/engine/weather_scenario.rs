@@ -0,0 +1,382 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+use crate::engine::time::{SimClock, Season};
+use crate::engine::weather::WeatherSystem;
+use crate::world::chunk::ChunkCoord;
+
+/// Path of the weather scenarios loaded at startup
+pub const WEATHER_SCENARIOS_PATH: &str = "assets/data/weather_scenarios.ron";
+
+/// Built-in copy of the weather scenarios, used when the file can't be read
+const DEFAULT_WEATHER_SCENARIOS: &str = include_str!("../../assets/data/weather_scenarios.ron");
+
+/// Field of `WeatherSystem` a scenario can adjust
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum WeatherField {
+    Temperature,
+    Humidity,
+    Pressure,
+    Precipitation,
+    WindSpeed,
+    CloudCover,
+}
+
+impl WeatherField {
+    fn get_mut(self, weather: &mut WeatherSystem) -> &mut f32 {
+        match self {
+            Self::Temperature => &mut weather.temperature,
+            Self::Humidity => &mut weather.humidity,
+            Self::Pressure => &mut weather.pressure,
+            Self::Precipitation => &mut weather.precipitation,
+            Self::WindSpeed => &mut weather.wind_speed,
+            Self::CloudCover => &mut weather.cloud_cover,
+        }
+    }
+
+    /// Keeps a value within the field's physical range
+    fn clamp(self, value: f32) -> f32 {
+        match self {
+            Self::Humidity | Self::CloudCover => value.clamp(0.0, 1.0),
+            Self::Precipitation | Self::WindSpeed | Self::Pressure => value.max(0.0),
+            Self::Temperature => value,
+        }
+    }
+}
+
+/// Change a scenario makes to a field
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum Adjustment {
+    /// Replaces the natural value
+    Set(f32),
+    /// Adds to the natural value
+    Add(f32),
+    /// Multiplies the natural value
+    Scale(f32),
+}
+
+impl Adjustment {
+    /// Applies the adjustment at a strength from 0.0 (none) to 1.0 (full)
+    pub fn apply(self, value: f32, strength: f32) -> f32 {
+        match self {
+            Self::Set(target) => value + (target - value) * strength,
+            Self::Add(offset) => value + offset * strength,
+            Self::Scale(factor) => value * (1.0 + (factor - 1.0) * strength),
+        }
+    }
+}
+
+/// Regions a scenario covers, in weather grid region coordinates
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+pub enum ScenarioArea {
+    #[default]
+    Everywhere,
+    Regions(Vec<(i32, i32)>),
+    /// Every region in an inclusive rectangle
+    Area { min: (i32, i32), max: (i32, i32) },
+}
+
+impl ScenarioArea {
+    pub fn contains(&self, region: ChunkCoord) -> bool {
+        match self {
+            Self::Everywhere => true,
+            Self::Regions(regions) => regions.contains(&(region.x, region.y)),
+            Self::Area { min, max } => {
+                (min.0..=max.0).contains(&region.x) && (min.1..=max.1).contains(&region.y)
+            }
+        }
+    }
+}
+
+/// When a scenario applies
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum ScenarioWindow {
+    /// Once, from a day counted from the calendar's epoch
+    Once { start_day: f64, days: f64 },
+    /// The same days of every year
+    Yearly { day_of_year: f64, days: f64 },
+    /// Every year during a season at the region's latitude
+    Season(Season),
+}
+
+/// A scripted change to the weather of some regions over a time window
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct WeatherScenario {
+    pub name: String,
+    #[serde(default)]
+    pub area: ScenarioArea,
+    pub window: ScenarioWindow,
+    /// Days over which the adjustments fade in and out
+    #[serde(default)]
+    pub ramp_days: f64,
+    pub adjust: Vec<(WeatherField, Adjustment)>,
+}
+
+impl WeatherScenario {
+    #[cfg(test)]
+    pub fn new(name: impl Into<String>, window: ScenarioWindow) -> Self {
+        Self {
+            name: name.into(),
+            area: ScenarioArea::Everywhere,
+            window,
+            ramp_days: 0.0,
+            adjust: Vec::new(),
+        }
+    }
+
+    #[cfg(test)]
+    pub fn adjust(mut self, field: WeatherField, adjustment: Adjustment) -> Self {
+        self.adjust.push((field, adjustment));
+        self
+    }
+
+    /// Whether the scenario will never apply again after a day counted
+    /// from the calendar's epoch
+    pub fn has_ended(&self, day: f64) -> bool {
+        match self.window {
+            ScenarioWindow::Once { start_day, days } => day >= start_day + days,
+            ScenarioWindow::Yearly { .. } | ScenarioWindow::Season(_) => false,
+        }
+    }
+
+    /// How strongly the scenario applies to a region at a time, 0.0 when
+    /// it doesn't
+    pub fn strength(&self, region: ChunkCoord, latitude: f32, clock: &SimClock, elapsed: f64) -> f32 {
+        if !self.area.contains(region) {
+            return 0.0;
+        }
+        let day = clock.days_at(elapsed);
+        let (into, length) = match self.window {
+            ScenarioWindow::Once { start_day, days } => (day - start_day, days),
+            ScenarioWindow::Yearly { day_of_year, days } => {
+                ((day - day_of_year).rem_euclid(clock.days_per_year.max(1) as f64), days)
+            }
+            ScenarioWindow::Season(season) => {
+                let in_season = clock.date_at(elapsed).season_at(latitude) == season;
+                return if in_season { 1.0 } else { 0.0 };
+            }
+        };
+        if into < 0.0 || into >= length {
+            return 0.0;
+        }
+        if self.ramp_days <= 0.0 {
+            return 1.0;
+        }
+        (into.min(length - into) / self.ramp_days).min(1.0) as f32
+    }
+}
+
+/// Identifies a scenario added to `WeatherScenarios`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ScenarioId(pub u64);
+
+/// Resource holding the scripted weather scenarios
+///
+/// Scenarios come from the scenarios file and can be added or removed at
+/// runtime. The `WeatherGrid` keeps a copy and lays their adjustments over
+/// its cells after every step; the natural model keeps running underneath
+/// and is not influenced by them.
+#[derive(Debug, Clone, Default, PartialEq, Resource)]
+pub struct WeatherScenarios {
+    scenarios: Vec<(ScenarioId, WeatherScenario)>,
+    next_id: u64,
+}
+
+impl WeatherScenarios {
+    /// Parses a RON list of weather scenarios
+    pub fn from_ron_str(source: &str) -> Result<Self, String> {
+        let definitions: Vec<WeatherScenario> = ron::from_str(source)
+            .map_err(|err| format!("invalid weather scenarios: {}", err))?;
+
+        let mut scenarios = Self::default();
+        for scenario in definitions {
+            scenarios.add(scenario);
+        }
+        Ok(scenarios)
+    }
+
+    /// Loads weather scenarios from a RON file
+    pub fn load(path: &str) -> Result<Self, String> {
+        let source = std::fs::read_to_string(path)
+            .map_err(|err| format!("failed to read {}: {}", path, err))?;
+        Self::from_ron_str(&source)
+    }
+
+    pub fn add(&mut self, scenario: WeatherScenario) -> ScenarioId {
+        let id = ScenarioId(self.next_id);
+        self.next_id += 1;
+        self.scenarios.push((id, scenario));
+        id
+    }
+
+    pub fn remove(&mut self, id: ScenarioId) -> Option<WeatherScenario> {
+        let index = self.scenarios.iter().position(|(existing, _)| *existing == id)?;
+        Some(self.scenarios.remove(index).1)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (ScenarioId, &WeatherScenario)> {
+        self.scenarios.iter().map(|(id, scenario)| (*id, scenario))
+    }
+
+    pub fn len(&self) -> usize {
+        self.scenarios.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scenarios.is_empty()
+    }
+
+    /// Applies every scenario active in a region, in the order they were
+    /// added
+    ///
+    /// Returns whether any changed the weather.
+    pub fn apply(&self, region: ChunkCoord, latitude: f32, clock: &SimClock, elapsed: f64, weather: &mut WeatherSystem) -> bool {
+        let mut applied = false;
+        for (_, scenario) in &self.scenarios {
+            let strength = scenario.strength(region, latitude, clock, elapsed);
+            if strength <= 0.0 {
+                continue;
+            }
+            for (field, adjustment) in &scenario.adjust {
+                let value = field.get_mut(weather);
+                *value = field.clamp(adjustment.apply(*value, strength));
+            }
+            applied = true;
+        }
+        applied
+    }
+}
+
+/// System removing one-off scenarios whose window has passed, so the
+/// grid stops laying them over its cells
+pub fn remove_finished_scenarios(clock: Res<SimClock>, mut scenarios: ResMut<WeatherScenarios>) {
+    let today = clock.days_at(clock.elapsed());
+    let finished: Vec<ScenarioId> = scenarios
+        .iter()
+        .filter(|(_, scenario)| scenario.has_ended(today))
+        .map(|(id, _)| id)
+        .collect();
+    for id in finished {
+        if let Some(scenario) = scenarios.remove(id) {
+            info!("Weather scenario '{}' is over", scenario.name);
+        }
+    }
+}
+
+/// Loads weather scenarios from the scenarios file, falling back to the built-in set
+pub fn load_weather_scenarios(mut scenarios: ResMut<WeatherScenarios>) {
+    let loaded = WeatherScenarios::load(WEATHER_SCENARIOS_PATH).or_else(|err| {
+        warn!("Using built-in weather scenarios: {}", err);
+        WeatherScenarios::from_ron_str(DEFAULT_WEATHER_SCENARIOS)
+    });
+
+    match loaded {
+        Ok(loaded) => {
+            info!("Loaded {} weather scenarios", loaded.len());
+            *scenarios = loaded;
+        }
+        Err(err) => {
+            error!("No usable weather scenarios: {}", err);
+            *scenarios = WeatherScenarios::default();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+    use crate::engine::time::DAY_LENGTH;
+    use crate::engine::weather_grid::WeatherGrid;
+
+    #[test]
+    fn test_scenarios_parse_and_ramp() {
+        assert!(WeatherScenarios::from_ron_str(DEFAULT_WEATHER_SCENARIOS).unwrap().is_empty());
+
+        let source = r#"[
+            (
+                name: "drought",
+                area: Area(min: (-1, -1), max: (1, 1)),
+                window: Once(start_day: 100.0, days: 30.0),
+                ramp_days: 2.0,
+                adjust: [(Precipitation, Set(0.0)), (Humidity, Scale(0.6))],
+            ),
+            (
+                name: "cold snap",
+                window: Season(Winter),
+                adjust: [(Temperature, Add(-8.0))],
+            ),
+        ]"#;
+        let scenarios = WeatherScenarios::from_ron_str(source).unwrap();
+        let clock = SimClock::default();
+        let day = clock.day_length as f64;
+        let (drought_id, drought) = scenarios.iter().next().unwrap();
+        let origin = ChunkCoord::new(0, 0);
+
+        assert_eq!(drought.strength(origin, 45.0, &clock, 99.0 * day), 0.0);
+        assert_eq!(drought.strength(origin, 45.0, &clock, 101.0 * day), 0.5);
+        assert_eq!(drought.strength(origin, 45.0, &clock, 110.0 * day), 1.0);
+        assert_eq!(drought.strength(ChunkCoord::new(2, 0), 45.0, &clock, 110.0 * day), 0.0);
+        assert_eq!(drought.strength(origin, 45.0, &clock, 130.0 * day), 0.0);
+
+        // Day 110 is in the winter of the first year
+        let mut weather = WeatherSystem {
+            precipitation: 3.0,
+            ..Default::default()
+        };
+        assert!(scenarios.apply(origin, 45.0, &clock, 110.0 * day, &mut weather));
+        assert_eq!(weather.precipitation, 0.0);
+        assert!((weather.humidity - 0.3).abs() < 1e-6);
+        assert_eq!(weather.temperature, 12.0);
+
+        let mut runtime = scenarios.clone();
+        assert!(runtime.remove(drought_id).is_some());
+        assert!(runtime.remove(drought_id).is_none());
+        assert_eq!(runtime.len(), 1);
+    }
+
+    #[test]
+    fn test_finished_scenarios_are_removed() {
+        let mut scenarios = WeatherScenarios::default();
+        scenarios.add(WeatherScenario::new("storm", ScenarioWindow::Once { start_day: 0.0, days: 1.0 }));
+        scenarios.add(WeatherScenario::new("cold snap", ScenarioWindow::Season(Season::Winter)));
+        let mut world = World::new();
+        world.insert_resource(scenarios);
+        let mut clock = SimClock::default();
+        clock.advance(clock.day_length as f64 * 0.5);
+        world.insert_resource(clock);
+
+        world.run_system_once(remove_finished_scenarios).unwrap();
+        assert_eq!(world.resource::<WeatherScenarios>().len(), 2);
+
+        world.resource_mut::<SimClock>().advance(DAY_LENGTH as f64);
+        world.run_system_once(remove_finished_scenarios).unwrap();
+        let names: Vec<&str> = world.resource::<WeatherScenarios>().iter().map(|(_, scenario)| scenario.name.as_str()).collect();
+        assert_eq!(names, ["cold snap"]);
+    }
+
+    #[test]
+    fn test_scenarios_layer_over_the_natural_model() {
+        let mut natural = WeatherGrid::default();
+        let mut scripted = natural.clone();
+        let mut scenarios = WeatherScenarios::default();
+        scenarios.add(
+            WeatherScenario::new("heat", ScenarioWindow::Once { start_day: 0.5, days: 1.0 })
+                .adjust(WeatherField::Temperature, Adjustment::Add(10.0))
+                .adjust(WeatherField::Precipitation, Adjustment::Set(0.0)),
+        );
+        scripted.set_scenarios(&scenarios);
+
+        let origin = ChunkCoord::new(0, 0);
+        natural.run_for(1440.0);
+        scripted.run_for(1440.0);
+        let expected = natural.cell(origin).unwrap().temperature + 10.0;
+        assert!((scripted.cell(origin).unwrap().temperature - expected).abs() < 1e-4);
+        assert_eq!(scripted.cell(origin).unwrap().precipitation, 0.0);
+        assert_eq!(scripted.natural(origin), natural.cell(origin));
+
+        // Once the window closes the natural weather shows through again
+        natural.run_for(1440.0);
+        scripted.run_for(1440.0);
+        assert_eq!(scripted.cell(origin), natural.cell(origin));
+    }
+}
@@ -2,24 +2,39 @@ mod engine;
 mod agents;
 mod world;
 
+use bevy::app::ScheduleRunnerPlugin;
+use bevy::log::LogPlugin;
 use bevy::prelude::*;
 use world::chunk::{WorldSeed, LoadedChunks, chunk_loading_system, setup_world, ChunkLoaded, ChunkUnloaded};
 use world::terrain::{TerrainGenerator, terrain_generation_system};
 use engine::tick::{agent_tick_system, AgentTickCompleted, clear_agent_tick_events};
-use agents::agent::spawn_agents;
+use engine::tick_rates::{load_tick_rates, wake_agents, TickRates, WakeAgent};
+use agents::agent::{spawn_agents, update_agents};
 use std::collections::HashMap;
+use std::time::Duration;
 use bevy::diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin};
 use bevy::window::WindowMode;
 use bevy::window::WindowResolution;
-use engine::{update_time_system, WeatherPlugin};
+use engine::WeatherPlugin;
+use engine::time::SimClock;
+use engine::control::{keyboard_sim_controls, show_sim_status, SimControlPlugin, SimulationControl, WINDOW_TITLE};
+use engine::scheduler::SchedulerPlugin;
+use engine::pipeline::{Act, AdvanceTime, Decide, Perception, PreTick, TickPipelinePlugin};
+use engine::weather_history::remember_recent_weather;
+use engine::weather_grid::sync_weather_grid;
 use crate::engine::memory::MemoryProfilingPlugin;
+use world::deposits::gather_job_system;
+use world::resources::ResourcePlugin;
+use world::conservation::ConservationPlugin;
+use world::hydrology::HydrologyPlugin;
 
 /// System sets for organizing simulation systems
 /// 
 /// This enum defines the logical groups of systems in our simulation:
-/// - WorldGeneration: Handles terrain and chunk generation
-/// - AgentProcessing: Manages agent behavior and interactions
-/// - Debug: Provides debugging information and visualization
+/// - WorldGeneration: Handles terrain and chunk generation, in `PreTick`
+/// - AgentProcessing: Manages agent behavior and interactions, in
+///   `Perception` and `Decide`
+/// - Debug: Provides debugging information and visualization, in `Update`
 #[derive(Debug, Hash, PartialEq, Eq, Clone, SystemSet)]
 pub enum SimulationSet {
     WorldGeneration,
@@ -38,6 +53,8 @@ pub struct SimulationConfig {
     pub simulation_speed: f64,
     /// Number of agents to spawn
     pub agent_count: u32,
+    /// Run without a window, as fast as possible
+    pub headless: bool,
 }
 
 impl Default for SimulationConfig {
@@ -47,6 +64,7 @@ impl Default for SimulationConfig {
             chunk_load_radius: 5,
             simulation_speed: 60.0,
             agent_count: 100,
+            headless: std::env::args().any(|arg| arg == "--headless"),
         }
     }
 }
@@ -64,24 +82,41 @@ fn memory_management_system(
 fn main() {
     // Create a single instance of the config to reuse
     let config = SimulationConfig::default();
-    
-    App::new()
-        .add_plugins(DefaultPlugins.set(WindowPlugin {
+    let mut app = App::new();
+
+    if config.headless {
+        // No window to wait for: run frames back to back and ticks at max speed
+        app
+            .add_plugins(MinimalPlugins.set(ScheduleRunnerPlugin::run_loop(Duration::ZERO)))
+            .add_plugins(LogPlugin::default())
+            .insert_resource(SimulationControl::max_speed());
+    } else {
+        app.add_plugins(DefaultPlugins.set(WindowPlugin {
             primary_window: Some(Window {
-                title: "Neo Simulation".to_string(),
+                title: WINDOW_TITLE.to_string(),
                 resolution: WindowResolution::new(1280.0, 720.0),
                 mode: WindowMode::Windowed,
                 ..default()
             }),
             ..default()
         }))
+        .add_systems(Update, (keyboard_sim_controls, show_sim_status).chain());
+    }
+
+    app
         .add_plugins(FrameTimeDiagnosticsPlugin::default())
         .add_plugins(LogDiagnosticsPlugin::default())
+        .add_plugins(TickPipelinePlugin)
+        .add_plugins(SimControlPlugin)
+        .add_plugins(SchedulerPlugin)
         .add_plugins(WeatherPlugin)
         .add_plugins(MemoryProfilingPlugin)
+        .add_plugins(ResourcePlugin)
+        .add_plugins(HydrologyPlugin)
         .add_event::<ChunkLoaded>()
         .add_event::<ChunkUnloaded>()
         .add_event::<AgentTickCompleted>()
+        .add_event::<WakeAgent>()
         .insert_resource(WorldSeed(config.world_seed))
         .insert_resource(TerrainGenerator::default())
         .insert_resource(LoadedChunks {
@@ -91,16 +126,18 @@ fn main() {
         })
         .insert_resource(Time::<Fixed>::from_hz(config.simulation_speed))
         .insert_resource(Time::<Virtual>::default())
+        .insert_resource(SimClock::with_tick_length(1.0 / config.simulation_speed))
         .insert_resource(config)
+        .init_resource::<TickRates>()
+        .add_systems(PreStartup, load_tick_rates)
         .add_systems(Startup, (setup_world, spawn_agents))
-        .add_systems(Update, (
+        .add_systems(PreTick, (
             chunk_loading_system,
             terrain_generation_system,
-        ).in_set(SimulationSet::WorldGeneration))
-        .add_systems(Update, (
-            agent_tick_system,
-            update_time_system,
-        ).in_set(SimulationSet::AgentProcessing))
+        ).chain().in_set(SimulationSet::WorldGeneration))
+        .add_systems(Perception, wake_agents.after(remember_recent_weather).in_set(SimulationSet::AgentProcessing))
+        .add_systems(Decide, agent_tick_system.in_set(SimulationSet::AgentProcessing))
+        .add_systems(Act, update_agents.before(gather_job_system).in_set(SimulationSet::AgentProcessing))
         .add_systems(Update, (
             world::chunk::debug_chunk_system,
         ).in_set(SimulationSet::Debug))
@@ -108,10 +145,11 @@ fn main() {
             memory_management_system,
             clear_agent_tick_events,
         ).after(SimulationSet::Debug))
-        .configure_sets(Update, (
-            SimulationSet::WorldGeneration,
-            SimulationSet::AgentProcessing,
-            SimulationSet::Debug,
-        ).chain())
-        .run();
+        .configure_sets(PreTick, SimulationSet::WorldGeneration.after(AdvanceTime).before(sync_weather_grid));
+
+    // Catch resources being minted or lost outside the ledger in debug builds
+    #[cfg(debug_assertions)]
+    app.add_plugins(ConservationPlugin);
+
+    app.run();
 }
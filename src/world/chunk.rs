@@ -1,5 +1,7 @@
 use bevy::prelude::*;
+use serde::Deserialize;
 use std::collections::HashMap;
+use crate::world::hydrology::WaterState;
 
 /// Resource representing the world seed
 #[derive(Resource, Debug, Clone, Copy)]
@@ -35,7 +37,7 @@ pub struct Chunk {
 }
 
 /// Coordinates for a chunk
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct ChunkCoord {
     pub x: i32,
     pub y: i32,
@@ -53,6 +55,10 @@ pub struct Tile {
     pub coord: TileCoord,
     pub biome: Biome,
     pub height: f32,
+    pub stratum: Stratum,
+    /// Whether the tile is flooded or in drought, kept up to date by the
+    /// hydrology
+    pub water: WaterState,
 }
 
 /// Coordinates for a tile within a chunk
@@ -69,13 +75,28 @@ impl TileCoord {
 }
 
 /// Represents a biome in the world
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
 pub enum Biome {
     Plains,
     Forest,
     Desert,
     Mountains,
     Ocean,
+    Lake,
+    /// A channel carrying water downhill
+    River,
+}
+
+/// Represents the dominant underground layer beneath a tile
+///
+/// Strata determine which mineral deposits a tile can carry,
+/// independent of its surface biome.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum Stratum {
+    Soil,
+    Clay,
+    Bedrock,
+    OreVein,
 }
 
 /// System for loading and unloading chunks
@@ -0,0 +1,243 @@
+use bevy::prelude::*;
+use std::collections::BTreeMap;
+use crate::engine::time::SimClock;
+use crate::engine::pipeline::{Act, Resolve};
+use crate::world::deposits::{gather_job_system, regrow_deposits};
+use crate::world::ledger::{Holder, ResourceLedger};
+use crate::world::market::market_clearing_system;
+use crate::world::ownership::stockpile_job_system;
+use crate::world::storage::apply_storage_tiers;
+use crate::world::recipes::production_job_system;
+use crate::world::resource_flow::{haul_job_system, solve_resource_flows};
+use crate::world::quantity::Quantity;
+use crate::world::resources::{update_resources, ResourceId, ResourceManager, ResourceSystem};
+
+/// Maximum number of violations kept for inspection
+const MAX_VIOLATIONS: usize = 1000;
+
+/// Label of the checkpoint covering everything between two ticks
+const OUTSIDE_PIPELINE: &str = "outside the resource pipeline";
+
+/// An unexplained change in the total of a resource
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConservationViolation {
+    pub tick: u64,
+    /// System that ran since the previous checkpoint
+    pub system: &'static str,
+    pub resource: ResourceId,
+    /// Net change declared through the ledger's environment sources and sinks
+    pub expected: Quantity,
+    /// Net change actually observed across all holders
+    pub actual: Quantity,
+}
+
+impl ConservationViolation {
+    /// Amount created (positive) or destroyed (negative) without a ledger entry
+    pub fn unexplained(&self) -> Quantity {
+        self.actual - self.expected
+    }
+}
+
+/// Resource verifying that resources are only created or destroyed through
+/// declared sources and sinks
+///
+/// At each checkpoint the total of every resource across all holders is
+/// compared with the previous checkpoint. The difference must match the
+/// ledger entries moving resources in from or out to the environment
+/// (gathering, regeneration, decay, recipes, flow losses); anything else
+/// is reported against the system that ran in between. Quantities are
+/// exact, so any difference at all is a violation.
+#[derive(Debug, Default, Resource)]
+pub struct ConservationChecker {
+    /// Panic on the first violation instead of logging it
+    pub panic_on_violation: bool,
+    totals: Option<BTreeMap<ResourceId, Quantity>>,
+    ledger_cursor: usize,
+    violations: Vec<ConservationViolation>,
+}
+
+impl ConservationChecker {
+    /// Returns the violations found so far, oldest first
+    #[cfg(test)]
+    pub fn violations(&self) -> &[ConservationViolation] {
+        &self.violations
+    }
+
+    /// Removes and returns the violations found so far
+    #[cfg(test)]
+    pub fn take_violations(&mut self) -> Vec<ConservationViolation> {
+        std::mem::take(&mut self.violations)
+    }
+
+    /// Compares the current totals with the previous checkpoint
+    ///
+    /// The first call only records the totals.
+    pub fn check(
+        &mut self,
+        tick: u64,
+        system: &'static str,
+        totals: BTreeMap<ResourceId, Quantity>,
+        ledger: &ResourceLedger,
+    ) -> Vec<ConservationViolation> {
+        let new_entries = ledger.entries_since(self.ledger_cursor);
+        self.ledger_cursor = ledger.end();
+
+        let Some(previous) = self.totals.replace(totals) else {
+            return Vec::new();
+        };
+        let current = self.totals.as_ref().unwrap();
+
+        let mut expected: BTreeMap<ResourceId, Quantity> = BTreeMap::new();
+        for entry in new_entries {
+            let from_environment = matches!(entry.from, Holder::Environment(_));
+            let to_environment = matches!(entry.to, Holder::Environment(_));
+            if from_environment && !to_environment {
+                *expected.entry(entry.resource).or_default() += entry.amount;
+            } else if to_environment && !from_environment {
+                *expected.entry(entry.resource).or_default() -= entry.amount;
+            }
+        }
+
+        let mut resources: Vec<ResourceId> = previous.keys().chain(current.keys()).copied().collect();
+        resources.sort();
+        resources.dedup();
+
+        let mut found = Vec::new();
+        for resource in resources {
+            let actual = current.get(&resource).copied().unwrap_or_default()
+                - previous.get(&resource).copied().unwrap_or_default();
+            let violation = ConservationViolation {
+                tick,
+                system,
+                resource,
+                expected: expected.get(&resource).copied().unwrap_or_default(),
+                actual,
+            };
+            if !violation.unexplained().is_zero() {
+                found.push(violation);
+            }
+        }
+
+        for violation in &found {
+            if self.panic_on_violation {
+                panic!("Resource conservation violated: {:?}", violation);
+            }
+            warn!(
+                "{} changed {:?} by {} without a ledger entry (tick {})",
+                violation.system, violation.resource, violation.unexplained(), violation.tick
+            );
+        }
+        self.violations.extend(found.iter().cloned());
+        if self.violations.len() > MAX_VIOLATIONS {
+            let excess = self.violations.len() - MAX_VIOLATIONS;
+            self.violations.drain(..excess);
+        }
+        found
+    }
+}
+
+/// Sums every resource across all agent stores and the global pool
+pub fn resource_totals(
+    systems: &Query<&ResourceSystem>,
+    global: Option<&ResourceManager>,
+) -> BTreeMap<ResourceId, Quantity> {
+    let mut totals = BTreeMap::new();
+    let stores = systems
+        .iter()
+        .map(|system| &system.store)
+        .chain(global.map(|manager| &manager.store));
+    for store in stores {
+        for (resource, amount) in store.totals() {
+            *totals.entry(resource).or_default() += amount;
+        }
+    }
+    totals
+}
+
+/// Creates a checkpoint system attributing changes since the previous
+/// checkpoint to `system`
+#[allow(clippy::type_complexity)]
+pub fn conservation_checkpoint(
+    system: &'static str,
+) -> impl FnMut(Res<SimClock>, Res<ResourceLedger>, ResMut<ConservationChecker>, Query<&ResourceSystem>, Option<Res<ResourceManager>>) {
+    move |clock, ledger, mut checker, systems, global| {
+        let totals = resource_totals(&systems, global.as_deref());
+        checker.check(clock.tick(), system, totals, &ledger);
+    }
+}
+
+/// Opt-in plugin checking resource conservation after every resource system
+///
+/// Intended for tests and debug builds; summing all holders after each
+/// system is too slow to leave on in release runs.
+pub struct ConservationPlugin;
+
+impl Plugin for ConservationPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<ConservationChecker>()
+            .add_systems(Act, (
+                conservation_checkpoint(OUTSIDE_PIPELINE).before(update_resources),
+                conservation_checkpoint("update_resources").after(update_resources).before(apply_storage_tiers),
+                conservation_checkpoint("apply_storage_tiers").after(apply_storage_tiers).before(gather_job_system),
+                conservation_checkpoint("gather_job_system").after(gather_job_system).before(production_job_system),
+                conservation_checkpoint("production_job_system").after(production_job_system).before(stockpile_job_system),
+                conservation_checkpoint("stockpile_job_system").after(stockpile_job_system).before(haul_job_system),
+                conservation_checkpoint("haul_job_system").after(haul_job_system),
+            ))
+            .add_systems(Resolve, (
+                conservation_checkpoint("regrow_deposits").after(regrow_deposits).before(solve_resource_flows),
+                conservation_checkpoint("solve_resource_flows").after(solve_resource_flows).before(market_clearing_system),
+                conservation_checkpoint("market_clearing_system").after(market_clearing_system),
+            ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::ledger::{LedgerReason, SingleHolder, ResourceTransaction};
+    use crate::world::resources::ResourceRegistry;
+
+    #[test]
+    fn test_unexplained_changes_name_the_system() {
+        let food = ResourceRegistry::default().id("food").unwrap();
+        let mut world = World::new();
+        world.init_resource::<SimClock>();
+        world.init_resource::<ResourceLedger>();
+        world.init_resource::<ConservationChecker>();
+        let mut system = ResourceSystem::new();
+        system.add(food, Quantity::from_units(10), None, None);
+        let agent = world.spawn(system).id();
+
+        let regenerate = move |clock: Res<SimClock>, mut ledger: ResMut<ResourceLedger>, mut query: Query<&mut ResourceSystem>| {
+            let mut system = query.get_mut(agent).unwrap();
+            let mut holders = SingleHolder { entity: agent, store: &mut system.store };
+            let mut transaction = ResourceTransaction::new(LedgerReason::Regeneration);
+            transaction.credit(Holder::Entity(agent), food, Quantity::from_units(5));
+            transaction.commit(clock.tick(), &mut holders, &mut ledger, None).unwrap();
+        };
+        let mint = move |mut query: Query<&mut ResourceSystem>| {
+            let mut system = query.get_mut(agent).unwrap();
+            let amount = system.get(food);
+            system.set(food, amount + Quantity::from_units(3), None, None);
+        };
+
+        let mut schedule = Schedule::default();
+        schedule.add_systems((
+            conservation_checkpoint("start"),
+            regenerate,
+            conservation_checkpoint("regenerate"),
+            mint,
+            conservation_checkpoint("mint"),
+        ).chain());
+        schedule.run(&mut world);
+
+        let violations = world.resource_mut::<ConservationChecker>().take_violations();
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].system, "mint");
+        assert_eq!(violations[0].resource, food);
+        assert_eq!(violations[0].unexplained(), Quantity::from_units(3));
+        assert_eq!(world.get::<ResourceSystem>(agent).unwrap().get(food), Quantity::from_units(18));
+    }
+}
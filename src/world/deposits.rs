@@ -0,0 +1,298 @@
+use bevy::prelude::*;
+use crate::agents::agent::Agent;
+use crate::agents::job::Job;
+use crate::world::chunk::Tile;
+use crate::world::hydrology::{WaterBudget, WaterState};
+use crate::world::quantity::{Quantity, Remainder};
+use crate::world::resources::{ResourceChanged, ResourceId, ResourceRegistry, ResourceSystem};
+use crate::world::ledger::{Holder, LedgerReason, ResourceLedger, ResourceTransaction, SingleHolder};
+use crate::engine::time::SimClock;
+
+/// Units of a resource an agent can gather per second
+const GATHER_RATE: f32 = 5.0;
+
+/// A finite amount of a single resource located on a tile
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ResourceDeposit {
+    pub resource_type: ResourceId,
+    pub amount: Quantity,
+    pub max_amount: Quantity,
+    /// Units restored per second, only applied to renewable resources
+    pub regrowth_rate: f32,
+    /// Regrowth and refill too small to show up in a single tick
+    pub regrowth_remainder: Remainder,
+    /// Damage too small to show up in a single tick
+    pub damage_remainder: Remainder,
+}
+
+impl ResourceDeposit {
+    /// Creates a full deposit
+    pub fn new(resource_type: ResourceId, max_amount: Quantity, regrowth_rate: f32) -> Self {
+        Self {
+            resource_type,
+            amount: max_amount,
+            max_amount,
+            regrowth_rate,
+            regrowth_remainder: Remainder::default(),
+            damage_remainder: Remainder::default(),
+        }
+    }
+
+    /// Removes up to `amount` from the deposit and returns how much was taken
+    pub fn gather(&mut self, amount: Quantity) -> Quantity {
+        let taken = amount.clamp(Quantity::ZERO, self.amount);
+        self.amount -= taken;
+        taken
+    }
+
+    /// Destroys a fraction of what is left in the deposit
+    pub fn damage(&mut self, fraction: f32) {
+        let lost = self.damage_remainder.accrue(self.amount.to_f64() * fraction.min(1.0) as f64);
+        self.amount -= lost.min(self.amount);
+    }
+
+    /// Regrows the deposit towards its maximum
+    pub fn regrow(&mut self, delta: f32) {
+        let regrown = self.regrowth_remainder.accrue(self.regrowth_rate as f64 * delta as f64);
+        self.amount = (self.amount + regrown).min(self.max_amount);
+    }
+}
+
+/// Component holding the resource deposits of a tile
+#[derive(Component, Debug, Clone, Default)]
+pub struct TileDeposits {
+    pub deposits: Vec<ResourceDeposit>,
+}
+
+impl TileDeposits {
+    /// Builds the deposits for a tile from its biome and underground stratum
+    ///
+    /// Each registered resource contributes at most one deposit, taken from
+    /// the first of its deposit rules matching the tile. Regrowth is only
+    /// kept for renewable resources.
+    pub fn for_tile(tile: &Tile, registry: &ResourceRegistry) -> Self {
+        let deposits = registry
+            .iter()
+            .filter_map(|(id, definition)| {
+                let rule = definition
+                    .deposits
+                    .iter()
+                    .find(|rule| rule.matches(tile.biome, tile.stratum))?;
+                let regrowth_rate = if definition.is_renewable { rule.regrowth_rate } else { 0.0 };
+                Some(ResourceDeposit::new(id, Quantity::from_f32(rule.amount), regrowth_rate))
+            })
+            .collect();
+
+        Self { deposits }
+    }
+
+    /// Returns the amount of a resource currently available on the tile
+    pub fn get(&self, resource: ResourceId) -> Quantity {
+        self.deposits
+            .iter()
+            .filter(|deposit| deposit.resource_type == resource)
+            .map(|deposit| deposit.amount)
+            .sum()
+    }
+
+    /// Gathers up to `amount` of a resource and returns how much was taken
+    pub fn gather(&mut self, resource: ResourceId, amount: Quantity) -> Quantity {
+        let mut remaining = amount;
+        for deposit in self.deposits.iter_mut().filter(|d| d.resource_type == resource) {
+            if !remaining.is_positive() {
+                break;
+            }
+            remaining -= deposit.gather(remaining);
+        }
+        amount - remaining
+    }
+
+    /// Refills a resource's deposit by `units`, which may be less than a milliunit
+    pub fn refill(&mut self, resource: ResourceId, units: f64) {
+        if let Some(deposit) = self.deposits.iter_mut().find(|d| d.resource_type == resource) {
+            let refilled = deposit.regrowth_remainder.accrue(units);
+            deposit.amount = (deposit.amount + refilled).min(deposit.max_amount);
+        }
+    }
+
+    /// Returns an amount to the tile, e.g. when it couldn't be stored
+    pub fn restore(&mut self, resource: ResourceId, amount: Quantity) {
+        if let Some(deposit) = self.deposits.iter_mut().find(|d| d.resource_type == resource) {
+            deposit.amount = (deposit.amount + amount).min(deposit.max_amount);
+        }
+    }
+}
+
+/// System that attaches deposits to tiles that don't have any yet
+pub fn attach_tile_deposits(
+    mut commands: Commands,
+    registry: Res<ResourceRegistry>,
+    query: Query<(Entity, &Tile), Without<TileDeposits>>,
+) {
+    for (entity, tile) in query.iter() {
+        commands.entity(entity).insert(TileDeposits::for_tile(tile, &registry));
+    }
+}
+
+/// System that regrows renewable deposits over time
+pub fn regrow_deposits(
+    clock: Res<SimClock>,
+    mut query: Query<&mut TileDeposits>,
+) {
+    let delta = clock.tick_length as f32;
+
+    for mut tile_deposits in query.iter_mut() {
+        for deposit in tile_deposits.deposits.iter_mut() {
+            deposit.regrow(delta);
+        }
+    }
+}
+
+/// System that executes `Job::Gather` for agents
+///
+/// Agents gather from the nearest tile within their perception range that
+/// still holds the requested resource and isn't flooded. The job ends once the agent's
+/// inventory is full or nothing is left in range. Every gather is recorded
+/// in the ledger as coming from the tile.
+pub fn gather_job_system(
+    clock: Res<SimClock>,
+    registry: Res<ResourceRegistry>,
+    mut ledger: ResMut<ResourceLedger>,
+    mut agents: Query<(Entity, &mut Agent, &mut ResourceSystem)>,
+    mut tiles: Query<(Entity, &Transform, &mut TileDeposits, Option<&WaterBudget>)>,
+    mut events: EventWriter<ResourceChanged>,
+) {
+    let delta = clock.tick_length as f32;
+
+    for (agent_entity, mut agent, mut inventory) in agents.iter_mut() {
+        let Some(Job::Gather { resource_type }) = agent.current_job.clone() else {
+            continue;
+        };
+
+        let Some(resource) = registry.id(&resource_type) else {
+            warn!("Agent {} cannot gather unknown resource '{}'", agent.name, resource_type);
+            agent.current_job = Some(Job::Idle);
+            continue;
+        };
+
+        let space = inventory.get_available_space(resource);
+        if !space.is_positive() {
+            debug!("Agent {} inventory full of {}", agent.name, resource_type);
+            agent.current_job = Some(Job::Idle);
+            continue;
+        }
+
+        // Find the nearest tile in range that still holds the resource
+        let nearest = tiles
+            .iter()
+            .filter(|(_, _, deposits, water)| {
+                deposits.get(resource).is_positive() && water.is_none_or(|water| water.state != WaterState::Flooded)
+            })
+            .map(|(entity, transform, _, _)| {
+                (entity, transform.translation.truncate().distance(agent.position))
+            })
+            .filter(|(_, distance)| *distance <= agent.effective_perception_range())
+            .min_by(|a, b| a.1.total_cmp(&b.1));
+
+        let Some((tile_entity, _)) = nearest else {
+            debug!("Agent {} found no {} in range", agent.name, resource_type);
+            agent.current_job = Some(Job::Idle);
+            continue;
+        };
+
+        if let Ok((_, _, mut deposits, _)) = tiles.get_mut(tile_entity) {
+            let gathered = deposits.gather(resource, Quantity::from_f32(GATHER_RATE * delta).min(space));
+            let mut transaction = ResourceTransaction::new(LedgerReason::Gather).with_source(agent_entity);
+            transaction.transfer(Holder::Environment(Some(tile_entity)), Holder::Entity(agent_entity), resource, gathered);
+
+            let mut holders = SingleHolder { entity: agent_entity, store: &mut inventory.store };
+            match transaction.commit(clock.tick(), &mut holders, &mut ledger, Some(&mut events)) {
+                Ok(()) => debug!("Agent {:?} gathered {} {}", agent_entity, gathered, resource_type),
+                Err(err) => {
+                    // Put back what couldn't be stored
+                    warn!("Agent {} failed to store gathered {}: {:?}", agent.name, resource_type, err);
+                    deposits.restore(resource, gathered);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::chunk::{Biome, Stratum, TileCoord};
+    use crate::world::hydrology::WaterState;
+
+    fn tile(biome: Biome, stratum: Stratum) -> Tile {
+        Tile {
+            coord: TileCoord::new(0, 0),
+            biome,
+            height: 0.0,
+            stratum,
+            water: WaterState::Normal,
+        }
+    }
+
+    #[test]
+    fn test_deposits_follow_biome_and_stratum() {
+        let registry = ResourceRegistry::default();
+        let food = registry.id("food").unwrap();
+        let wood = registry.id("wood").unwrap();
+        let water = registry.id("water").unwrap();
+        let metal = registry.id("metal").unwrap();
+
+        let forest = TileDeposits::for_tile(&tile(Biome::Forest, Stratum::Soil), &registry);
+        assert!(forest.get(food).is_positive());
+        assert!(forest.get(wood).is_positive());
+        assert_eq!(forest.get(metal), Quantity::ZERO);
+
+        let lake = TileDeposits::for_tile(&tile(Biome::Lake, Stratum::OreVein), &registry);
+        assert!(lake.get(water).is_positive());
+        assert_eq!(lake.get(metal), Quantity::ZERO);
+
+        let mountain = TileDeposits::for_tile(&tile(Biome::Mountains, Stratum::OreVein), &registry);
+        let plains = TileDeposits::for_tile(&tile(Biome::Plains, Stratum::OreVein), &registry);
+        assert!(mountain.get(metal) > plains.get(metal));
+        assert!(plains.get(metal).is_positive());
+    }
+
+    #[test]
+    fn test_deposit_depletion_and_regrowth() {
+        let registry = ResourceRegistry::default();
+        let wood = registry.id("wood").unwrap();
+        let metal = registry.id("metal").unwrap();
+        let mut deposits = TileDeposits::for_tile(&tile(Biome::Forest, Stratum::OreVein), &registry);
+
+        let wood_amount = deposits.get(wood);
+        assert_eq!(deposits.gather(wood, wood_amount + Quantity::from_units(10)), wood_amount);
+        assert_eq!(deposits.get(wood), Quantity::ZERO);
+
+        let metal_amount = deposits.get(metal);
+        let hundred = Quantity::from_units(100);
+        assert_eq!(deposits.gather(metal, hundred), hundred);
+
+        for deposit in deposits.deposits.iter_mut() {
+            deposit.regrow(10.0);
+        }
+
+        // Wood is renewable, metal is not
+        assert_eq!(deposits.get(wood), Quantity::from_units(10));
+        assert_eq!(deposits.get(metal), metal_amount - hundred);
+    }
+
+    #[test]
+    fn test_slow_regrowth_adds_up_over_short_ticks() {
+        let registry = ResourceRegistry::default();
+        let wood = registry.id("wood").unwrap();
+        let mut deposit = ResourceDeposit::new(wood, Quantity::from_units(10), 0.5);
+        deposit.gather(Quantity::from_units(5));
+
+        // A tick's worth is 8.33 milliunits, which doesn't round to a whole one
+        let delta = SimClock::default().tick_length as f32;
+        for _ in 0..120 {
+            deposit.regrow(delta);
+        }
+        assert_eq!(deposit.amount, Quantity::from_units(6));
+    }
+}
@@ -0,0 +1,493 @@
+use bevy::prelude::*;
+use std::collections::HashMap;
+use crate::engine::pipeline::{AdvanceTime, PreTick, Resolve};
+use crate::engine::weather_grid::WeatherGrid;
+use crate::world::chunk::{Biome, Tile};
+use crate::world::deposits::{regrow_deposits, TileDeposits};
+use crate::world::resources::ResourceRegistry;
+
+/// Configuration of the surface water and soil moisture budget
+#[derive(Debug, Clone, PartialEq)]
+pub struct HydrologyConfig {
+    /// Distance between neighbouring tiles, in world units
+    pub tile_size: f32,
+    /// Simulation seconds between updates
+    pub interval: f64,
+    /// Water the soil can hold, in mm
+    pub field_capacity: f32,
+    /// Surface water soaking into the soil per hour, in mm
+    pub infiltration_rate: f32,
+    /// Evaporation per hour in mm at 0°C, no wind and dry air
+    pub evaporation_rate: f32,
+    /// Surface water a tile holds back from runoff, in mm
+    pub retention: f32,
+    /// Fraction of the water above `retention` running off per hour
+    pub runoff_rate: f32,
+    /// Water a lake holds above its normal level before it overflows, in mm
+    pub bank_capacity: f32,
+    /// Water a river carries before it overflows, in mm
+    pub channel_capacity: f32,
+    /// Fraction of a river's water carried downstream per hour
+    pub river_flow_rate: f32,
+    /// Standing water at which land floods, in mm
+    pub flood_depth: f32,
+    /// Fraction of field capacity below which soil counts as dry
+    pub drought_moisture: f32,
+    /// Hours soil must stay dry before a drought sets in
+    pub drought_hours: f32,
+    /// Resource whose deposits are damaged by floods and droughts
+    pub crop: String,
+    /// Fraction of crops lost per hour of flood
+    pub flood_damage: f32,
+    /// Fraction of crops lost per hour of drought
+    pub drought_damage: f32,
+}
+
+impl Default for HydrologyConfig {
+    fn default() -> Self {
+        Self {
+            tile_size: 1.0,
+            interval: 60.0,
+            field_capacity: 100.0,
+            infiltration_rate: 5.0,
+            evaporation_rate: 0.2,
+            retention: 5.0,
+            runoff_rate: 0.5,
+            bank_capacity: 200.0,
+            channel_capacity: 100.0,
+            river_flow_rate: 0.8,
+            flood_depth: 50.0,
+            drought_moisture: 0.2,
+            drought_hours: 72.0,
+            crop: "food".to_string(),
+            flood_damage: 0.02,
+            drought_damage: 0.01,
+        }
+    }
+}
+
+/// State of a tile's water budget
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WaterState {
+    #[default]
+    Normal,
+    Flooded,
+    Drought,
+}
+
+/// How a tile's water behaves
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum WaterBody {
+    Land,
+    /// Holds water up to its banks and spills the rest onto the land around it
+    Lake,
+    /// Carries water to its lowest lower neighbour that is water too, and
+    /// spills what it can't carry onto the land beside it
+    River,
+    /// Takes any amount of water
+    Sea,
+}
+
+impl WaterBody {
+    fn of(biome: Biome) -> Self {
+        match biome {
+            Biome::Lake => Self::Lake,
+            Biome::River => Self::River,
+            Biome::Ocean => Self::Sea,
+            _ => Self::Land,
+        }
+    }
+}
+
+/// Component holding the surface water and soil moisture of a tile
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct WaterBudget {
+    /// Standing water in mm; for lakes, water above their normal level
+    pub surface_water: f32,
+    /// Water held in the soil in mm
+    pub soil_moisture: f32,
+    /// Hours the soil has been dry
+    pub dry_hours: f32,
+    pub state: WaterState,
+}
+
+impl WaterBudget {
+    /// Starting budget for a tile
+    pub fn for_tile(tile: &Tile, config: &HydrologyConfig) -> Self {
+        let (surface_water, soil_moisture) = match WaterBody::of(tile.biome) {
+            WaterBody::Land => (0.0, config.field_capacity * 0.5),
+            WaterBody::Lake => (config.bank_capacity * 0.5, config.field_capacity),
+            WaterBody::River => (0.0, config.field_capacity),
+            WaterBody::Sea => (0.0, config.field_capacity),
+        };
+        Self {
+            surface_water,
+            soil_moisture,
+            dry_hours: 0.0,
+            state: WaterState::Normal,
+        }
+    }
+
+    /// Soil moisture as a fraction of field capacity
+    pub fn saturation(&self, config: &HydrologyConfig) -> f32 {
+        self.soil_moisture / config.field_capacity.max(f32::EPSILON)
+    }
+}
+
+/// Event fired when a tile floods, dries out or recovers
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct WaterStateChanged {
+    pub tile: Entity,
+    pub position: Vec2,
+    pub from: WaterState,
+    pub to: WaterState,
+}
+
+/// Resource driving the water budget of the tiles
+///
+/// Rain from the `WeatherGrid` lands on each tile, soaks into its soil up
+/// to field capacity and evaporates with heat, wind and dry air. Water
+/// above what a tile holds back runs off to its lowest lower neighbour;
+/// lakes hold water up to their banks and spill the rest onto the land
+/// around them, rivers carry water downstream and overflow onto their
+/// banks when swollen, and the sea takes everything. Land floods when standing
+/// water gets deep and falls into drought when its soil stays dry.
+#[derive(Debug, Default, Resource)]
+pub struct Hydrology {
+    pub config: HydrologyConfig,
+    last_update: Option<f64>,
+}
+
+impl Hydrology {
+    /// Grid cell of a world position
+    fn cell(&self, position: Vec2) -> IVec2 {
+        (position / self.config.tile_size.max(f32::EPSILON)).round().as_ivec2()
+    }
+
+    /// New state of a tile after an update
+    fn next_state(&self, body: WaterBody, budget: &WaterBudget) -> WaterState {
+        if body != WaterBody::Land {
+            return WaterState::Normal;
+        }
+        let config = &self.config;
+        match budget.state {
+            WaterState::Flooded if budget.surface_water > config.flood_depth * 0.5 => WaterState::Flooded,
+            WaterState::Drought if budget.saturation(config) < config.drought_moisture * 1.5 => WaterState::Drought,
+            _ if budget.surface_water >= config.flood_depth => WaterState::Flooded,
+            _ if budget.dry_hours >= config.drought_hours => WaterState::Drought,
+            _ => WaterState::Normal,
+        }
+    }
+}
+
+/// System that attaches water budgets to tiles that don't have any yet
+pub fn attach_water_budgets(
+    mut commands: Commands,
+    hydrology: Res<Hydrology>,
+    query: Query<(Entity, &Tile), Without<WaterBudget>>,
+) {
+    for (entity, tile) in query.iter() {
+        commands.entity(entity).insert(WaterBudget::for_tile(tile, &hydrology.config));
+    }
+}
+
+/// System updating the water budget of every tile from the weather
+///
+/// Runs every `interval` of weather time. Runoff is computed from the
+/// budgets before the update, so the result doesn't depend on the order
+/// tiles are visited in. The resulting flood or drought state is copied
+/// to the tile.
+#[allow(clippy::type_complexity)]
+pub fn update_hydrology(
+    grid: Res<WeatherGrid>,
+    registry: Res<ResourceRegistry>,
+    mut hydrology: ResMut<Hydrology>,
+    mut tiles: Query<(Entity, &mut Tile, &Transform, &mut WaterBudget, Option<&mut TileDeposits>)>,
+    mut events: EventWriter<WaterStateChanged>,
+) {
+    let now = grid.elapsed();
+    let since = match hydrology.last_update {
+        Some(last) if now - last < hydrology.config.interval => return,
+        Some(last) => now - last,
+        None => {
+            hydrology.last_update = Some(now);
+            return;
+        }
+    };
+    hydrology.last_update = Some(now);
+
+    let hydrology = &*hydrology;
+    let config = &hydrology.config;
+    let hours = (since / grid.clock().hour_length().max(f64::EPSILON)) as f32;
+    let crop = registry.id(&config.crop);
+
+    // Snapshot of every tile, keyed by grid cell
+    let snapshot: HashMap<IVec2, (Entity, WaterBody, f32, f32)> = tiles
+        .iter()
+        .map(|(entity, tile, transform, budget, _)| {
+            let cell = hydrology.cell(transform.translation.truncate());
+            (cell, (entity, WaterBody::of(tile.biome), tile.height, budget.surface_water))
+        })
+        .collect();
+
+    // Runoff and overflow between tiles
+    let mut inflow: HashMap<Entity, f32> = HashMap::new();
+    let mut outflow: HashMap<Entity, f32> = HashMap::new();
+    for (cell, (entity, body, height, surface)) in &snapshot {
+        let neighbors: Vec<&(Entity, WaterBody, f32, f32)> = [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y]
+            .iter()
+            .filter_map(|offset| snapshot.get(&(*cell + *offset)))
+            .collect();
+        match body {
+            WaterBody::Land => {
+                let lowest = neighbors
+                    .iter()
+                    .filter(|neighbor| neighbor.2 < *height)
+                    .min_by(|a, b| a.2.total_cmp(&b.2));
+                if let Some(lowest) = lowest {
+                    let amount = (surface - config.retention).max(0.0) * (config.runoff_rate * hours).min(1.0);
+                    if amount > 0.0 {
+                        *outflow.entry(*entity).or_default() += amount;
+                        *inflow.entry(lowest.0).or_default() += amount;
+                    }
+                }
+            }
+            WaterBody::Lake => {
+                let banks: Vec<_> = neighbors.iter().filter(|neighbor| neighbor.1 == WaterBody::Land).collect();
+                let excess = surface - config.bank_capacity;
+                if excess > 0.0 && !banks.is_empty() {
+                    *outflow.entry(*entity).or_default() += excess;
+                    for bank in &banks {
+                        *inflow.entry(bank.0).or_default() += excess / banks.len() as f32;
+                    }
+                }
+            }
+            WaterBody::River => {
+                let banks: Vec<_> = neighbors.iter().filter(|neighbor| neighbor.1 == WaterBody::Land).collect();
+                let excess = surface - config.channel_capacity;
+                let mut carried = *surface;
+                if excess > 0.0 && !banks.is_empty() {
+                    carried -= excess;
+                    *outflow.entry(*entity).or_default() += excess;
+                    for bank in &banks {
+                        *inflow.entry(bank.0).or_default() += excess / banks.len() as f32;
+                    }
+                }
+                let downstream = neighbors
+                    .iter()
+                    .filter(|neighbor| neighbor.1 != WaterBody::Land && neighbor.2 < *height)
+                    .min_by(|a, b| a.2.total_cmp(&b.2));
+                if let Some(downstream) = downstream {
+                    let amount = carried * (config.river_flow_rate * hours).min(1.0);
+                    if amount > 0.0 {
+                        *outflow.entry(*entity).or_default() += amount;
+                        *inflow.entry(downstream.0).or_default() += amount;
+                    }
+                }
+            }
+            WaterBody::Sea => {}
+        }
+    }
+
+    for (entity, mut tile, transform, mut budget, deposits) in tiles.iter_mut() {
+        let position = transform.translation.truncate();
+        let body = WaterBody::of(tile.biome);
+        let Some(weather) = grid.at_position(position) else {
+            continue;
+        };
+
+        let mut surface = budget.surface_water
+            + weather.precipitation * hours
+            + inflow.get(&entity).copied().unwrap_or(0.0)
+            - outflow.get(&entity).copied().unwrap_or(0.0);
+        let mut soil = budget.soil_moisture;
+
+        // Soaking in, land only; water bodies keep their soil saturated
+        if body == WaterBody::Land {
+            let soaked = surface
+                .min(config.infiltration_rate * hours)
+                .min(config.field_capacity - soil)
+                .max(0.0);
+            surface -= soaked;
+            soil += soaked;
+        }
+
+        // Evaporation from standing water first, then from the soil
+        let potential = config.evaporation_rate
+            * (1.0 + 0.05 * weather.temperature.max(0.0))
+            * (1.0 + 0.1 * weather.wind_speed)
+            * (1.0 - weather.humidity).max(0.0)
+            * hours;
+        let from_surface = potential.min(surface.max(0.0));
+        surface -= from_surface;
+        if body == WaterBody::Land {
+            let from_soil = ((potential - from_surface) * budget.saturation(config)).min(soil);
+            soil -= from_soil;
+        }
+        if body == WaterBody::Sea {
+            surface = 0.0;
+        }
+
+        budget.surface_water = surface.max(0.0);
+        budget.soil_moisture = soil.clamp(0.0, config.field_capacity);
+        budget.dry_hours = if body == WaterBody::Land && budget.saturation(config) < config.drought_moisture {
+            budget.dry_hours + hours
+        } else {
+            0.0
+        };
+
+        let state = hydrology.next_state(body, &budget);
+        if state != budget.state {
+            events.send(WaterStateChanged { tile: entity, position, from: budget.state, to: state });
+            budget.state = state;
+        }
+        if tile.water != state {
+            tile.water = state;
+        }
+
+        // Crops drown in floods and wither in droughts
+        let damage = match budget.state {
+            WaterState::Normal => 0.0,
+            WaterState::Flooded => config.flood_damage,
+            WaterState::Drought => config.drought_damage,
+        };
+        if let (Some(crop), Some(mut deposits)) = (crop, deposits) {
+            if damage > 0.0 {
+                let fraction = (damage * hours).min(1.0);
+                for deposit in deposits.deposits.iter_mut().filter(|deposit| deposit.resource_type == crop) {
+                    deposit.amount -= deposit.amount.mul_f32(fraction);
+                }
+            }
+        }
+    }
+}
+
+/// Plugin for the water budget of the tiles
+pub struct HydrologyPlugin;
+
+impl Plugin for HydrologyPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<Hydrology>()
+            .add_event::<WaterStateChanged>()
+            .add_systems(PreTick, attach_water_budgets.after(AdvanceTime))
+            .add_systems(Resolve, update_hydrology.after(regrow_deposits));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+    use crate::engine::weather_scenario::{Adjustment, WeatherField, WeatherScenario, WeatherScenarios, ScenarioWindow};
+    use crate::world::chunk::{Stratum, TileCoord};
+    use crate::world::quantity::Quantity;
+
+    fn spawn_tile(world: &mut World, x: f32, biome: Biome, height: f32) -> Entity {
+        let registry = world.resource::<ResourceRegistry>().clone();
+        let tile = Tile {
+            coord: TileCoord::new(x as i32, 0),
+            biome,
+            height,
+            stratum: Stratum::Soil,
+            water: WaterState::Normal,
+        };
+        let deposits = TileDeposits::for_tile(&tile, &registry);
+        world.spawn((tile, Transform::from_xyz(x, 0.0, 0.0), deposits)).id()
+    }
+
+    fn weather_world(scenario: WeatherScenario) -> World {
+        let mut world = World::new();
+        let mut scenarios = WeatherScenarios::default();
+        scenarios.add(scenario);
+        let mut grid = WeatherGrid::default();
+        grid.set_scenarios(&scenarios);
+        world.insert_resource(grid);
+        world.init_resource::<ResourceRegistry>();
+        world.init_resource::<Hydrology>();
+        world.init_resource::<Events<WaterStateChanged>>();
+        world
+    }
+
+    /// Runs the weather and hydrology for a number of hours
+    fn run_hours(world: &mut World, hours: u32) {
+        for _ in 0..hours {
+            world.resource_mut::<WeatherGrid>().run_for(60.0);
+            world.run_system_once(attach_water_budgets).unwrap();
+            world.run_system_once(update_hydrology).unwrap();
+        }
+    }
+
+    fn changes(world: &World) -> Vec<(Entity, WaterState)> {
+        let events = world.resource::<Events<WaterStateChanged>>();
+        events.get_cursor().read(events).map(|event| (event.tile, event.to)).collect()
+    }
+
+    #[test]
+    fn test_overflowing_lake_floods_its_banks() {
+        let mut world = weather_world(
+            WeatherScenario::new("storm", ScenarioWindow::Once { start_day: 0.0, days: 1.0 })
+                .adjust(WeatherField::Precipitation, Adjustment::Set(30.0)),
+        );
+        let food = world.resource::<ResourceRegistry>().id("food").unwrap();
+        let hill = spawn_tile(&mut world, 0.0, Biome::Plains, 5.0);
+        let bank = spawn_tile(&mut world, 1.0, Biome::Plains, 1.0);
+        let lake = spawn_tile(&mut world, 2.0, Biome::Lake, 0.0);
+        let harvest = world.get::<TileDeposits>(bank).unwrap().get(food);
+
+        run_hours(&mut world, 12);
+
+        assert!(changes(&world).contains(&(bank, WaterState::Flooded)));
+        assert_eq!(world.get::<WaterBudget>(lake).unwrap().state, WaterState::Normal);
+        assert!((world.get::<WaterBudget>(bank).unwrap().soil_moisture - 100.0).abs() < 1e-3);
+        // The hill sheds its water downhill
+        assert!(world.get::<WaterBudget>(hill).unwrap().surface_water < world.get::<WaterBudget>(bank).unwrap().surface_water);
+        assert!(world.get::<TileDeposits>(bank).unwrap().get(food) < harvest);
+    }
+
+    #[test]
+    fn test_dry_heat_brings_drought() {
+        let mut world = weather_world(
+            WeatherScenario::new("heatwave", ScenarioWindow::Once { start_day: 0.0, days: 30.0 })
+                .adjust(WeatherField::Precipitation, Adjustment::Set(0.0))
+                .adjust(WeatherField::Temperature, Adjustment::Set(35.0))
+                .adjust(WeatherField::Humidity, Adjustment::Set(0.1)),
+        );
+        let field = spawn_tile(&mut world, 0.0, Biome::Plains, 0.0);
+        let lake = spawn_tile(&mut world, 1.0, Biome::Lake, 0.0);
+
+        run_hours(&mut world, 24);
+        assert_eq!(world.get::<WaterBudget>(field).unwrap().state, WaterState::Normal);
+        run_hours(&mut world, 24 * 14);
+
+        assert_eq!(world.get::<WaterBudget>(field).unwrap().state, WaterState::Drought);
+        assert_eq!(world.get::<Tile>(field).unwrap().water, WaterState::Drought);
+        assert_eq!(changes(&world), vec![(field, WaterState::Drought)]);
+        assert_eq!(world.get::<WaterBudget>(lake).unwrap().state, WaterState::Normal);
+        assert!(world.get::<TileDeposits>(field).unwrap().get(world.resource::<ResourceRegistry>().id("food").unwrap()) < Quantity::from_units(150));
+    }
+
+    #[test]
+    fn test_swollen_river_floods_its_banks() {
+        let mut world = weather_world(
+            WeatherScenario::new("dry", ScenarioWindow::Once { start_day: 0.0, days: 1.0 })
+                .adjust(WeatherField::Precipitation, Adjustment::Set(0.0)),
+        );
+        let bank = spawn_tile(&mut world, 0.0, Biome::Plains, 1.0);
+        let river = spawn_tile(&mut world, 1.0, Biome::River, 0.5);
+        let lake = spawn_tile(&mut world, 2.0, Biome::Lake, 0.0);
+        run_hours(&mut world, 1);
+        let lake_level = world.get::<WaterBudget>(lake).unwrap().surface_water;
+
+        // A flood wave arrives from upstream
+        world.get_mut::<WaterBudget>(river).unwrap().surface_water = 400.0;
+        run_hours(&mut world, 1);
+
+        assert_eq!(changes(&world), vec![(bank, WaterState::Flooded)]);
+        assert_eq!(world.get::<Tile>(bank).unwrap().water, WaterState::Flooded);
+        assert_eq!(world.get::<Tile>(river).unwrap().water, WaterState::Normal);
+        // What stays in the channel flows on downstream
+        assert!(world.get::<WaterBudget>(river).unwrap().surface_water < 100.0);
+        assert!(world.get::<WaterBudget>(lake).unwrap().surface_water > lake_level + 50.0);
+    }
+}
@@ -0,0 +1,145 @@
+use bevy::prelude::*;
+use crate::world::quantity::{Quantity, Remainder};
+use crate::world::resources::Spoilage;
+
+/// Largest quality difference between two stacks that may be merged
+const QUALITY_MERGE_TOLERANCE: f32 = 0.05;
+
+/// Largest age difference in seconds between two stacks that may be merged
+const AGE_MERGE_TOLERANCE: f32 = 10.0;
+
+/// A quantity of one resource sharing quality, age and provenance
+#[derive(Debug, Clone, PartialEq)]
+pub struct ItemStack {
+    pub amount: Quantity,
+    /// 0.0 (spoiled) to 1.0 (perfect)
+    pub quality: f32,
+    /// Seconds since the stack was produced or gathered
+    pub age: f32,
+    /// Tile the stack was gathered from
+    pub origin: Option<Entity>,
+    /// Agent that gathered or crafted the stack
+    pub producer: Option<Entity>,
+    /// Spoilage too small to show up in a single tick
+    pub decay_remainder: Remainder,
+}
+
+impl ItemStack {
+    /// Creates a fresh, perfect stack of unknown provenance
+    pub fn new(amount: Quantity) -> Self {
+        Self {
+            amount,
+            quality: 1.0,
+            age: 0.0,
+            origin: None,
+            producer: None,
+            decay_remainder: Remainder::default(),
+        }
+    }
+
+    pub fn with_quality(mut self, quality: f32) -> Self {
+        self.quality = quality.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Returns a copy of the stack holding a different amount
+    ///
+    /// The copy starts without a decay remainder; it stays with this stack.
+    pub fn split(&self, amount: Quantity) -> Self {
+        Self { amount, decay_remainder: Remainder::default(), ..self.clone() }
+    }
+
+    /// Whether two stacks are close enough to be held as one
+    pub fn can_merge(&self, other: &ItemStack) -> bool {
+        self.origin == other.origin
+            && self.producer == other.producer
+            && (self.quality - other.quality).abs() <= QUALITY_MERGE_TOLERANCE
+            && (self.age - other.age).abs() <= AGE_MERGE_TOLERANCE
+    }
+
+    /// Merges another stack into this one, averaging quality and age by amount
+    pub fn merge(&mut self, other: ItemStack) {
+        let total = self.amount + other.amount;
+        if total.is_positive() {
+            let (own, theirs) = (self.amount.to_f32(), other.amount.to_f32());
+            self.quality = (self.quality * own + other.quality * theirs) / (own + theirs);
+            self.age = (self.age * own + other.age * theirs) / (own + theirs);
+        }
+        self.amount = total;
+    }
+
+    /// Ages the stack by `delta` seconds and returns the amount lost
+    ///
+    /// A stack whose quality reaches zero is spoiled and loses everything.
+    pub fn spoil(&mut self, delta: f32, spoilage: &Spoilage) -> Quantity {
+        let fresh_left = (spoilage.fresh_for - self.age).max(0.0);
+        self.age += delta;
+        let spoiling = (delta - fresh_left).max(0.0);
+        if spoiling <= 0.0 {
+            return Quantity::ZERO;
+        }
+
+        self.quality = (self.quality - spoilage.quality_loss * spoiling).max(0.0);
+        let lost = if self.quality <= 0.0 {
+            self.amount
+        } else {
+            let fraction = (spoilage.amount_loss * spoiling).min(1.0) as f64;
+            self.decay_remainder.accrue(self.amount.to_f64() * fraction).min(self.amount)
+        };
+        self.amount -= lost;
+        lost
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stack_spoils_after_fresh_period() {
+        let spoilage = Spoilage {
+            fresh_for: 10.0,
+            quality_loss: 0.1,
+            amount_loss: 0.0,
+        };
+        let mut stack = ItemStack::new(Quantity::from_units(5));
+
+        assert_eq!(stack.spoil(8.0, &spoilage), Quantity::ZERO);
+        assert_eq!(stack.quality, 1.0);
+
+        // Two seconds fresh, three spoiling
+        assert_eq!(stack.spoil(5.0, &spoilage), Quantity::ZERO);
+        assert!((stack.quality - 0.7).abs() < 1e-5);
+
+        assert_eq!(stack.spoil(10.0, &spoilage), Quantity::from_units(5));
+        assert_eq!(stack.amount, Quantity::ZERO);
+    }
+
+    #[test]
+    fn test_merge_averages_by_amount() {
+        let mut a = ItemStack::new(Quantity::from_units(3)).with_quality(1.0);
+        let b = ItemStack::new(Quantity::from_units(1)).with_quality(0.96);
+        assert!(a.can_merge(&b));
+        a.merge(b);
+        assert_eq!(a.amount, Quantity::from_units(4));
+        assert!((a.quality - 0.99).abs() < 1e-5);
+
+        let other_farm = ItemStack { origin: Some(Entity::from_raw(7)), ..ItemStack::new(Quantity::from_units(1)) };
+        assert!(!a.can_merge(&other_farm));
+    }
+
+    #[test]
+    fn test_small_stacks_spoil_over_short_ticks() {
+        let spoilage = Spoilage {
+            fresh_for: 0.0,
+            quality_loss: 0.0,
+            amount_loss: 0.002,
+        };
+        let mut stack = ItemStack::new(Quantity::from_units(5));
+
+        // Each tick loses a sixth of a milliunit, which only adds up across ticks
+        let lost: Quantity = (0..600).map(|_| stack.spoil(1.0 / 60.0, &spoilage)).sum();
+        assert!((lost.millis() - 100).abs() <= 1, "lost {}", lost);
+        assert_eq!(stack.amount + lost, Quantity::from_units(5));
+    }
+}
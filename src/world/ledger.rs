@@ -0,0 +1,806 @@
+use bevy::prelude::*;
+use std::collections::BTreeMap;
+use crate::engine::time::SimClock;
+use crate::world::items::ItemStack;
+use crate::world::ownership::AccessView;
+use crate::world::quantity::Quantity;
+use crate::world::resources::{ResourceChanged, ResourceId, ResourceManager, ResourceStore, ResourceSystem};
+
+/// One side of a resource movement
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Holder {
+    /// An entity with a `ResourceSystem`
+    Entity(Entity),
+    /// The global `ResourceManager`
+    Global,
+    /// Outside the tracked economy: deposits, regeneration, decay and losses.
+    /// May name the entity involved, such as the tile gathered from.
+    Environment(Option<Entity>),
+}
+
+/// Why resources moved
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum LedgerReason {
+    Gather,
+    Regeneration,
+    Decay,
+    Recipe(String),
+    Trade,
+    Flow,
+    FlowLoss,
+    Transfer,
+}
+
+/// A single recorded movement
+#[derive(Debug, Clone, PartialEq)]
+pub struct LedgerEntry {
+    pub tick: u64,
+    pub from: Holder,
+    pub to: Holder,
+    pub resource: ResourceId,
+    pub amount: Quantity,
+    pub reason: LedgerReason,
+    /// Entity that initiated the transaction
+    pub source: Option<Entity>,
+}
+
+/// Ticks of ledger entries kept by default
+pub const DEFAULT_LEDGER_RETENTION: u64 = 10_000;
+
+/// Resource holding the append-only record of committed movements
+///
+/// Entries older than `retention` ticks are dropped at the end of each
+/// tick. Readers that follow the ledger keep an absolute index from
+/// `end()` and read on with `entries_since()`, which stays valid after
+/// older entries are trimmed.
+#[derive(Debug, Resource)]
+pub struct ResourceLedger {
+    /// Ticks of entries kept, counting back from the current tick
+    pub retention: u64,
+    entries: Vec<LedgerEntry>,
+    /// Number of entries trimmed so far
+    base: usize,
+}
+
+impl Default for ResourceLedger {
+    fn default() -> Self {
+        Self {
+            retention: DEFAULT_LEDGER_RETENTION,
+            entries: Vec::new(),
+            base: 0,
+        }
+    }
+}
+
+impl ResourceLedger {
+    /// Appends an entry for a change applied outside a transaction, like
+    /// stacks spoiling in place
+    pub fn record(&mut self, entry: LedgerEntry) {
+        self.entries.push(entry);
+    }
+
+    /// Number of entries still kept
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    #[cfg(test)]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Absolute index of the next entry to be recorded
+    pub fn end(&self) -> usize {
+        self.base + self.entries.len()
+    }
+
+    /// Returns the entries from an absolute index on
+    ///
+    /// Entries before it that were already trimmed are skipped.
+    pub fn entries_since(&self, index: usize) -> &[LedgerEntry] {
+        let start = index.saturating_sub(self.base).min(self.entries.len());
+        &self.entries[start..]
+    }
+
+    /// Iterates over the entries recorded at or after a tick
+    #[cfg(test)]
+    pub fn since(&self, tick: u64) -> impl Iterator<Item = &LedgerEntry> {
+        // Entries are appended in tick order
+        let start = self.entries.partition_point(|entry| entry.tick < tick);
+        self.entries[start..].iter()
+    }
+
+    /// Drops the entries recorded before a tick
+    ///
+    /// Waits until at least half of the entries are stale, so shifting the
+    /// rest down costs constant time per entry.
+    pub fn trim_before(&mut self, tick: u64) {
+        let stale = self.entries.partition_point(|entry| entry.tick < tick);
+        if stale > 0 && stale * 2 >= self.entries.len() {
+            self.entries.drain(..stale);
+            self.base += stale;
+        }
+    }
+
+    /// Iterates over the movements of a resource into a holder
+    pub fn received_by(&self, holder: Holder, resource: ResourceId) -> impl Iterator<Item = &LedgerEntry> {
+        self.entries
+            .iter()
+            .filter(move |entry| entry.to == holder && entry.resource == resource)
+    }
+
+    /// Iterates over the movements of a resource out of a holder
+    #[cfg(test)]
+    pub fn sent_by(&self, holder: Holder, resource: ResourceId) -> impl Iterator<Item = &LedgerEntry> {
+        self.entries
+            .iter()
+            .filter(move |entry| entry.from == holder && entry.resource == resource)
+    }
+
+    /// Answers "where did this come from" for a holder's resource
+    ///
+    /// Returns the total received from each sender for each reason,
+    /// largest first.
+    // Provenance query for tools and tests; no system asks it yet
+    #[allow(dead_code)]
+    pub fn sources_of(&self, holder: Holder, resource: ResourceId) -> Vec<(Holder, LedgerReason, Quantity)> {
+        let mut totals: BTreeMap<(Holder, LedgerReason), Quantity> = BTreeMap::new();
+        for entry in self.received_by(holder, resource) {
+            *totals.entry((entry.from, entry.reason.clone())).or_default() += entry.amount;
+        }
+
+        let mut sources: Vec<_> = totals
+            .into_iter()
+            .map(|((from, reason), amount)| (from, reason, amount))
+            .collect();
+        sources.sort_by_key(|source| std::cmp::Reverse(source.2));
+        sources
+    }
+}
+
+/// System dropping ledger entries older than the ledger's retention
+pub fn trim_resource_ledger(
+    clock: Res<SimClock>,
+    mut ledger: ResMut<ResourceLedger>,
+) {
+    let cutoff = clock.tick().saturating_sub(ledger.retention);
+    ledger.trim_before(cutoff);
+}
+
+/// Reason a transaction was rejected
+#[derive(Debug, Clone, PartialEq)]
+pub enum TransactionError {
+    /// The holder has no store, e.g. a despawned entity
+    UnknownHolder(Holder),
+    /// Amounts must be non-negative
+    InvalidAmount(Quantity),
+    Insufficient {
+        holder: Holder,
+        resource: ResourceId,
+        available: Quantity,
+        required: Quantity,
+    },
+    NoSpace {
+        holder: Holder,
+        resource: ResourceId,
+        space: Quantity,
+        required: Quantity,
+    },
+    /// None of the entities behind the transaction may take from the holder
+    AccessDenied {
+        holder: Holder,
+        actor: Option<Entity>,
+    },
+}
+
+/// Access to the stores a transaction reads and writes
+///
+/// Environment holders never have a store; they act as unlimited sources
+/// and sinks.
+pub trait ResourceHolders {
+    fn store(&self, holder: Holder) -> Option<&ResourceStore>;
+    fn store_mut(&mut self, holder: Holder) -> Option<&mut ResourceStore>;
+
+    /// Whether `actor` may take resources out of `holder`
+    ///
+    /// Holders without ownership information only let an entity take from
+    /// itself.
+    fn can_withdraw(&self, holder: Entity, actor: Entity) -> bool {
+        holder == actor
+    }
+}
+
+/// Holders backed by a `ResourceSystem` query and optionally the global pool
+///
+/// Withdrawals are checked against stockpile ownership and access policies.
+pub struct HolderQuery<'a, 'w, 's> {
+    pub systems: &'a mut Query<'w, 's, &'static mut ResourceSystem>,
+    pub global: Option<&'a mut ResourceManager>,
+    pub access: AccessView<'a, 'a, 'a>,
+}
+
+impl<'a, 'w, 's> HolderQuery<'a, 'w, 's> {
+    pub fn new(systems: &'a mut Query<'w, 's, &'static mut ResourceSystem>, access: AccessView<'a, 'a, 'a>) -> Self {
+        Self { systems, global: None, access }
+    }
+}
+
+impl ResourceHolders for HolderQuery<'_, '_, '_> {
+    fn can_withdraw(&self, holder: Entity, actor: Entity) -> bool {
+        self.access.can_withdraw(actor, holder)
+    }
+
+    fn store(&self, holder: Holder) -> Option<&ResourceStore> {
+        match holder {
+            Holder::Entity(entity) => self.systems.get(entity).ok().map(|system| &system.store),
+            Holder::Global => self.global.as_deref().map(|manager| &manager.store),
+            Holder::Environment(_) => None,
+        }
+    }
+
+    fn store_mut(&mut self, holder: Holder) -> Option<&mut ResourceStore> {
+        match holder {
+            Holder::Entity(entity) => self
+                .systems
+                .get_mut(entity)
+                .ok()
+                .map(|system| &mut system.into_inner().store),
+            Holder::Global => self.global.as_deref_mut().map(|manager| &mut manager.store),
+            Holder::Environment(_) => None,
+        }
+    }
+}
+
+/// A single entity's store, for systems that already borrowed it
+pub struct SingleHolder<'a> {
+    pub entity: Entity,
+    pub store: &'a mut ResourceStore,
+}
+
+impl ResourceHolders for SingleHolder<'_> {
+    fn store(&self, holder: Holder) -> Option<&ResourceStore> {
+        (holder == Holder::Entity(self.entity)).then_some(&*self.store)
+    }
+
+    fn store_mut(&mut self, holder: Holder) -> Option<&mut ResourceStore> {
+        (holder == Holder::Entity(self.entity)).then_some(&mut *self.store)
+    }
+}
+
+/// A single movement within a transaction
+#[derive(Debug, Clone)]
+struct Movement {
+    from: Holder,
+    to: Holder,
+    resource: ResourceId,
+    amount: Quantity,
+    /// Quality and provenance of amounts created from the environment
+    stack: Option<ItemStack>,
+    /// Reason recorded instead of the transaction's
+    reason: Option<LedgerReason>,
+    /// Entity checked for access instead of the source
+    actor: Option<Entity>,
+}
+
+/// An all-or-nothing set of resource movements
+///
+/// Movements are checked against the net change per holder and resource,
+/// so a holder may pass on what it receives in the same transaction. If
+/// any holder would go negative or over capacity nothing is applied;
+/// otherwise every movement is written to the `ResourceLedger`. Stacks
+/// moved between holders keep their quality, age and provenance.
+///
+/// Every entity resources are taken from must let the source withdraw
+/// from it under its access policy, so a transaction without a source can
+/// only take from the environment and the global pool. Entities giving up
+/// their own holdings, like the two sides of a trade, are checked as
+/// acting for themselves instead.
+#[derive(Debug, Clone)]
+pub struct ResourceTransaction {
+    reason: LedgerReason,
+    source: Option<Entity>,
+    movements: Vec<Movement>,
+}
+
+impl ResourceTransaction {
+    pub fn new(reason: LedgerReason) -> Self {
+        Self {
+            reason,
+            source: None,
+            movements: Vec::new(),
+        }
+    }
+
+    /// Sets the entity reported as the source of the changes
+    pub fn with_source(mut self, source: Entity) -> Self {
+        self.source = Some(source);
+        self
+    }
+
+    /// Moves `amount` of a resource between two holders
+    pub fn transfer(&mut self, from: Holder, to: Holder, resource: ResourceId, amount: Quantity) -> &mut Self {
+        self.movements.push(Movement { from, to, resource, amount, stack: None, reason: None, actor: None });
+        self
+    }
+
+    /// Moves `amount` out of an entity's own holdings, checked as the
+    /// entity acting for itself, e.g. its side of a trade
+    ///
+    /// Agents may always give what they hold; stockpiles only if their
+    /// policy lets anyone take from them.
+    pub fn give(&mut self, from: Entity, to: Holder, resource: ResourceId, amount: Quantity) -> &mut Self {
+        self.movements.push(Movement {
+            from: Holder::Entity(from),
+            to,
+            resource,
+            amount,
+            stack: None,
+            reason: None,
+            actor: Some(from),
+        });
+        self
+    }
+
+    /// Adds resources to a holder from the environment
+    pub fn credit(&mut self, to: Holder, resource: ResourceId, amount: Quantity) -> &mut Self {
+        self.transfer(Holder::Environment(None), to, resource, amount)
+    }
+
+    /// Adds a stack with the given quality and provenance from the environment
+    pub fn credit_stack(&mut self, to: Holder, resource: ResourceId, stack: ItemStack) -> &mut Self {
+        self.movements.push(Movement {
+            from: Holder::Environment(None),
+            to,
+            resource,
+            amount: stack.amount,
+            stack: Some(stack),
+            reason: None,
+            actor: None,
+        });
+        self
+    }
+
+    /// Removes resources from a holder into the environment
+    pub fn debit(&mut self, from: Holder, resource: ResourceId, amount: Quantity) -> &mut Self {
+        self.transfer(from, Holder::Environment(None), resource, amount)
+    }
+
+    /// Removes resources into the environment under a reason of their own,
+    /// e.g. what a flow loses on the way
+    pub fn debit_as(&mut self, reason: LedgerReason, from: Holder, resource: ResourceId, amount: Quantity) -> &mut Self {
+        self.movements.push(Movement {
+            from,
+            to: Holder::Environment(None),
+            resource,
+            amount,
+            stack: None,
+            reason: Some(reason),
+            actor: None,
+        });
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.movements.is_empty()
+    }
+
+    /// Validates and applies every movement, or none of them
+    pub fn commit(
+        self,
+        tick: u64,
+        holders: &mut impl ResourceHolders,
+        ledger: &mut ResourceLedger,
+        event_writer: Option<&mut EventWriter<ResourceChanged>>,
+    ) -> Result<(), TransactionError> {
+        // Net change per holder and resource, ordered so events are deterministic
+        let mut net: BTreeMap<(Holder, ResourceId), Quantity> = BTreeMap::new();
+        for movement in &self.movements {
+            if movement.amount < Quantity::ZERO {
+                return Err(TransactionError::InvalidAmount(movement.amount));
+            }
+            *net.entry((movement.from, movement.resource)).or_default() -= movement.amount;
+            *net.entry((movement.to, movement.resource)).or_default() += movement.amount;
+        }
+
+        let mut old_amounts = BTreeMap::new();
+        for ((holder, resource), delta) in &net {
+            if matches!(holder, Holder::Environment(_)) {
+                continue;
+            }
+            let store = holders.store(*holder).ok_or(TransactionError::UnknownHolder(*holder))?;
+            let available = store.get(*resource);
+            if available + *delta < Quantity::ZERO {
+                return Err(TransactionError::Insufficient {
+                    holder: *holder,
+                    resource: *resource,
+                    available,
+                    required: -*delta,
+                });
+            }
+            let space = store.get_available_space(*resource);
+            if *delta > space {
+                return Err(TransactionError::NoSpace {
+                    holder: *holder,
+                    resource: *resource,
+                    space,
+                    required: *delta,
+                });
+            }
+            old_amounts.insert((*holder, *resource), available);
+        }
+
+        for movement in &self.movements {
+            let Holder::Entity(entity) = movement.from else {
+                continue;
+            };
+            let actor = movement.actor.or(self.source);
+            if !actor.is_some_and(|actor| holders.can_withdraw(entity, actor)) {
+                return Err(TransactionError::AccessDenied {
+                    holder: movement.from,
+                    actor,
+                });
+            }
+        }
+
+        // A holder may pass on what it receives, so find an order in which
+        // every movement is covered when it runs
+        let order = self.plan(holders)?;
+        for index in order {
+            let movement = &self.movements[index];
+            let shortfall = self.apply(movement, movement.amount, holders);
+            debug_assert!(shortfall.is_zero(), "planned movement came up {} short", shortfall);
+        }
+
+        self.send_events(&old_amounts, holders, event_writer);
+
+        for movement in self.movements {
+            if movement.amount.is_positive() {
+                ledger.entries.push(LedgerEntry {
+                    tick,
+                    from: movement.from,
+                    to: movement.to,
+                    resource: movement.resource,
+                    amount: movement.amount,
+                    reason: movement.reason.unwrap_or_else(|| self.reason.clone()),
+                    source: self.source,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Orders the movements so each holder only sends what it holds at
+    /// that point
+    ///
+    /// Runs the movements against the current balances, repeatedly taking
+    /// the first one whose sender can cover it. Movements left over, like
+    /// holders that only pass goods around in a circle, are reported as
+    /// `Insufficient` before anything is moved.
+    fn plan(&self, holders: &impl ResourceHolders) -> Result<Vec<usize>, TransactionError> {
+        fn balance<'a>(
+            balances: &'a mut BTreeMap<(Holder, ResourceId), Quantity>,
+            holders: &impl ResourceHolders,
+            holder: Holder,
+            resource: ResourceId,
+        ) -> &'a mut Quantity {
+            balances
+                .entry((holder, resource))
+                .or_insert_with(|| holders.store(holder).map_or(Quantity::ZERO, |store| store.get(resource)))
+        }
+
+        let mut balances = BTreeMap::new();
+        let mut pending: Vec<usize> = (0..self.movements.len()).collect();
+        let mut order = Vec::with_capacity(pending.len());
+
+        while !pending.is_empty() {
+            let ready = pending.iter().position(|&index| {
+                let movement = &self.movements[index];
+                matches!(movement.from, Holder::Environment(_))
+                    || *balance(&mut balances, holders, movement.from, movement.resource) >= movement.amount
+            });
+            let Some(position) = ready else {
+                let movement = &self.movements[pending[0]];
+                return Err(TransactionError::Insufficient {
+                    holder: movement.from,
+                    resource: movement.resource,
+                    available: *balance(&mut balances, holders, movement.from, movement.resource),
+                    required: movement.amount,
+                });
+            };
+
+            let index = pending.remove(position);
+            let movement = &self.movements[index];
+            if !matches!(movement.from, Holder::Environment(_)) {
+                *balance(&mut balances, holders, movement.from, movement.resource) -= movement.amount;
+            }
+            *balance(&mut balances, holders, movement.to, movement.resource) += movement.amount;
+            order.push(index);
+        }
+        Ok(order)
+    }
+
+    /// Moves up to `amount` for a movement and returns what couldn't be taken
+    fn apply(&self, movement: &Movement, amount: Quantity, holders: &mut impl ResourceHolders) -> Quantity {
+        let stacks = match movement.from {
+            Holder::Environment(origin) => {
+                let mut stack = movement.stack.clone().unwrap_or_else(|| ItemStack::new(amount));
+                stack.amount = amount;
+                stack.origin = stack.origin.or(origin);
+                stack.producer = stack.producer.or(self.source);
+                vec![stack]
+            }
+            from => match holders.store_mut(from) {
+                Some(store) => store.take(movement.resource, amount),
+                None => Vec::new(),
+            },
+        };
+
+        let moved: Quantity = stacks.iter().map(|stack| stack.amount).sum();
+        if let Some(store) = holders.store_mut(movement.to) {
+            for stack in stacks {
+                // Capacity was checked against the net change up front
+                store.merge_stack(movement.resource, stack);
+            }
+        }
+        amount - moved
+    }
+
+    fn send_events(
+        &self,
+        old_amounts: &BTreeMap<(Holder, ResourceId), Quantity>,
+        holders: &mut impl ResourceHolders,
+        event_writer: Option<&mut EventWriter<ResourceChanged>>,
+    ) {
+        let Some(events) = event_writer else {
+            return;
+        };
+        for ((holder, resource), old_amount) in old_amounts {
+            let Some(store) = holders.store(*holder) else {
+                continue;
+            };
+            let new_amount = store.get(*resource);
+            if new_amount != *old_amount {
+                events.send(ResourceChanged {
+                    resource_type: *resource,
+                    old_amount: *old_amount,
+                    new_amount,
+                    source: self.source,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+    use crate::world::ownership::{AccessPolicy, Membership, Owner, Stockpile};
+    use crate::world::resources::ResourceRegistry;
+
+    #[test]
+    fn test_transaction_is_all_or_nothing() {
+        let registry = ResourceRegistry::default();
+        let food = registry.id("food").unwrap();
+        let coin = registry.id("coin").unwrap();
+
+        let mut world = World::new();
+        world.init_resource::<ResourceLedger>();
+        let mut buyer_system = ResourceSystem::new();
+        buyer_system.add(coin, Quantity::from_units(5), None, None);
+        let buyer = world.spawn(buyer_system).id();
+        let mut seller_system = ResourceSystem::new();
+        seller_system.add(food, Quantity::from_units(10), None, None);
+        let seller = world.spawn(seller_system).id();
+
+        let results = world
+            .run_system_once(move |mut systems: Query<&'static mut ResourceSystem>,
+                                   stockpiles: Query<&'static Stockpile>,
+                                   members: Query<&'static Membership>,
+                                   mut ledger: ResMut<ResourceLedger>| {
+                let access = AccessView { stockpiles: &stockpiles, members: &members };
+                let mut holders = HolderQuery::new(&mut systems, access);
+
+                // The buyer can't pay for the second leg, so the first leg is not applied either
+                let mut expensive = ResourceTransaction::new(LedgerReason::Trade).with_source(buyer);
+                expensive
+                    .give(seller, Holder::Entity(buyer), food, Quantity::from_units(10))
+                    .give(buyer, Holder::Entity(seller), coin, Quantity::from_units(20));
+                let first = expensive.commit(1, &mut holders, &mut ledger, None);
+
+                let mut cheap = ResourceTransaction::new(LedgerReason::Trade).with_source(buyer);
+                cheap
+                    .give(seller, Holder::Entity(buyer), food, Quantity::from_units(2))
+                    .give(buyer, Holder::Entity(seller), coin, Quantity::from_units(5));
+                let second = cheap.commit(2, &mut holders, &mut ledger, None);
+                (first, second)
+            })
+            .unwrap();
+
+        assert!(matches!(results.0, Err(TransactionError::Insufficient { .. })));
+        assert!(results.1.is_ok());
+        assert_eq!(world.get::<ResourceSystem>(seller).unwrap().get(food), Quantity::from_units(8));
+        assert_eq!(world.get::<ResourceSystem>(buyer).unwrap().get(food), Quantity::from_units(2));
+        assert_eq!(world.get::<ResourceSystem>(buyer).unwrap().get(coin), Quantity::from_units(0));
+        assert_eq!(world.resource::<ResourceLedger>().len(), 2);
+    }
+
+    #[test]
+    fn test_ledger_provenance() {
+        let registry = ResourceRegistry::default();
+        let food = registry.id("food").unwrap();
+        let agent = Entity::from_raw(1);
+        let tile = Entity::from_raw(2);
+        let farmer = Entity::from_raw(3);
+
+        let mut store = ResourceStore::with_capacity(Quantity::from_units(100));
+        let mut ledger = ResourceLedger::default();
+        let mut holders = SingleHolder { entity: agent, store: &mut store };
+
+        let mut gather = ResourceTransaction::new(LedgerReason::Gather).with_source(agent);
+        gather.transfer(Holder::Environment(Some(tile)), Holder::Entity(agent), food, Quantity::from_units(4));
+        gather.commit(1, &mut holders, &mut ledger, None).unwrap();
+        assert_eq!(holders.store.stacks(food)[0].origin, Some(tile));
+        assert_eq!(holders.store.stacks(food)[0].producer, Some(agent));
+
+        // Entities outside the holder set can't be touched
+        let mut gift = ResourceTransaction::new(LedgerReason::Transfer);
+        gift.transfer(Holder::Entity(farmer), Holder::Entity(agent), food, Quantity::from_units(1));
+        assert_eq!(
+            gift.commit(2, &mut holders, &mut ledger, None),
+            Err(TransactionError::UnknownHolder(Holder::Entity(farmer)))
+        );
+
+        let mut regrow = ResourceTransaction::new(LedgerReason::Regeneration);
+        regrow.credit(Holder::Entity(agent), food, Quantity::from_units(1));
+        regrow.commit(3, &mut holders, &mut ledger, None).unwrap();
+
+        assert_eq!(store.get(food), Quantity::from_units(5));
+        assert_eq!(
+            ledger.sources_of(Holder::Entity(agent), food),
+            vec![
+                (Holder::Environment(Some(tile)), LedgerReason::Gather, Quantity::from_units(4)),
+                (Holder::Environment(None), LedgerReason::Regeneration, Quantity::from_units(1)),
+            ]
+        );
+        assert_eq!(ledger.since(2).count(), 1);
+    }
+
+    #[test]
+    fn test_trimming_keeps_cursors_valid() {
+        let registry = ResourceRegistry::default();
+        let food = registry.id("food").unwrap();
+        let mut ledger = ResourceLedger::default();
+        let entry = |tick| LedgerEntry {
+            tick,
+            from: Holder::Environment(None),
+            to: Holder::Global,
+            resource: food,
+            amount: Quantity::from_units(1),
+            reason: LedgerReason::Regeneration,
+            source: None,
+        };
+
+        for tick in 0..4 {
+            ledger.record(entry(tick));
+        }
+        let cursor = ledger.end();
+        ledger.record(entry(4));
+
+        // Fewer than half of the entries are stale, so nothing moves yet
+        ledger.trim_before(1);
+        assert_eq!(ledger.len(), 5);
+
+        ledger.trim_before(4);
+        assert_eq!(ledger.len(), 1);
+        assert_eq!(ledger.end(), 5);
+        assert_eq!(ledger.entries_since(cursor), [entry(4)]);
+        assert_eq!(ledger.entries_since(0).len(), 1);
+        assert!(ledger.entries_since(ledger.end()).is_empty());
+    }
+
+    #[test]
+    fn test_withdrawals_need_an_authorized_actor() {
+        let registry = ResourceRegistry::default();
+        let food = registry.id("food").unwrap();
+
+        let mut world = World::new();
+        world.init_resource::<ResourceLedger>();
+        let owner = world.spawn(ResourceSystem::new()).id();
+        let stranger = world.spawn(ResourceSystem::new()).id();
+        let mut pile = ResourceSystem::new();
+        pile.add(food, Quantity::from_units(10), None, None);
+        let granary = world.spawn((pile, Stockpile::new(Owner::Agent(owner), AccessPolicy::Private))).id();
+
+        let results = world
+            .run_system_once(move |mut systems: Query<&'static mut ResourceSystem>,
+                                   stockpiles: Query<&'static Stockpile>,
+                                   members: Query<&'static Membership>,
+                                   mut ledger: ResMut<ResourceLedger>| {
+                let access = AccessView { stockpiles: &stockpiles, members: &members };
+                let mut holders = HolderQuery::new(&mut systems, access);
+                let take = |source: Option<Entity>| {
+                    let mut transaction = ResourceTransaction::new(LedgerReason::Transfer);
+                    if let Some(source) = source {
+                        transaction = transaction.with_source(source);
+                    }
+                    transaction.transfer(Holder::Entity(granary), Holder::Entity(stranger), food, Quantity::from_units(1));
+                    transaction
+                };
+                let mut giveaway = ResourceTransaction::new(LedgerReason::Transfer).with_source(stranger);
+                giveaway.give(granary, Holder::Entity(stranger), food, Quantity::from_units(1));
+                [
+                    // Nobody vouches for the withdrawal
+                    take(None).commit(1, &mut holders, &mut ledger, None),
+                    // A stranger can't take from the private stockpile
+                    take(Some(stranger)).commit(1, &mut holders, &mut ledger, None),
+                    // Nor can the stockpile vouch for itself
+                    giveaway.commit(1, &mut holders, &mut ledger, None),
+                    take(Some(owner)).commit(1, &mut holders, &mut ledger, None),
+                ]
+            })
+            .unwrap();
+
+        assert!(matches!(results[0], Err(TransactionError::AccessDenied { actor: None, .. })));
+        assert!(matches!(results[1], Err(TransactionError::AccessDenied { .. })));
+        assert!(matches!(results[2], Err(TransactionError::AccessDenied { .. })));
+        // The owner is let in by the stockpile's policy
+        assert!(results[3].is_ok());
+        assert_eq!(world.get::<ResourceSystem>(stranger).unwrap().get(food), Quantity::from_units(1));
+    }
+
+    /// Stores anyone may take from, to test transactions without access rules
+    struct OpenStores(Vec<(Entity, ResourceStore)>);
+
+    impl ResourceHolders for OpenStores {
+        fn store(&self, holder: Holder) -> Option<&ResourceStore> {
+            self.0.iter().find(|(entity, _)| holder == Holder::Entity(*entity)).map(|(_, store)| store)
+        }
+
+        fn store_mut(&mut self, holder: Holder) -> Option<&mut ResourceStore> {
+            self.0.iter_mut().find(|(entity, _)| holder == Holder::Entity(*entity)).map(|(_, store)| store)
+        }
+
+        fn can_withdraw(&self, _holder: Entity, _actor: Entity) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn test_chains_are_ordered_and_never_under_deliver() {
+        let food = ResourceRegistry::default().id("food").unwrap();
+        let [a, b, c, d] = [1, 2, 3, 4].map(Entity::from_raw);
+        let mut holders = OpenStores([a, b, c, d].map(|entity| (entity, ResourceStore::with_capacity(Quantity::from_units(100)))).into());
+        holders.0[0].1.add(food, Quantity::from_units(5), None, None);
+        let mut ledger = ResourceLedger::default();
+        let units = Quantity::from_units;
+
+        // Goods pass along the chain even though it's listed backwards
+        let mut chain = ResourceTransaction::new(LedgerReason::Transfer).with_source(a);
+        chain
+            .transfer(Holder::Entity(c), Holder::Entity(d), food, units(5))
+            .transfer(Holder::Entity(b), Holder::Entity(c), food, units(5))
+            .transfer(Holder::Entity(a), Holder::Entity(b), food, units(5));
+        chain.commit(1, &mut holders, &mut ledger, None).unwrap();
+        let amounts = |holders: &OpenStores| holders.0.iter().map(|(_, store)| store.get(food)).collect::<Vec<_>>();
+        assert_eq!(amounts(&holders), [units(0), units(0), units(0), units(5)]);
+        assert_eq!(ledger.sources_of(Holder::Entity(d), food), vec![(Holder::Entity(c), LedgerReason::Transfer, units(5))]);
+
+        // The net change of every holder is fine, but B and C only pass
+        // goods they don't have around in a circle
+        let mut circle = ResourceTransaction::new(LedgerReason::Transfer).with_source(a);
+        circle
+            .transfer(Holder::Entity(d), Holder::Entity(c), food, units(2))
+            .transfer(Holder::Entity(b), Holder::Entity(c), food, units(4))
+            .transfer(Holder::Entity(c), Holder::Entity(b), food, units(4))
+            .transfer(Holder::Entity(c), Holder::Entity(a), food, units(2));
+        assert_eq!(
+            circle.commit(2, &mut holders, &mut ledger, None),
+            Err(TransactionError::Insufficient { holder: Holder::Entity(b), resource: food, available: units(0), required: units(4) })
+        );
+        assert_eq!(amounts(&holders), [units(0), units(0), units(0), units(5)]);
+        assert_eq!(ledger.len(), 3);
+    }
+}
@@ -0,0 +1,652 @@
+use bevy::prelude::*;
+use std::collections::{HashMap, VecDeque};
+use crate::agents::agent::Agent;
+use crate::engine::time::SimClock;
+use crate::world::ledger::{Holder, HolderQuery, LedgerReason, ResourceHolders, ResourceLedger, ResourceTransaction};
+use crate::world::ownership::{AccessView, Membership, Stockpile};
+use crate::world::quantity::Quantity;
+use crate::world::resources::{ResourceChanged, ResourceId, ResourceRegistry, ResourceSystem};
+
+/// Maximum number of price points kept per resource
+const MAX_PRICE_HISTORY: usize = 1000;
+
+/// Units of each edible resource an agent tries to keep on hand
+const FOOD_RESERVE: Quantity = Quantity::from_units(10);
+
+/// Price per unit agents start from before a resource has traded
+const OPENING_PRICE: f32 = 1.0;
+
+/// Memory key holding an agent's last trade
+pub const LAST_TRADE_MEMORY: &str = "last_trade";
+
+/// Identifier of an order placed on the market
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct OrderId(pub u64);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OrderSide {
+    Buy,
+    Sell,
+}
+
+/// A limit order to buy or sell a resource
+#[derive(Debug, Clone, PartialEq)]
+pub struct Order {
+    pub id: OrderId,
+    /// Entity whose `ResourceSystem` pays and receives
+    pub trader: Entity,
+    pub side: OrderSide,
+    pub resource: ResourceId,
+    /// Quantity still to be filled
+    pub quantity: Quantity,
+    /// Highest price a buyer pays or lowest price a seller accepts, per unit
+    pub limit_price: f32,
+}
+
+/// A completed exchange between two traders
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trade {
+    /// Simulation time in seconds when the trade cleared
+    pub time: f32,
+    pub resource: ResourceId,
+    pub buyer: Entity,
+    pub seller: Entity,
+    pub quantity: Quantity,
+    /// Price per unit, in the market currency
+    pub price: f32,
+}
+
+/// Event fired for every trade the market clears
+#[derive(Event, Debug)]
+pub struct TradeExecuted {
+    pub trade: Trade,
+}
+
+/// Clearing price and volume of one auction
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PricePoint {
+    pub time: f32,
+    pub price: f32,
+    pub volume: Quantity,
+}
+
+/// Resource holding the append-only record of all trades
+#[derive(Debug, Default, Resource)]
+pub struct TradeLedger {
+    trades: Vec<Trade>,
+}
+
+impl TradeLedger {
+    pub fn record(&mut self, trade: Trade) {
+        self.trades.push(trade);
+    }
+
+    // Read by economic studies through tests; no system queries past trades yet
+    #[allow(dead_code)]
+    pub fn trades(&self) -> &[Trade] {
+        &self.trades
+    }
+
+    /// Iterates over the trades a holder took part in
+    #[allow(dead_code)]
+    pub fn trades_for(&self, trader: Entity) -> impl Iterator<Item = &Trade> {
+        self.trades
+            .iter()
+            .filter(move |trade| trade.buyer == trader || trade.seller == trader)
+    }
+}
+
+/// Buy and sell orders for a single resource
+#[derive(Debug, Clone, Default)]
+pub struct OrderBook {
+    pub bids: Vec<Order>,
+    pub asks: Vec<Order>,
+}
+
+impl OrderBook {
+    /// Sorts bids by descending and asks by ascending price, oldest first on ties
+    fn sort(&mut self) {
+        self.bids.sort_by(|a, b| b.limit_price.total_cmp(&a.limit_price).then(a.id.cmp(&b.id)));
+        self.asks.sort_by(|a, b| a.limit_price.total_cmp(&b.limit_price).then(a.id.cmp(&b.id)));
+    }
+
+    /// Finds the uniform clearing price of the crossing orders
+    ///
+    /// Walks both sides from the best price until they no longer cross and
+    /// returns the midpoint of the last matched bid and ask.
+    fn clearing_price(&self) -> Option<f32> {
+        let (mut bid_index, mut ask_index) = (0, 0);
+        let mut bid_left = self.bids.first()?.quantity;
+        let mut ask_left = self.asks.first()?.quantity;
+        let mut marginal = None;
+
+        while bid_index < self.bids.len() && ask_index < self.asks.len() {
+            let bid = &self.bids[bid_index];
+            let ask = &self.asks[ask_index];
+            if bid.limit_price < ask.limit_price {
+                break;
+            }
+            marginal = Some((bid.limit_price + ask.limit_price) / 2.0);
+
+            let matched = bid_left.min(ask_left);
+            bid_left -= matched;
+            ask_left -= matched;
+            if bid_left.is_zero() {
+                bid_index += 1;
+                bid_left = self.bids.get(bid_index).map_or(Quantity::ZERO, |order| order.quantity);
+            }
+            if ask_left.is_zero() {
+                ask_index += 1;
+                ask_left = self.asks.get(ask_index).map_or(Quantity::ZERO, |order| order.quantity);
+            }
+        }
+
+        marginal
+    }
+}
+
+/// Resource implementing a periodic call auction between traders
+///
+/// Traders post limit orders that rest in per-resource order books. Every
+/// `clearing_interval` seconds each book is cleared at a single price set
+/// by the marginal crossing bid and ask, goods and currency are exchanged
+/// in a single `Trade` transaction, and the result is written to the
+/// `TradeLedger` and the price history.
+#[derive(Debug, Resource)]
+pub struct Market {
+    /// Resource used to pay for trades
+    pub currency: Option<ResourceId>,
+    /// Seconds between two auctions
+    pub clearing_interval: f32,
+    books: HashMap<ResourceId, OrderBook>,
+    history: HashMap<ResourceId, VecDeque<PricePoint>>,
+    next_order_id: u64,
+    time_since_clearing: f32,
+}
+
+impl Default for Market {
+    fn default() -> Self {
+        Self {
+            currency: None,
+            clearing_interval: 1.0,
+            books: HashMap::new(),
+            history: HashMap::new(),
+            next_order_id: 0,
+            time_since_clearing: 0.0,
+        }
+    }
+}
+
+impl Market {
+    /// Places a limit order and returns its ID
+    pub fn place_order(&mut self, trader: Entity, side: OrderSide, resource: ResourceId, quantity: Quantity, limit_price: f32) -> OrderId {
+        let id = OrderId(self.next_order_id);
+        self.next_order_id += 1;
+
+        let order = Order {
+            id,
+            trader,
+            side,
+            resource,
+            quantity,
+            limit_price,
+        };
+        let book = self.books.entry(resource).or_default();
+        match side {
+            OrderSide::Buy => book.bids.push(order),
+            OrderSide::Sell => book.asks.push(order),
+        }
+        id
+    }
+
+    /// Removes every order placed by a trader
+    pub fn cancel_orders_of(&mut self, trader: Entity) {
+        for book in self.books.values_mut() {
+            book.bids.retain(|order| order.trader != trader);
+            book.asks.retain(|order| order.trader != trader);
+        }
+    }
+
+    #[cfg(test)]
+    pub fn order_book(&self, resource: ResourceId) -> Option<&OrderBook> {
+        self.books.get(&resource)
+    }
+
+    /// Returns the clearing price of the most recent auction with trades
+    pub fn last_price(&self, resource: ResourceId) -> Option<f32> {
+        self.history.get(&resource)?.back().map(|point| point.price)
+    }
+
+    /// Returns past clearing prices, oldest first
+    // Kept for economic studies; agents only look at the last price
+    #[allow(dead_code)]
+    pub fn price_history(&self, resource: ResourceId) -> impl Iterator<Item = &PricePoint> {
+        self.history.get(&resource).into_iter().flatten()
+    }
+
+    /// Runs one auction for every order book
+    ///
+    /// Orders whose trader can't pay, deliver or store the goods are
+    /// filled as far as possible and then cancelled. Orders of traders that
+    /// may not take from their own holdings, like stockpiles that aren't
+    /// public, are cancelled before clearing.
+    #[allow(clippy::too_many_arguments)]
+    pub fn clear<'a>(
+        &mut self,
+        time: f32,
+        tick: u64,
+        holders: &'a mut Query<&'static mut ResourceSystem>,
+        access: AccessView<'a, 'a, 'a>,
+        ledger: &mut TradeLedger,
+        resource_ledger: &mut ResourceLedger,
+        mut event_writer: Option<&mut EventWriter<ResourceChanged>>,
+    ) -> Vec<Trade> {
+        let Some(currency) = self.currency else {
+            return Vec::new();
+        };
+
+        let mut holders = HolderQuery::new(holders, access);
+        let mut trades = Vec::new();
+        let mut resources: Vec<ResourceId> = self.books.keys().copied().collect();
+        resources.sort();
+
+        for resource in resources {
+            let book = self.books.get_mut(&resource).unwrap();
+            let can_trade = |order: &Order| {
+                holders.systems.contains(order.trader) && holders.can_withdraw(order.trader, order.trader)
+            };
+            book.bids.retain(can_trade);
+            book.asks.retain(can_trade);
+            book.sort();
+
+            let Some(price) = book.clearing_price() else {
+                continue;
+            };
+
+            let mut volume = Quantity::ZERO;
+            let (mut bid_index, mut ask_index) = (0, 0);
+            loop {
+                // Asks filled out of order, past the bidder's own, are skipped
+                while book.asks.get(ask_index).is_some_and(|ask| ask.quantity.is_zero()) {
+                    ask_index += 1;
+                }
+                if bid_index >= book.bids.len() || ask_index >= book.asks.len() {
+                    break;
+                }
+                let (bid, ask) = (&book.bids[bid_index], &book.asks[ask_index]);
+                if bid.limit_price < price || ask.limit_price > price {
+                    break;
+                }
+                // Never trade with yourself; the bid takes the next ask of
+                // someone else and leaves its own asks for the next bids
+                let Some(match_index) = (ask_index..book.asks.len()).find(|&index| {
+                    let ask = &book.asks[index];
+                    ask.trader != bid.trader && ask.quantity.is_positive()
+                }) else {
+                    bid_index += 1;
+                    continue;
+                };
+                let ask = &book.asks[match_index];
+                if ask.limit_price > price {
+                    bid_index += 1;
+                    continue;
+                }
+
+                let (buyer, seller) = (bid.trader, ask.trader);
+                let Ok([buyer_system, seller_system]) = holders.systems.get_many([buyer, seller]) else {
+                    break;
+                };
+
+                // Limit the quantity to what both sides can settle
+                let mut quantity = bid.quantity.min(ask.quantity);
+                quantity = quantity.min(seller_system.get(resource));
+                quantity = quantity.min(buyer_system.get_available_space(resource));
+                if price > 0.0 {
+                    // Rounding down keeps the payment within what can be paid and stored
+                    quantity = quantity.min(buyer_system.get(currency).div_f32_floor(price));
+                    quantity = quantity.min(seller_system.get_available_space(currency).div_f32_floor(price));
+                }
+
+                if quantity.is_positive() {
+                    let mut settlement = ResourceTransaction::new(LedgerReason::Trade).with_source(buyer);
+                    settlement
+                        .give(seller, Holder::Entity(buyer), resource, quantity)
+                        .give(buyer, Holder::Entity(seller), currency, quantity.mul_f32(price));
+                    if let Err(err) = settlement.commit(tick, &mut holders, resource_ledger, event_writer.as_deref_mut()) {
+                        warn!("Trade settlement failed: {:?}", err);
+                        break;
+                    }
+
+                    let trade = Trade {
+                        time,
+                        resource,
+                        buyer,
+                        seller,
+                        quantity,
+                        price,
+                    };
+                    ledger.record(trade.clone());
+                    trades.push(trade);
+                    volume += quantity;
+                }
+
+                let bid = &mut book.bids[bid_index];
+                bid.quantity -= quantity;
+                let bid_done = bid.quantity.is_zero();
+                let ask = &mut book.asks[match_index];
+                ask.quantity -= quantity;
+                let ask_done = ask.quantity.is_zero();
+
+                if !bid_done && !ask_done {
+                    // Neither order is filled, so one side couldn't settle; drop
+                    // whichever trader ran out
+                    let Ok(buyer_system) = holders.systems.get(buyer) else {
+                        break;
+                    };
+                    let buyer_short = (price > 0.0 && buyer_system.get(currency).div_f32_floor(price).is_zero())
+                        || buyer_system.get_available_space(resource).is_zero();
+                    if buyer_short {
+                        book.bids[bid_index].quantity = Quantity::ZERO;
+                        bid_index += 1;
+                    } else {
+                        book.asks[match_index].quantity = Quantity::ZERO;
+                    }
+                    continue;
+                }
+                if bid_done {
+                    bid_index += 1;
+                }
+            }
+
+            book.bids.retain(|order| order.quantity.is_positive());
+            book.asks.retain(|order| order.quantity.is_positive());
+
+            if volume.is_positive() {
+                let history = self.history.entry(resource).or_default();
+                history.push_back(PricePoint { time, price, volume });
+                if history.len() > MAX_PRICE_HISTORY {
+                    history.pop_front();
+                }
+            }
+        }
+
+        trades
+    }
+}
+
+/// Picks the market currency from the registry's "currency" tag
+pub fn configure_market_currency(
+    registry: Res<ResourceRegistry>,
+    mut market: ResMut<Market>,
+) {
+    market.currency = registry.with_tag("currency").next();
+    if market.currency.is_none() {
+        warn!("No resource tagged 'currency'; the market won't clear");
+    }
+}
+
+/// System where agents trade edible resources towards `FOOD_RESERVE`
+///
+/// Every tick each agent replaces its orders: it bids for what it lacks and
+/// offers what it holds beyond the reserve. Prices start from the last
+/// clearing price and move with the agent's need, so hungry agents bid up
+/// to twice the price and well-stocked ones ask less, which is how prices
+/// follow supply and demand.
+pub fn post_agent_orders(
+    registry: Res<ResourceRegistry>,
+    mut market: ResMut<Market>,
+    agents: Query<(Entity, &ResourceSystem), With<Agent>>,
+) {
+    let Some(currency) = market.currency else {
+        return;
+    };
+
+    for (entity, system) in agents.iter() {
+        market.cancel_orders_of(entity);
+        for resource in registry.with_tag("edible") {
+            let price = market.last_price(resource).unwrap_or(OPENING_PRICE);
+            let held = system.get(resource);
+            if held < FOOD_RESERVE {
+                if system.get(currency).is_positive() {
+                    let need = 1.0 - held.to_f32() / FOOD_RESERVE.to_f32();
+                    market.place_order(entity, OrderSide::Buy, resource, FOOD_RESERVE - held, price * (1.0 + need));
+                }
+            } else if held > FOOD_RESERVE {
+                let ask = price * FOOD_RESERVE.to_f32() / held.to_f32();
+                market.place_order(entity, OrderSide::Sell, resource, held - FOOD_RESERVE, ask);
+            }
+        }
+    }
+}
+
+/// System that clears the market every `clearing_interval` seconds
+#[allow(clippy::too_many_arguments)]
+pub fn market_clearing_system(
+    clock: Res<SimClock>,
+    registry: Res<ResourceRegistry>,
+    mut market: ResMut<Market>,
+    mut ledger: ResMut<TradeLedger>,
+    mut resource_ledger: ResMut<ResourceLedger>,
+    mut holders: Query<&'static mut ResourceSystem>,
+    stockpiles: Query<&'static Stockpile>,
+    members: Query<&'static Membership>,
+    mut events: EventWriter<ResourceChanged>,
+    mut trade_events: EventWriter<TradeExecuted>,
+) {
+    market.time_since_clearing += clock.tick_length as f32;
+    if market.time_since_clearing < market.clearing_interval {
+        return;
+    }
+    market.time_since_clearing = 0.0;
+
+    let access = AccessView { stockpiles: &stockpiles, members: &members };
+    let trades = market.clear(clock.elapsed() as f32, clock.tick(), &mut holders, access, &mut ledger, &mut resource_ledger, Some(&mut events));
+    for trade in trades {
+        debug!("Trade: {} {} at {:.2}", trade.quantity, registry.key(trade.resource), trade.price);
+        trade_events.send(TradeExecuted { trade });
+    }
+}
+
+/// System where agents remember the last trade they took part in
+pub fn remember_trades(
+    registry: Res<ResourceRegistry>,
+    mut trades: EventReader<TradeExecuted>,
+    mut agents: Query<&mut Agent>,
+) {
+    for TradeExecuted { trade } in trades.read() {
+        let resource = registry.key(trade.resource);
+        for (trader, verb) in [(trade.buyer, "bought"), (trade.seller, "sold")] {
+            if let Ok(mut agent) = agents.get_mut(trader) {
+                let memory = format!("{} {} {} at {:.2}", verb, trade.quantity, resource, trade.price);
+                agent.memory.insert(LAST_TRADE_MEMORY.to_string(), memory);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+
+    struct Setup {
+        world: World,
+        food: ResourceId,
+        coin: ResourceId,
+    }
+
+    fn setup() -> Setup {
+        let registry = ResourceRegistry::default();
+        let mut world = World::new();
+        world.insert_resource(Market {
+            currency: registry.id("coin"),
+            ..default()
+        });
+        world.init_resource::<TradeLedger>();
+        world.init_resource::<ResourceLedger>();
+        Setup {
+            world,
+            food: registry.id("food").unwrap(),
+            coin: registry.id("coin").unwrap(),
+        }
+    }
+
+    fn trader(world: &mut World, holdings: &[(ResourceId, i64)]) -> Entity {
+        let mut system = ResourceSystem::new();
+        for (resource, units) in holdings {
+            system.add(*resource, Quantity::from_units(*units), None, None);
+        }
+        world.spawn(system).id()
+    }
+
+    fn clear(world: &mut World) {
+        world
+            .run_system_once(
+                |mut market: ResMut<Market>,
+                 mut ledger: ResMut<TradeLedger>,
+                 mut resource_ledger: ResMut<ResourceLedger>,
+                 mut holders: Query<&'static mut ResourceSystem>,
+                 stockpiles: Query<&'static Stockpile>,
+                 members: Query<&'static Membership>| {
+                    let access = AccessView { stockpiles: &stockpiles, members: &members };
+                    market.clear(0.0, 0, &mut holders, access, &mut ledger, &mut resource_ledger, None);
+                },
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn test_crossing_orders_clear_at_marginal_price() {
+        let Setup { mut world, food, coin } = setup();
+        let farmer = trader(&mut world, &[(food, 20)]);
+        let smith = trader(&mut world, &[(coin, 100)]);
+
+        {
+            let mut market = world.resource_mut::<Market>();
+            market.place_order(farmer, OrderSide::Sell, food, Quantity::from_units(10), 2.0);
+            market.place_order(smith, OrderSide::Buy, food, Quantity::from_units(10), 4.0);
+        }
+        clear(&mut world);
+
+        let market = world.resource::<Market>();
+        assert_eq!(market.last_price(food), Some(3.0));
+        let history: Vec<_> = market.price_history(food).collect();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].volume, Quantity::from_units(10));
+        assert!(market.order_book(food).unwrap().bids.is_empty());
+
+        let ledger = world.resource::<TradeLedger>();
+        assert_eq!(ledger.trades().len(), 1);
+        assert_eq!(ledger.trades_for(farmer).count(), 1);
+
+        let farmer_system = world.get::<ResourceSystem>(farmer).unwrap();
+        assert_eq!(farmer_system.get(food), Quantity::from_units(10));
+        assert_eq!(farmer_system.get(coin), Quantity::from_units(30));
+        let smith_system = world.get::<ResourceSystem>(smith).unwrap();
+        assert_eq!(smith_system.get(food), Quantity::from_units(10));
+        assert_eq!(smith_system.get(coin), Quantity::from_units(70));
+    }
+
+    #[test]
+    fn test_unfunded_buyer_is_partially_filled_and_cancelled() {
+        let Setup { mut world, food, coin } = setup();
+        let farmer = trader(&mut world, &[(food, 20)]);
+        let pauper = trader(&mut world, &[(coin, 4)]);
+
+        {
+            let mut market = world.resource_mut::<Market>();
+            market.place_order(farmer, OrderSide::Sell, food, Quantity::from_units(10), 2.0);
+            market.place_order(pauper, OrderSide::Buy, food, Quantity::from_units(10), 2.0);
+        }
+        clear(&mut world);
+
+        // Two coins per unit buys only two units
+        let pauper_system = world.get::<ResourceSystem>(pauper).unwrap();
+        assert_eq!(pauper_system.get(food), Quantity::from_units(2));
+        assert_eq!(pauper_system.get(coin), Quantity::from_units(0));
+
+        let book = world.resource::<Market>().order_book(food).unwrap();
+        assert!(book.bids.is_empty());
+        assert_eq!(book.asks[0].quantity, Quantity::from_units(8));
+
+        // Non-crossing orders don't trade
+        world.resource_mut::<Market>().place_order(pauper, OrderSide::Buy, food, Quantity::from_units(1), 1.0);
+        clear(&mut world);
+        assert_eq!(world.resource::<TradeLedger>().trades().len(), 1);
+    }
+
+    #[test]
+    fn test_own_asks_are_skipped_without_dropping_the_bid() {
+        let Setup { mut world, food, coin } = setup();
+        let dealer = trader(&mut world, &[(food, 5), (coin, 100)]);
+        let farmer = trader(&mut world, &[(food, 5)]);
+        let smith = trader(&mut world, &[(coin, 100)]);
+
+        {
+            // The dealer's own ask is the cheapest, ahead of the farmer's
+            let mut market = world.resource_mut::<Market>();
+            market.place_order(dealer, OrderSide::Buy, food, Quantity::from_units(5), 4.0);
+            market.place_order(smith, OrderSide::Buy, food, Quantity::from_units(5), 3.5);
+            market.place_order(dealer, OrderSide::Sell, food, Quantity::from_units(5), 2.0);
+            market.place_order(farmer, OrderSide::Sell, food, Quantity::from_units(5), 3.0);
+        }
+        clear(&mut world);
+
+        let ledger = world.resource::<TradeLedger>();
+        assert_eq!(ledger.trades().len(), 2);
+        assert!(ledger.trades().iter().all(|trade| trade.buyer != trade.seller));
+        assert!(ledger.trades().iter().any(|trade| trade.buyer == dealer && trade.seller == farmer));
+        assert!(ledger.trades().iter().any(|trade| trade.buyer == smith && trade.seller == dealer));
+
+        let book = world.resource::<Market>().order_book(food).unwrap();
+        assert!(book.bids.is_empty() && book.asks.is_empty());
+        assert_eq!(world.get::<ResourceSystem>(farmer).unwrap().get(food), Quantity::ZERO);
+        assert_eq!(world.get::<ResourceSystem>(dealer).unwrap().get(food), Quantity::from_units(5));
+        assert_eq!(world.get::<ResourceSystem>(smith).unwrap().get(food), Quantity::from_units(5));
+    }
+
+    #[test]
+    fn test_agents_trade_towards_their_food_reserve() {
+        let Setup { mut world, food, coin } = setup();
+        world.init_resource::<ResourceRegistry>();
+        let hungry = trader(&mut world, &[(food, 5), (coin, 100)]);
+        let farmer = trader(&mut world, &[(food, 30)]);
+        world.entity_mut(hungry).insert(Agent::default());
+        world.entity_mut(farmer).insert(Agent::default());
+
+        world.run_system_once(post_agent_orders).unwrap();
+        world.run_system_once(post_agent_orders).unwrap();
+        {
+            // Orders are replaced, not piled up
+            let book = world.resource::<Market>().order_book(food).unwrap();
+            assert_eq!(book.bids.len(), 1);
+            assert_eq!(book.bids[0].quantity, Quantity::from_units(5));
+            assert_eq!(book.bids[0].limit_price, 1.5);
+            assert_eq!(book.asks.len(), 1);
+            assert_eq!(book.asks[0].quantity, Quantity::from_units(20));
+            assert!((book.asks[0].limit_price - 1.0 / 3.0).abs() < 1e-6);
+        }
+        clear(&mut world);
+
+        assert_eq!(world.get::<ResourceSystem>(hungry).unwrap().get(food), Quantity::from_units(10));
+        assert_eq!(world.get::<ResourceSystem>(farmer).unwrap().get(food), Quantity::from_units(25));
+        assert!(world.resource::<Market>().last_price(food).unwrap() < OPENING_PRICE);
+    }
+
+    #[test]
+    fn test_agents_remember_their_last_trade() {
+        let Setup { mut world, food, .. } = setup();
+        world.init_resource::<ResourceRegistry>();
+        world.init_resource::<Events<TradeExecuted>>();
+        let buyer = world.spawn(Agent::default()).id();
+        let seller = world.spawn(Agent::default()).id();
+        let trade = Trade { time: 0.0, resource: food, buyer, seller, quantity: Quantity::from_units(5), price: 1.5 };
+        world.send_event(TradeExecuted { trade });
+
+        world.run_system_once(remember_trades).unwrap();
+
+        let memory = |entity| world.get::<Agent>(entity).unwrap().memory.get(LAST_TRADE_MEMORY).cloned();
+        assert_eq!(memory(buyer), Some(format!("bought {} food at 1.50", Quantity::from_units(5))));
+        assert_eq!(memory(seller), Some(format!("sold {} food at 1.50", Quantity::from_units(5))));
+    }
+}
@@ -2,6 +2,20 @@ pub mod chunk;
 pub mod hex;
 pub mod terrain;
 pub mod position;
+pub mod resources;
+pub mod items;
+pub mod quantity;
+pub mod deposits;
+pub mod recipes;
+pub mod resource_flow;
+pub mod market;
+pub mod ledger;
+pub mod ownership;
+pub mod storage;
+pub mod resource_history;
+pub mod conservation;
+pub mod structure;
+pub mod hydrology;
 
 // Re-export commonly used types
 pub use position::Position;
@@ -0,0 +1,270 @@
+use bevy::prelude::*;
+use crate::agents::agent::Agent;
+use crate::agents::job::Job;
+use crate::engine::time::SimClock;
+use crate::world::ledger::{Holder, HolderQuery, LedgerReason, ResourceLedger, ResourceTransaction, TransactionError};
+use crate::world::quantity::Quantity;
+use crate::world::resources::{ResourceChanged, ResourceId, ResourceRegistry, ResourceSystem};
+
+/// Identifier of a household agents can belong to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct HouseholdId(pub u32);
+
+/// Identifier of a faction agents can belong to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FactionId(pub u32);
+
+/// Component recording the groups an agent belongs to
+#[derive(Component, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Membership {
+    pub household: Option<HouseholdId>,
+    pub faction: Option<FactionId>,
+}
+
+/// Who a stockpile belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Owner {
+    Agent(Entity),
+    // Group stockpiles are founded together with their groups, which
+    // nothing does yet
+    #[allow(dead_code)]
+    Household(HouseholdId),
+    #[allow(dead_code)]
+    Faction(FactionId),
+}
+
+impl Owner {
+    /// Whether an agent is the owner or a member of the owning group
+    pub fn includes(&self, agent: Entity, membership: Option<&Membership>) -> bool {
+        match self {
+            Owner::Agent(owner) => *owner == agent,
+            Owner::Household(household) => membership.is_some_and(|m| m.household == Some(*household)),
+            Owner::Faction(faction) => membership.is_some_and(|m| m.faction == Some(*faction)),
+        }
+    }
+}
+
+/// Who may take resources out of a stockpile
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum AccessPolicy {
+    /// Only the owner, or members of the owning group
+    #[default]
+    Private,
+    /// The owner and their kin. For an agent's stockpile, kin are the agents
+    /// of the owner's household; for group stockpiles, the group's members.
+    Kin,
+    /// Anyone
+    // Opened up by group stockpiles, see `Owner::Household`
+    #[allow(dead_code)]
+    Public,
+}
+
+/// Component marking a `ResourceSystem` as an owned stockpile
+///
+/// Anyone may put resources into a stockpile, but taking them out requires
+/// access under its policy. Entities holding a `ResourceSystem` without a
+/// `Stockpile`, such as an agent's own inventory, are private to themselves.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stockpile {
+    pub owner: Owner,
+    pub policy: AccessPolicy,
+}
+
+impl Stockpile {
+    pub fn new(owner: Owner, policy: AccessPolicy) -> Self {
+        Self { owner, policy }
+    }
+}
+
+/// Event fired when an agent tries to take from a stockpile it has no access to
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct TheftAttempt {
+    pub tick: u64,
+    pub thief: Entity,
+    pub stockpile: Entity,
+    pub owner: Option<Owner>,
+    pub resource: ResourceId,
+    pub amount: Quantity,
+}
+
+/// Read access to ownership data, used to check withdrawals
+pub struct AccessView<'a, 'w, 's> {
+    pub stockpiles: &'a Query<'w, 's, &'static Stockpile>,
+    pub members: &'a Query<'w, 's, &'static Membership>,
+}
+
+impl AccessView<'_, '_, '_> {
+    /// Whether `actor` may take resources out of `holder`
+    ///
+    /// Holders without a `Stockpile` only allow themselves. A stockpile
+    /// can't authorize its own withdrawals; only its policy decides.
+    pub fn can_withdraw(&self, actor: Entity, holder: Entity) -> bool {
+        let Ok(stockpile) = self.stockpiles.get(holder) else {
+            return actor == holder;
+        };
+        let membership = self.members.get(actor).ok();
+        match stockpile.policy {
+            AccessPolicy::Public => true,
+            AccessPolicy::Private => stockpile.owner.includes(actor, membership),
+            AccessPolicy::Kin => {
+                stockpile.owner.includes(actor, membership)
+                    || match stockpile.owner {
+                        Owner::Agent(owner) => {
+                            let household = self.members.get(owner).ok().and_then(|m| m.household);
+                            household.is_some() && membership.and_then(|m| m.household) == household
+                        }
+                        Owner::Household(_) | Owner::Faction(_) => false,
+                    }
+            }
+        }
+    }
+
+    /// Returns the owner of a stockpile, if the holder is one
+    pub fn owner_of(&self, holder: Entity) -> Option<Owner> {
+        self.stockpiles.get(holder).ok().map(|stockpile| stockpile.owner)
+    }
+}
+
+/// System that executes `Job::Withdraw` and `Job::Deposit` for agents
+///
+/// Both move the requested amount between the agent's inventory and a
+/// stockpile in one transaction, as far as the stockpile and inventory
+/// allow. Withdrawals the agent has no access to are refused and reported
+/// as a `TheftAttempt`.
+#[allow(clippy::too_many_arguments)]
+pub fn stockpile_job_system(
+    clock: Res<SimClock>,
+    registry: Res<ResourceRegistry>,
+    mut ledger: ResMut<ResourceLedger>,
+    mut agents: Query<(Entity, &mut Agent)>,
+    mut holders: Query<&'static mut ResourceSystem>,
+    stockpiles: Query<&'static Stockpile>,
+    members: Query<&'static Membership>,
+    mut events: EventWriter<ResourceChanged>,
+    mut thefts: EventWriter<TheftAttempt>,
+) {
+    for (agent_entity, mut agent) in agents.iter_mut() {
+        let (stockpile, resource_type, amount, withdraw) = match agent.current_job.clone() {
+            Some(Job::Withdraw { stockpile, resource_type, amount }) => (stockpile, resource_type, amount, true),
+            Some(Job::Deposit { stockpile, resource_type, amount }) => (stockpile, resource_type, amount, false),
+            _ => continue,
+        };
+        agent.current_job = Some(Job::Idle);
+
+        let Some(resource) = registry.id(&resource_type) else {
+            warn!("Agent {} cannot move unknown resource '{}'", agent.name, resource_type);
+            continue;
+        };
+        let (Ok(inventory), Ok(pile)) = (holders.get(agent_entity), holders.get(stockpile)) else {
+            continue;
+        };
+        let (from, to) = if withdraw { (pile, inventory) } else { (inventory, pile) };
+        let amount = amount.min(from.get(resource)).min(to.get_available_space(resource));
+        if !amount.is_positive() {
+            continue;
+        }
+
+        let (from, to) = if withdraw {
+            (Holder::Entity(stockpile), Holder::Entity(agent_entity))
+        } else {
+            (Holder::Entity(agent_entity), Holder::Entity(stockpile))
+        };
+        let mut transaction = ResourceTransaction::new(LedgerReason::Transfer).with_source(agent_entity);
+        transaction.transfer(from, to, resource, amount);
+
+        let access = AccessView { stockpiles: &stockpiles, members: &members };
+        let owner = access.owner_of(stockpile);
+        let mut holders = HolderQuery::new(&mut holders, access);
+        match transaction.commit(clock.tick(), &mut holders, &mut ledger, Some(&mut events)) {
+            Ok(()) => {}
+            Err(TransactionError::AccessDenied { .. }) => {
+                warn!("Agent {} tried to take {} {} from {:?} without access", agent.name, amount, resource_type, stockpile);
+                thefts.send(TheftAttempt {
+                    tick: clock.tick(),
+                    thief: agent_entity,
+                    stockpile,
+                    owner,
+                    resource,
+                    amount,
+                });
+            }
+            Err(err) => debug!("Agent {} could not use stockpile {:?}: {:?}", agent.name, stockpile, err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+
+    #[test]
+    fn test_access_policies() {
+        let mut world = World::new();
+        let household = HouseholdId(1);
+        let owner = world.spawn(Membership { household: Some(household), faction: None }).id();
+        let sibling = world.spawn(Membership { household: Some(household), faction: None }).id();
+        let stranger = world.spawn(Membership::default()).id();
+        let private = world.spawn(Stockpile::new(Owner::Agent(owner), AccessPolicy::Private)).id();
+        let shared = world.spawn(Stockpile::new(Owner::Agent(owner), AccessPolicy::Kin)).id();
+        let granary = world.spawn(Stockpile::new(Owner::Household(household), AccessPolicy::Private)).id();
+        let well = world.spawn(Stockpile::new(Owner::Faction(FactionId(1)), AccessPolicy::Public)).id();
+
+        let allowed = world
+            .run_system_once(move |stockpiles: Query<&'static Stockpile>, members: Query<&'static Membership>| {
+                let access = AccessView { stockpiles: &stockpiles, members: &members };
+                [
+                    access.can_withdraw(owner, private),
+                    access.can_withdraw(sibling, private),
+                    access.can_withdraw(sibling, shared),
+                    access.can_withdraw(stranger, shared),
+                    access.can_withdraw(sibling, granary),
+                    access.can_withdraw(stranger, granary),
+                    access.can_withdraw(stranger, well),
+                    // An agent's own inventory is private to it
+                    access.can_withdraw(stranger, owner),
+                ]
+            })
+            .unwrap();
+
+        assert_eq!(allowed, [true, false, true, false, true, false, true, false]);
+    }
+
+    #[test]
+    fn test_theft_is_refused_and_reported() {
+        let registry = ResourceRegistry::default();
+        let food = registry.id("food").unwrap();
+        let mut world = World::new();
+        world.insert_resource(registry);
+        world.init_resource::<SimClock>();
+        world.init_resource::<ResourceLedger>();
+        world.init_resource::<Events<ResourceChanged>>();
+        world.init_resource::<Events<TheftAttempt>>();
+
+        let owner = world.spawn((Agent::default(), ResourceSystem::new())).id();
+        let mut pile = ResourceSystem::new();
+        pile.add(food, Quantity::from_units(20), None, None);
+        let stockpile = world.spawn((pile, Stockpile::new(Owner::Agent(owner), AccessPolicy::Private))).id();
+        let thief = world.spawn((Agent::default(), ResourceSystem::new())).id();
+
+        let take = Job::Withdraw {
+            stockpile,
+            resource_type: "food".to_string(),
+            amount: Quantity::from_units(5),
+        };
+        world.get_mut::<Agent>(owner).unwrap().current_job = Some(take.clone());
+        world.get_mut::<Agent>(thief).unwrap().current_job = Some(take);
+        world.run_system_once(stockpile_job_system).unwrap();
+
+        assert_eq!(world.get::<ResourceSystem>(owner).unwrap().get(food), Quantity::from_units(5));
+        assert_eq!(world.get::<ResourceSystem>(thief).unwrap().get(food), Quantity::ZERO);
+        assert_eq!(world.get::<ResourceSystem>(stockpile).unwrap().get(food), Quantity::from_units(15));
+
+        let events = world.resource::<Events<TheftAttempt>>();
+        let mut cursor = events.get_cursor();
+        let thefts: Vec<&TheftAttempt> = cursor.read(events).collect();
+        assert_eq!(thefts.len(), 1);
+        assert_eq!(thefts[0].thief, thief);
+        assert_eq!(thefts[0].owner, Some(Owner::Agent(owner)));
+    }
+}
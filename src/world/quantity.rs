@@ -0,0 +1,222 @@
+use std::fmt;
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Neg, Sub, SubAssign};
+
+/// Number of milliunits in one unit of a resource
+pub const MILLIS_PER_UNIT: i64 = 1000;
+
+/// Exact amount of a resource, counted in thousandths of a unit
+///
+/// Stores, flows and the ledger work in quantities rather than `f32` so
+/// that moving resources around never creates or loses anything to
+/// rounding, and economic runs reproduce exactly on any machine.
+/// Arithmetic saturates instead of overflowing. Quantities may be negative
+/// when used as a change in amount; stores never hold less than zero.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Quantity(i64);
+
+impl Quantity {
+    pub const ZERO: Quantity = Quantity(0);
+    #[cfg(test)]
+    pub const MAX: Quantity = Quantity(i64::MAX);
+
+    pub const fn from_millis(millis: i64) -> Self {
+        Self(millis)
+    }
+
+    pub const fn from_units(units: i64) -> Self {
+        Self(units.saturating_mul(MILLIS_PER_UNIT))
+    }
+
+    /// Converts from a float, rounding to the nearest milliunit
+    ///
+    /// NaN becomes zero and out-of-range values saturate.
+    pub fn from_f32(amount: f32) -> Self {
+        // `as` saturates and maps NaN to zero
+        Self((amount as f64 * MILLIS_PER_UNIT as f64).round() as i64)
+    }
+
+    pub const fn millis(self) -> i64 {
+        self.0
+    }
+
+    pub fn to_f32(self) -> f32 {
+        self.to_f64() as f32
+    }
+
+    pub fn to_f64(self) -> f64 {
+        self.0 as f64 / MILLIS_PER_UNIT as f64
+    }
+
+    pub const fn is_zero(self) -> bool {
+        self.0 == 0
+    }
+
+    pub const fn is_positive(self) -> bool {
+        self.0 > 0
+    }
+
+    #[cfg(test)]
+    pub const fn abs(self) -> Self {
+        Self(self.0.saturating_abs())
+    }
+
+    pub const fn saturating_add(self, other: Self) -> Self {
+        Self(self.0.saturating_add(other.0))
+    }
+
+    pub const fn saturating_sub(self, other: Self) -> Self {
+        Self(self.0.saturating_sub(other.0))
+    }
+
+    /// Clamps negative quantities to zero
+    pub fn non_negative(self) -> Self {
+        self.max(Self::ZERO)
+    }
+
+    /// Multiplies by a factor, rounding to the nearest milliunit
+    pub fn mul_f32(self, factor: f32) -> Self {
+        Self((self.0 as f64 * factor as f64).round() as i64)
+    }
+
+    /// Divides by a factor, rounding down; zero for a non-positive divisor
+    pub fn div_f32_floor(self, divisor: f32) -> Self {
+        if divisor > 0.0 {
+            Self((self.0 as f64 / divisor as f64).floor() as i64)
+        } else {
+            Self::ZERO
+        }
+    }
+
+    /// Exact `self * numerator / denominator`, rounding down
+    ///
+    /// Used to share an amount proportionally without creating any.
+    pub fn mul_div(self, numerator: Quantity, denominator: Quantity) -> Self {
+        if denominator.0 == 0 {
+            return Self::ZERO;
+        }
+        let result = self.0 as i128 * numerator.0 as i128 / denominator.0 as i128;
+        Self(result.clamp(i64::MIN as i128, i64::MAX as i128) as i64)
+    }
+}
+
+impl Add for Quantity {
+    type Output = Quantity;
+
+    fn add(self, other: Quantity) -> Quantity {
+        self.saturating_add(other)
+    }
+}
+
+impl AddAssign for Quantity {
+    fn add_assign(&mut self, other: Quantity) {
+        *self = *self + other;
+    }
+}
+
+impl Sub for Quantity {
+    type Output = Quantity;
+
+    fn sub(self, other: Quantity) -> Quantity {
+        self.saturating_sub(other)
+    }
+}
+
+impl SubAssign for Quantity {
+    fn sub_assign(&mut self, other: Quantity) {
+        *self = *self - other;
+    }
+}
+
+impl Neg for Quantity {
+    type Output = Quantity;
+
+    fn neg(self) -> Quantity {
+        Quantity(self.0.saturating_neg())
+    }
+}
+
+impl Sum for Quantity {
+    fn sum<I: Iterator<Item = Quantity>>(iter: I) -> Quantity {
+        iter.fold(Quantity::ZERO, Add::add)
+    }
+}
+
+impl<'a> Sum<&'a Quantity> for Quantity {
+    fn sum<I: Iterator<Item = &'a Quantity>>(iter: I) -> Quantity {
+        iter.copied().sum()
+    }
+}
+
+impl fmt::Display for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let millis = self.0.unsigned_abs();
+        let per_unit = MILLIS_PER_UNIT as u64;
+        write!(f, "{}{}.{:03}", sign, millis / per_unit, millis % per_unit)
+    }
+}
+
+/// Fraction of a milliunit carried over between ticks
+///
+/// Rates applied every tick often come to a fraction of a milliunit, which
+/// rounding each tick would either inflate or drop entirely. Accruing the
+/// fractions here releases them as whole milliunits once they add up, so
+/// the total over many ticks matches the rate.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Remainder(f64);
+
+impl Remainder {
+    /// Adds an amount in units and takes out the whole milliunits accrued
+    ///
+    /// Non-finite amounts accrue nothing.
+    pub fn accrue(&mut self, units: f64) -> Quantity {
+        let total = self.0 + units * MILLIS_PER_UNIT as f64;
+        if !total.is_finite() {
+            return Quantity::ZERO;
+        }
+        let whole = total.floor();
+        self.0 = total - whole;
+        // `as` saturates for amounts beyond the range of a quantity
+        Quantity(whole as i64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conversion_and_saturation() {
+        assert_eq!(Quantity::from_f32(1.2345), Quantity::from_millis(1235));
+        assert_eq!(Quantity::from_f32(f32::NAN), Quantity::ZERO);
+        assert_eq!(Quantity::from_f32(f32::INFINITY), Quantity::MAX);
+        assert_eq!(Quantity::MAX + Quantity::from_units(1), Quantity::MAX);
+        assert_eq!(Quantity::from_units(-3).to_string(), "-3.000");
+        assert_eq!(Quantity::from_millis(2050).to_string(), "2.050");
+    }
+
+    #[test]
+    fn test_proportional_shares_never_exceed_the_total() {
+        // Splitting 10 units between requests of 1, 1 and 1
+        let total = Quantity::from_units(10);
+        let request = Quantity::from_units(1);
+        let requested = Quantity::from_units(3);
+        let shares: Vec<Quantity> = (0..3).map(|_| total.mul_div(request, requested)).collect();
+        assert_eq!(shares[0], Quantity::from_millis(3333));
+        assert!(shares.iter().sum::<Quantity>() <= total);
+    }
+
+    #[test]
+    fn test_remainders_add_up_across_ticks() {
+        // One unit per second at 60 ticks per second is 16.67 millis a tick
+        let mut remainder = Remainder::default();
+        let total: Quantity = (0..600).map(|_| remainder.accrue(1.0 / 60.0)).sum();
+        assert_eq!(total, Quantity::from_units(10));
+
+        // Far less than a milliunit a tick still shows up eventually
+        let mut remainder = Remainder::default();
+        let total: Quantity = (0..1000).map(|_| remainder.accrue(0.0001)).sum();
+        assert!((total.millis() - 100).abs() <= 1);
+    }
+}
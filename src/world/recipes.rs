@@ -0,0 +1,504 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use crate::agents::agent::Agent;
+use crate::agents::job::Job;
+use crate::world::resources::{ResourceChanged, ResourceId, ResourceRegistry, ResourceSystem};
+use crate::world::storage::{Storage, StorageTiers};
+use crate::world::structure::Structure;
+use crate::world::items::ItemStack;
+use crate::world::quantity::Quantity;
+use crate::world::ledger::{Holder, LedgerReason, ResourceLedger, ResourceTransaction, SingleHolder};
+use crate::world::ownership::{AccessView, Membership, Stockpile};
+use crate::engine::time::SimClock;
+
+/// Path of the recipe definitions loaded at startup
+pub const RECIPE_DEFINITIONS_PATH: &str = "assets/data/recipes.ron";
+
+/// Built-in copy of the recipe definitions, used when the file can't be read
+const DEFAULT_RECIPE_DEFINITIONS: &str = include_str!("../../assets/data/recipes.ron");
+
+/// Memory key holding the name of the last recipe an agent finished
+pub const LAST_RECIPE_MEMORY: &str = "last_recipe";
+
+/// Event fired when an agent finishes a recipe
+#[derive(Event, Debug)]
+pub struct RecipeCompleted {
+    pub agent: Entity,
+    pub recipe_id: String,
+}
+
+/// Minimum skill level needed to execute a recipe
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct SkillRequirement {
+    pub name: String,
+    pub level: f32,
+}
+
+/// Recipe as written in data files, naming resources by registry key
+#[derive(Debug, Clone, Deserialize)]
+pub struct RecipeDefinition {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub inputs: Vec<(String, f32)>,
+    #[serde(default)]
+    pub outputs: Vec<(String, f32)>,
+    pub duration: f32,
+    #[serde(default)]
+    pub tool: Option<String>,
+    #[serde(default)]
+    pub building: Option<String>,
+    #[serde(default)]
+    pub skill: Option<SkillRequirement>,
+    #[serde(default)]
+    pub structure: Option<String>,
+    #[serde(default)]
+    pub storage: Option<String>,
+}
+
+/// Recipe with its resources resolved against the registry
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recipe {
+    pub id: String,
+    pub name: String,
+    pub inputs: Vec<(ResourceId, Quantity)>,
+    pub outputs: Vec<(ResourceId, Quantity)>,
+    /// Seconds of work needed to finish
+    pub duration: f32,
+    /// Resource that must be held but isn't consumed
+    pub tool: Option<ResourceId>,
+    /// Structure kind that must be within perception range
+    pub building: Option<String>,
+    pub skill: Option<SkillRequirement>,
+    /// Structure placed when the recipe finishes
+    pub structure: Option<String>,
+    /// Storage tier a storage is upgraded to when the recipe finishes
+    pub storage: Option<String>,
+}
+
+impl Recipe {
+    /// Resolves a recipe definition against the resource registry
+    pub fn resolve(definition: RecipeDefinition, registry: &ResourceRegistry) -> Result<Self, String> {
+        let lookup = |key: &str| {
+            registry
+                .id(key)
+                .ok_or_else(|| format!("recipe '{}' uses unknown resource '{}'", definition.id, key))
+        };
+        let resolve_list = |list: &[(String, f32)]| {
+            list.iter()
+                .map(|(key, amount)| lookup(key).map(|id| (id, Quantity::from_f32(*amount))))
+                .collect::<Result<Vec<_>, String>>()
+        };
+
+        let inputs = resolve_list(&definition.inputs)?;
+        let outputs = resolve_list(&definition.outputs)?;
+        let tool = definition.tool.as_deref().map(lookup).transpose()?;
+
+        Ok(Self {
+            id: definition.id,
+            name: definition.name,
+            inputs,
+            outputs,
+            duration: definition.duration,
+            tool,
+            building: definition.building,
+            skill: definition.skill,
+            structure: definition.structure,
+            storage: definition.storage,
+        })
+    }
+
+    /// Checks the tool, building and skill requirements for an agent
+    ///
+    /// Returns a description of the first unmet requirement.
+    pub fn check_requirements<'a>(
+        &self,
+        agent: &Agent,
+        inventory: &ResourceSystem,
+        mut nearby_structures: impl Iterator<Item = &'a str>,
+    ) -> Result<(), String> {
+        if let Some(tool) = self.tool {
+            if !inventory.get(tool).is_positive() {
+                return Err("missing tool".to_string());
+            }
+        }
+        if let Some(building) = &self.building {
+            if !nearby_structures.any(|kind| kind == building) {
+                return Err(format!("no {} nearby", building));
+            }
+        }
+        if let Some(skill) = &self.skill {
+            let level = agent.skills.get(&skill.name).copied().unwrap_or(0.0);
+            if level < skill.level {
+                return Err(format!("{} skill {:.1} below {:.1}", skill.name, level, skill.level));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns whether the inventory holds every input
+    pub fn has_inputs(&self, inventory: &ResourceSystem) -> bool {
+        self.inputs.iter().all(|(resource, amount)| inventory.get(*resource) >= *amount)
+    }
+
+    /// Average quality of the inputs the inventory would consume, weighted
+    /// by amount
+    pub fn input_quality(&self, inventory: &ResourceSystem) -> f32 {
+        let (weighted, total) = self.inputs.iter().fold((0.0, 0.0), |(weighted, total), (resource, amount)| {
+            let quality = inventory.quality(*resource).unwrap_or(1.0);
+            (weighted + quality * amount.to_f32(), total + amount.to_f32())
+        });
+        if total > 0.0 { weighted / total } else { 1.0 }
+    }
+
+    /// Builds the transaction consuming the inputs and producing the outputs
+    ///
+    /// Outputs are produced at the given quality.
+    pub fn transaction(&self, holder: Holder, quality: f32) -> ResourceTransaction {
+        let mut transaction = ResourceTransaction::new(LedgerReason::Recipe(self.id.clone()));
+        for (resource, amount) in &self.inputs {
+            transaction.debit(holder, *resource, *amount);
+        }
+        for (resource, amount) in &self.outputs {
+            transaction.credit_stack(holder, *resource, ItemStack::new(*amount).with_quality(quality));
+        }
+        transaction
+    }
+}
+
+/// Resource holding all known recipes by ID
+///
+/// At most one recipe places each structure and upgrades to each storage
+/// tier, so `Job::Build` and `Job::UpgradeStorage` always pick the same one.
+#[derive(Debug, Clone, Default, Resource)]
+pub struct RecipeBook {
+    recipes: BTreeMap<String, Recipe>,
+}
+
+impl RecipeBook {
+    /// Parses a RON list of recipe definitions
+    pub fn from_ron_str(source: &str, registry: &ResourceRegistry) -> Result<Self, String> {
+        let definitions: Vec<RecipeDefinition> = ron::from_str(source)
+            .map_err(|err| format!("invalid recipe definitions: {}", err))?;
+
+        let mut book = Self::default();
+        for definition in definitions {
+            let recipe = Recipe::resolve(definition, registry)?;
+            if book.recipes.contains_key(&recipe.id) {
+                return Err(format!("duplicate recipe id '{}'", recipe.id));
+            }
+            if let Some(other) = recipe.structure.as_deref().and_then(|kind| book.for_structure(kind)) {
+                return Err(format!("recipes '{}' and '{}' both build {}", other.id, recipe.id, other.structure.as_deref().unwrap_or_default()));
+            }
+            if let Some(other) = recipe.storage.as_deref().and_then(|tier| book.for_storage(tier)) {
+                return Err(format!("recipes '{}' and '{}' both upgrade to {}", other.id, recipe.id, other.storage.as_deref().unwrap_or_default()));
+            }
+            book.recipes.insert(recipe.id.clone(), recipe);
+        }
+        Ok(book)
+    }
+
+    /// Loads recipe definitions from a RON file
+    pub fn load(path: &str, registry: &ResourceRegistry) -> Result<Self, String> {
+        let source = std::fs::read_to_string(path)
+            .map_err(|err| format!("failed to read {}: {}", path, err))?;
+        Self::from_ron_str(&source, registry)
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Recipe> {
+        self.recipes.get(id)
+    }
+
+    /// Finds the recipe that places the given structure
+    pub fn for_structure(&self, kind: &str) -> Option<&Recipe> {
+        self.iter().find(|recipe| recipe.structure.as_deref() == Some(kind))
+    }
+
+    /// Finds the recipe that upgrades a storage to the given tier
+    pub fn for_storage(&self, tier: &str) -> Option<&Recipe> {
+        self.iter().find(|recipe| recipe.storage.as_deref() == Some(tier))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Recipe> {
+        self.recipes.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.recipes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.recipes.is_empty()
+    }
+}
+
+/// Component tracking an agent's progress on its current recipe
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct CraftingProgress {
+    pub recipe_id: String,
+    pub elapsed: f32,
+}
+
+/// Loads recipes from the definitions file, falling back to the built-in set
+///
+/// Runs after the resource registry is loaded so resource keys resolve
+/// against the final registry.
+pub fn load_recipe_book(
+    registry: Res<ResourceRegistry>,
+    mut book: ResMut<RecipeBook>,
+) {
+    let loaded = RecipeBook::load(RECIPE_DEFINITIONS_PATH, &registry).or_else(|err| {
+        warn!("Using built-in recipe definitions: {}", err);
+        RecipeBook::from_ron_str(DEFAULT_RECIPE_DEFINITIONS, &registry)
+    });
+
+    match loaded {
+        Ok(loaded) => {
+            if loaded.is_empty() {
+                warn!("No recipes defined; agents can't produce or build anything");
+            } else {
+                info!("Loaded {} recipes", loaded.len());
+            }
+            *book = loaded;
+        }
+        Err(err) => {
+            error!("No usable recipe definitions: {}", err);
+            *book = RecipeBook::default();
+        }
+    }
+}
+
+/// System that executes `Job::Produce`, `Job::Build` and
+/// `Job::UpgradeStorage` for agents
+///
+/// Work accumulates in `CraftingProgress`. Once the recipe's duration has
+/// passed, inputs are consumed and outputs produced in a single
+/// transaction, structures are placed at the agent's position and
+/// storages are raised to the next tier. Agents may only upgrade storages
+/// within their perception range that they are allowed to take from.
+#[allow(clippy::too_many_arguments)]
+pub fn production_job_system(
+    mut commands: Commands,
+    clock: Res<SimClock>,
+    book: Res<RecipeBook>,
+    tiers: Res<StorageTiers>,
+    mut ledger: ResMut<ResourceLedger>,
+    mut agents: Query<(Entity, &mut Agent, &mut ResourceSystem, Option<&mut CraftingProgress>)>,
+    structures: Query<(&Structure, &Transform)>,
+    mut storages: Query<(&mut Storage, Option<&Transform>)>,
+    stockpiles: Query<&'static Stockpile>,
+    members: Query<&'static Membership>,
+    mut events: EventWriter<ResourceChanged>,
+    mut completed: EventWriter<RecipeCompleted>,
+) {
+    let delta = clock.tick_length as f32;
+    let access = AccessView { stockpiles: &stockpiles, members: &members };
+
+    for (entity, mut agent, mut inventory, progress) in agents.iter_mut() {
+        if let Some(Job::UpgradeStorage { stockpile }) = agent.current_job {
+            let in_reach = storages.get(stockpile).ok().and_then(|(_, transform)| transform).is_some_and(|transform| {
+                transform.translation.truncate().distance(agent.position) <= agent.effective_perception_range()
+            });
+            if !in_reach || !access.can_withdraw(entity, stockpile) {
+                warn!("Agent {} cannot upgrade storage {:?} out of reach or without access", agent.name, stockpile);
+                agent.current_job = Some(Job::Idle);
+                if progress.is_some() {
+                    commands.entity(entity).remove::<CraftingProgress>();
+                }
+                continue;
+            }
+        }
+
+        let recipe = match &agent.current_job {
+            Some(Job::Produce { recipe_id }) => book.get(recipe_id),
+            Some(Job::Build { structure_type }) => book.for_structure(structure_type),
+            Some(Job::UpgradeStorage { stockpile }) => storages
+                .get(*stockpile)
+                .ok()
+                .and_then(|(storage, _)| tiers.next(&storage.tier))
+                .and_then(|next| book.for_storage(&next.id)),
+            _ => {
+                // Drop progress left over from an abandoned job
+                if progress.is_some() {
+                    commands.entity(entity).remove::<CraftingProgress>();
+                }
+                continue;
+            }
+        };
+
+        let Some(recipe) = recipe else {
+            warn!("Agent {} has no recipe for job {:?}", agent.name, agent.current_job);
+            agent.current_job = Some(Job::Idle);
+            continue;
+        };
+
+        let nearby = structures
+            .iter()
+            .filter(|(_, transform)| {
+                transform.translation.truncate().distance(agent.position) <= agent.effective_perception_range()
+            })
+            .map(|(structure, _)| structure.kind.as_str());
+
+        let ready = recipe
+            .check_requirements(&agent, &inventory, nearby)
+            .and_then(|_| if recipe.has_inputs(&inventory) { Ok(()) } else { Err("missing inputs".to_string()) });
+        if let Err(reason) = ready {
+            debug!("Agent {} cannot {}: {}", agent.name, recipe.id, reason);
+            agent.current_job = Some(Job::Idle);
+            commands.entity(entity).remove::<CraftingProgress>();
+            continue;
+        }
+
+        let elapsed = match &progress {
+            Some(progress) if progress.recipe_id == recipe.id => progress.elapsed + delta,
+            _ => delta,
+        };
+
+        if elapsed < recipe.duration {
+            match progress {
+                Some(mut progress) => {
+                    progress.recipe_id.clone_from(&recipe.id);
+                    progress.elapsed = elapsed;
+                }
+                None => {
+                    commands.entity(entity).insert(CraftingProgress {
+                        recipe_id: recipe.id.clone(),
+                        elapsed,
+                    });
+                }
+            }
+            continue;
+        }
+
+        // Outputs are only as good as what went into them
+        let quality = recipe.input_quality(&inventory);
+        let transaction = recipe.transaction(Holder::Entity(entity), quality).with_source(entity);
+        let mut holders = SingleHolder { entity, store: &mut inventory.store };
+        let result = transaction.commit(clock.tick(), &mut holders, &mut ledger, Some(&mut events));
+        if result.is_ok() {
+            if let Some(kind) = &recipe.structure {
+                commands.spawn((
+                    Structure::new(kind.clone()),
+                    Transform::from_translation(agent.position.extend(0.0)),
+                ));
+            }
+            if let (Some(tier), Some(Job::UpgradeStorage { stockpile })) = (&recipe.storage, &agent.current_job) {
+                if let Ok((mut storage, _)) = storages.get_mut(*stockpile) {
+                    storage.tier.clone_from(tier);
+                }
+            }
+            completed.send(RecipeCompleted {
+                agent: entity,
+                recipe_id: recipe.id.clone(),
+            });
+            info!("Agent {} completed recipe {}", agent.name, recipe.name);
+        } else {
+            debug!("Agent {} could not finish {}: {:?}", agent.name, recipe.id, result);
+        }
+
+        agent.current_job = Some(Job::Idle);
+        commands.entity(entity).remove::<CraftingProgress>();
+    }
+}
+
+/// System where agents remember the last recipe they finished
+pub fn remember_completed_recipes(
+    book: Res<RecipeBook>,
+    mut completed: EventReader<RecipeCompleted>,
+    mut agents: Query<&mut Agent>,
+) {
+    for event in completed.read() {
+        let Ok(mut agent) = agents.get_mut(event.agent) else {
+            continue;
+        };
+        let name = book.get(&event.recipe_id).map_or(event.recipe_id.as_str(), |recipe| recipe.name.as_str());
+        agent.memory.insert(LAST_RECIPE_MEMORY.to_string(), name.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+
+    #[test]
+    fn test_builtin_recipes_resolve() {
+        let registry = ResourceRegistry::default();
+        let book = RecipeBook::from_ron_str(DEFAULT_RECIPE_DEFINITIONS, &registry).unwrap();
+
+        let burn = book.get("burn_wood").unwrap();
+        assert_eq!(burn.inputs, vec![(registry.id("wood").unwrap(), Quantity::from_units(2))]);
+        assert_eq!(book.for_structure("shelter").unwrap().id, "build_shelter");
+
+        // Recipes referring to unregistered resources are rejected
+        let unknown = r#"[(id: "x", name: "X", inputs: [("unobtainium", 1.0)], duration: 1.0)]"#;
+        assert!(RecipeBook::from_ron_str(unknown, &registry).is_err());
+
+        // Two ways to build the same structure would make `Job::Build` ambiguous
+        let rival = r#"[
+            (id: "hut", name: "Hut", duration: 1.0, structure: Some("shelter")),
+            (id: "tent", name: "Tent", duration: 1.0, structure: Some("shelter")),
+        ]"#;
+        assert!(RecipeBook::from_ron_str(rival, &registry).unwrap_err().contains("both build shelter"));
+    }
+
+    #[test]
+    fn test_recipe_transaction_is_atomic() {
+        let registry = ResourceRegistry::default();
+        let book = RecipeBook::from_ron_str(DEFAULT_RECIPE_DEFINITIONS, &registry).unwrap();
+        let recipe = book.get("brew_medicine").unwrap();
+        let food = registry.id("food").unwrap();
+        let water = registry.id("water").unwrap();
+        let medicine = registry.id("medicine").unwrap();
+
+        let agent = Entity::from_raw(1);
+        let mut inventory = ResourceSystem::new();
+        let mut ledger = ResourceLedger::default();
+        inventory.insert_stack(food, ItemStack::new(Quantity::from_units(2)).with_quality(0.4));
+
+        let brew = |inventory: &mut ResourceSystem, ledger: &mut ResourceLedger| {
+            let quality = recipe.input_quality(inventory);
+            let mut holders = SingleHolder { entity: agent, store: &mut inventory.store };
+            recipe.transaction(Holder::Entity(agent), quality).with_source(agent).commit(0, &mut holders, ledger, None)
+        };
+
+        // Missing water: nothing is consumed
+        assert!(brew(&mut inventory, &mut ledger).is_err());
+        assert_eq!(inventory.get(food), Quantity::from_units(2));
+
+        // No room for the output: nothing is consumed
+        inventory.add(water, Quantity::from_units(1), None, None);
+        inventory.set_capacity(medicine, Quantity::from_millis(500));
+        assert!(brew(&mut inventory, &mut ledger).is_err());
+        assert_eq!(inventory.get(water), Quantity::from_units(1));
+        assert!(ledger.is_empty());
+
+        inventory.set_capacity(medicine, Quantity::from_units(10));
+        assert!(brew(&mut inventory, &mut ledger).is_ok());
+        assert_eq!(inventory.get(food), Quantity::ZERO);
+        assert_eq!(inventory.get(water), Quantity::ZERO);
+        assert_eq!(inventory.get(medicine), Quantity::from_units(1));
+        // Two parts stale food and one part fresh water
+        let stack = &inventory.stacks(medicine)[0];
+        assert!((stack.quality - 0.6).abs() < 1e-5);
+        assert_eq!(stack.producer, Some(agent));
+        assert_eq!(ledger.sources_of(Holder::Entity(agent), medicine)[0].1, LedgerReason::Recipe("brew_medicine".to_string()));
+    }
+
+    #[test]
+    fn test_agents_remember_the_recipe_they_finished() {
+        let registry = ResourceRegistry::default();
+        let book = RecipeBook::from_ron_str(DEFAULT_RECIPE_DEFINITIONS, &registry).unwrap();
+        let name = book.get("burn_wood").unwrap().name.clone();
+
+        let mut world = World::new();
+        world.insert_resource(book);
+        world.init_resource::<Events<RecipeCompleted>>();
+        let agent = world.spawn(Agent::default()).id();
+        world.send_event(RecipeCompleted { agent, recipe_id: "burn_wood".to_string() });
+        world.run_system_once(remember_completed_recipes).unwrap();
+
+        assert_eq!(world.get::<Agent>(agent).unwrap().memory[LAST_RECIPE_MEMORY], name);
+    }
+}
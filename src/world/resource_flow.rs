@@ -0,0 +1,484 @@
+use bevy::prelude::*;
+use std::collections::HashMap;
+use crate::agents::agent::Agent;
+use crate::agents::job::Job;
+use crate::engine::time::SimClock;
+use crate::world::ledger::{Holder, HolderQuery, LedgerReason, ResourceLedger, ResourceTransaction, TransactionError};
+use crate::world::ownership::{AccessView, Membership, Stockpile};
+use crate::world::quantity::Quantity;
+use crate::world::resources::{ResourceChanged, ResourceId, ResourceRegistry, ResourceSystem};
+
+/// Units per second an agent carries in fair weather
+const HAUL_RATE: f32 = 2.0;
+
+/// Identifier of an edge in the `ResourceNetwork`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FlowId(pub u64);
+
+/// What a flow edge represents
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FlowKind {
+    /// One-off transfer of a fixed amount
+    // Only built by `ResourceFlow::transfer`, see there
+    #[allow(dead_code)]
+    Transfer,
+    // Laid by world generation once canals are placed; only tests build them so far
+    #[allow(dead_code)]
+    Canal,
+    Caravan,
+}
+
+/// Why an edge moved less than its requested rate last tick
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bottleneck {
+    /// The edge capacity is below the requested rate
+    Capacity,
+    /// The source didn't hold enough for all of its outgoing edges
+    SourceEmpty,
+    /// The destination didn't have room for all of its incoming edges
+    DestinationFull,
+}
+
+/// A directed edge moving one resource between two holders
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResourceFlow {
+    pub from: Entity,
+    pub to: Entity,
+    pub resource: ResourceId,
+    pub kind: FlowKind,
+    /// Units per second the edge tries to move
+    pub rate: f32,
+    /// Maximum units per second the edge can carry
+    pub capacity: f32,
+    /// Fraction of the moved amount lost on the way, 0.0 to 1.0
+    pub loss: f32,
+    /// Amount left to move before the edge is removed, `None` for permanent edges
+    pub remaining: Option<Quantity>,
+    /// Entity running the edge, which must be allowed to take from `from`
+    pub operator: Entity,
+}
+
+impl ResourceFlow {
+    /// Creates a permanent edge with no loss and unlimited capacity, run by
+    /// its source
+    pub fn new(from: Entity, to: Entity, resource: ResourceId, kind: FlowKind, rate: f32) -> Self {
+        Self {
+            from,
+            to,
+            resource,
+            kind,
+            rate,
+            capacity: f32::INFINITY,
+            loss: 0.0,
+            remaining: None,
+            operator: from,
+        }
+    }
+
+    /// Creates an edge that moves `amount` as fast as possible and then disappears
+    // Kept for instant moves between holders; jobs still use transactions for those
+    #[allow(dead_code)]
+    pub fn transfer(from: Entity, to: Entity, resource: ResourceId, amount: Quantity) -> Self {
+        Self {
+            remaining: Some(amount),
+            ..Self::new(from, to, resource, FlowKind::Transfer, f32::INFINITY)
+        }
+    }
+
+    // Edge limits for canals, see `FlowKind::Canal`
+    #[allow(dead_code)]
+    pub fn with_capacity(mut self, capacity: f32) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn with_loss(mut self, loss: f32) -> Self {
+        self.loss = loss.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn with_limit(mut self, amount: Quantity) -> Self {
+        self.remaining = Some(amount);
+        self
+    }
+
+    /// Runs the edge on behalf of another entity, e.g. the owner of the
+    /// stockpile it drains
+    pub fn with_operator(mut self, operator: Entity) -> Self {
+        self.operator = operator;
+        self
+    }
+
+    /// Amount the edge wants to take from its source over `delta` seconds
+    fn requested(&self, delta: f32) -> Quantity {
+        let per_tick = self.rate.min(self.capacity) * delta;
+        let requested = match (per_tick.is_finite(), self.remaining) {
+            (true, None) => Quantity::from_f32(per_tick),
+            (true, Some(remaining)) => Quantity::from_f32(per_tick).min(remaining),
+            (false, Some(remaining)) => remaining,
+            // An unlimited edge still needs a finite amount
+            (false, None) => Quantity::ZERO,
+        };
+        requested.non_negative()
+    }
+}
+
+/// Result of the last solve for a single edge
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct FlowStats {
+    /// Units taken from the source
+    pub sent: Quantity,
+    /// Units that arrived at the destination
+    pub delivered: Quantity,
+    /// Units lost on the way
+    pub lost: Quantity,
+    pub bottleneck: Option<Bottleneck>,
+}
+
+/// Resource holding the graph of resource flows between holders
+///
+/// Nodes are entities with a `ResourceSystem`. Each tick every edge moves
+/// up to `min(rate, capacity) * delta` from its source; when a source or
+/// destination can't satisfy all of its edges, the amount is shared
+/// between them in proportion to what they requested. Shares are rounded
+/// down, so a solve never moves more than a holder has or can store.
+#[derive(Debug, Default, Resource)]
+pub struct ResourceNetwork {
+    edges: HashMap<FlowId, ResourceFlow>,
+    stats: HashMap<FlowId, FlowStats>,
+    next_id: u64,
+}
+
+impl ResourceNetwork {
+    pub fn add_edge(&mut self, flow: ResourceFlow) -> FlowId {
+        let id = FlowId(self.next_id);
+        self.next_id += 1;
+        self.edges.insert(id, flow);
+        id
+    }
+
+    pub fn remove_edge(&mut self, id: FlowId) -> Option<ResourceFlow> {
+        self.stats.remove(&id);
+        self.edges.remove(&id)
+    }
+
+    pub fn get_mut(&mut self, id: FlowId) -> Option<&mut ResourceFlow> {
+        self.edges.get_mut(&id)
+    }
+
+    /// Returns what an edge moved during the last solve
+    // Read by tests; no system reacts to bottlenecks yet
+    #[allow(dead_code)]
+    pub fn stats(&self, id: FlowId) -> Option<&FlowStats> {
+        self.stats.get(&id)
+    }
+
+    /// Iterates over all edges leaving a holder
+    pub fn edges_from(&self, holder: Entity) -> impl Iterator<Item = (FlowId, &ResourceFlow)> {
+        self.edges
+            .iter()
+            .filter(move |(_, flow)| flow.from == holder)
+            .map(|(id, flow)| (*id, flow))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.edges.is_empty()
+    }
+
+    /// Moves resources along every edge for `delta` seconds
+    ///
+    /// Amounts are planned from the holders' state at the start of the
+    /// solve, so the result doesn't depend on edge order. Edges whose
+    /// holders no longer exist are dropped, as are finished transfers and
+    /// edges whose operator may not take from the source. Delivered amounts
+    /// are recorded in the ledger as `Flow`, losses as `FlowLoss`.
+    pub fn solve<'a>(
+        &mut self,
+        delta: f32,
+        tick: u64,
+        holders: &'a mut Query<&'static mut ResourceSystem>,
+        access: AccessView<'a, 'a, 'a>,
+        ledger: &mut ResourceLedger,
+        mut event_writer: Option<&mut EventWriter<ResourceChanged>>,
+    ) {
+        self.edges.retain(|_, flow| holders.contains(flow.from) && holders.contains(flow.to));
+        self.stats.clear();
+
+        // Deterministic order for applying the planned amounts
+        let mut ids: Vec<FlowId> = self.edges.keys().copied().collect();
+        ids.sort();
+
+        // Requested amounts, limited by edge capacity
+        let mut planned: HashMap<FlowId, FlowStats> = HashMap::new();
+        let mut outgoing: HashMap<(Entity, ResourceId), Quantity> = HashMap::new();
+        for id in &ids {
+            let flow = &self.edges[id];
+            let requested = flow.requested(delta);
+            *outgoing.entry((flow.from, flow.resource)).or_default() += requested;
+            planned.insert(*id, FlowStats {
+                sent: requested,
+                bottleneck: (flow.capacity < flow.rate).then_some(Bottleneck::Capacity),
+                ..default()
+            });
+        }
+
+        // Share each source between its outgoing edges
+        for id in &ids {
+            let flow = &self.edges[id];
+            let total = outgoing[&(flow.from, flow.resource)];
+            let available = holders.get(flow.from).map(|system| system.get(flow.resource)).unwrap_or_default();
+            if total > available {
+                let stats = planned.get_mut(id).unwrap();
+                stats.sent = stats.sent.mul_div(available, total);
+                stats.bottleneck = Some(Bottleneck::SourceEmpty);
+            }
+        }
+
+        // Share each destination's free space between its incoming edges
+        let mut incoming: HashMap<(Entity, ResourceId), Quantity> = HashMap::new();
+        for id in &ids {
+            let flow = &self.edges[id];
+            let stats = planned.get_mut(id).unwrap();
+            stats.delivered = stats.sent - stats.sent.mul_f32(flow.loss);
+            *incoming.entry((flow.to, flow.resource)).or_default() += stats.delivered;
+        }
+        for id in &ids {
+            let flow = &self.edges[id];
+            let total = incoming[&(flow.to, flow.resource)];
+            let space = holders.get(flow.to).map(|system| system.get_available_space(flow.resource)).unwrap_or_default();
+            if total > space {
+                let stats = planned.get_mut(id).unwrap();
+                stats.sent = stats.sent.mul_div(space, total);
+                stats.delivered = stats.delivered.mul_div(space, total);
+                stats.bottleneck = Some(Bottleneck::DestinationFull);
+            }
+        }
+
+        // Apply the plan
+        let mut holders = HolderQuery::new(holders, access);
+        let mut denied = Vec::new();
+        for id in ids {
+            let flow = self.edges.get_mut(&id).unwrap();
+            let mut stats = planned[&id];
+
+            if stats.sent.is_positive() {
+                stats.lost = stats.sent - stats.delivered;
+
+                let from = Holder::Entity(flow.from);
+                let mut delivery = ResourceTransaction::new(LedgerReason::Flow).with_source(flow.operator);
+                delivery
+                    .transfer(from, Holder::Entity(flow.to), flow.resource, stats.delivered)
+                    .debit_as(LedgerReason::FlowLoss, from, flow.resource, stats.lost);
+
+                if let Err(err) = delivery.commit(tick, &mut holders, ledger, event_writer.as_deref_mut()) {
+                    warn!("Flow {:?} failed: {:?}", id, err);
+                    if matches!(err, TransactionError::AccessDenied { .. }) {
+                        denied.push(id);
+                    }
+                    stats = FlowStats::default();
+                }
+            }
+
+            if let Some(remaining) = flow.remaining.as_mut() {
+                *remaining -= stats.sent;
+            }
+            self.stats.insert(id, stats);
+        }
+
+        for id in denied {
+            self.remove_edge(id);
+        }
+        self.edges.retain(|_, flow| flow.remaining.is_none_or(|remaining| remaining.is_positive()));
+    }
+}
+
+/// System that solves the resource network once per tick
+pub fn solve_resource_flows(
+    clock: Res<SimClock>,
+    mut network: ResMut<ResourceNetwork>,
+    mut ledger: ResMut<ResourceLedger>,
+    mut holders: Query<&'static mut ResourceSystem>,
+    stockpiles: Query<&'static Stockpile>,
+    members: Query<&'static Membership>,
+    mut events: EventWriter<ResourceChanged>,
+) {
+    if network.is_empty() {
+        return;
+    }
+    let access = AccessView { stockpiles: &stockpiles, members: &members };
+    network.solve(clock.tick_length as f32, clock.tick(), &mut holders, access, &mut ledger, Some(&mut events));
+}
+
+/// System that turns `Job::Haul` into a caravan edge run by the agent
+///
+/// The edge carries the amount at the agent's `HAUL_RATE`, slowed by the
+/// weather it set out in, and disappears once everything arrived. Another
+/// haul of the same resource along the same route tops up the caravan
+/// already on its way. Hauls from holders the agent may not take from are
+/// dropped by the solver.
+pub fn haul_job_system(
+    registry: Res<ResourceRegistry>,
+    mut network: ResMut<ResourceNetwork>,
+    mut agents: Query<(Entity, &mut Agent)>,
+) {
+    for (entity, mut agent) in agents.iter_mut() {
+        let Some(Job::Haul { from, to, resource_type, amount }) = agent.current_job.clone() else {
+            continue;
+        };
+        agent.current_job = Some(Job::Idle);
+
+        let Some(resource) = registry.id(&resource_type) else {
+            warn!("Agent {} cannot haul unknown resource '{}'", agent.name, resource_type);
+            continue;
+        };
+
+        let running = network
+            .edges_from(from)
+            .find(|(_, flow)| {
+                flow.kind == FlowKind::Caravan && flow.to == to && flow.resource == resource && flow.operator == entity
+            })
+            .map(|(id, _)| id);
+        if let Some(flow) = running.and_then(|id| network.get_mut(id)) {
+            flow.remaining = Some(flow.remaining.unwrap_or_default() + amount);
+            continue;
+        }
+
+        let rate = HAUL_RATE * agent.weather.movement;
+        network.add_edge(
+            ResourceFlow::new(from, to, resource, FlowKind::Caravan, rate)
+                .with_limit(amount)
+                .with_operator(entity),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+    use crate::world::ownership::{AccessPolicy, Owner};
+
+    fn holder(world: &mut World, resource: ResourceId, units: i64) -> Entity {
+        let mut system = ResourceSystem::new();
+        system.add(resource, Quantity::from_units(units), None, None);
+        world.spawn(system).id()
+    }
+
+    fn solve(world: &mut World, delta: f32) {
+        world
+            .run_system_once(move |mut network: ResMut<ResourceNetwork>,
+                                   mut ledger: ResMut<ResourceLedger>,
+                                   mut holders: Query<&'static mut ResourceSystem>,
+                                   stockpiles: Query<&'static Stockpile>,
+                                   members: Query<&'static Membership>| {
+                let access = AccessView { stockpiles: &stockpiles, members: &members };
+                network.solve(delta, 0, &mut holders, access, &mut ledger, None);
+            })
+            .unwrap();
+    }
+
+    fn amount(world: &World, entity: Entity, resource: ResourceId) -> Quantity {
+        world.get::<ResourceSystem>(entity).unwrap().get(resource)
+    }
+
+    #[test]
+    fn test_flow_with_capacity_and_loss() {
+        let water = ResourceRegistry::default().id("water").unwrap();
+        let mut world = World::new();
+        world.init_resource::<ResourceNetwork>();
+        world.init_resource::<ResourceLedger>();
+        let well = holder(&mut world, water, 100);
+        let village = holder(&mut world, water, 0);
+
+        let canal = world.resource_mut::<ResourceNetwork>().add_edge(
+            ResourceFlow::new(well, village, water, FlowKind::Canal, 20.0)
+                .with_capacity(10.0)
+                .with_loss(0.5),
+        );
+        solve(&mut world, 1.0);
+
+        assert_eq!(amount(&world, well, water), Quantity::from_units(90));
+        assert_eq!(amount(&world, village, water), Quantity::from_units(5));
+        let stats = *world.resource::<ResourceNetwork>().stats(canal).unwrap();
+        assert_eq!(stats.lost, Quantity::from_units(5));
+        assert_eq!(stats.bottleneck, Some(Bottleneck::Capacity));
+        let ledger = world.resource::<ResourceLedger>();
+        assert_eq!(ledger.sources_of(Holder::Entity(village), water), vec![(Holder::Entity(well), LedgerReason::Flow, Quantity::from_units(5))]);
+        assert_eq!(ledger.sent_by(Holder::Entity(well), water).filter(|entry| entry.reason == LedgerReason::FlowLoss).count(), 1);
+    }
+
+    #[test]
+    fn test_shared_source_is_split_proportionally() {
+        let food = ResourceRegistry::default().id("food").unwrap();
+        let mut world = World::new();
+        world.init_resource::<ResourceNetwork>();
+        world.init_resource::<ResourceLedger>();
+        let farm = holder(&mut world, food, 30);
+        let a = holder(&mut world, food, 0);
+        let b = holder(&mut world, food, 0);
+
+        {
+            let mut network = world.resource_mut::<ResourceNetwork>();
+            network.add_edge(ResourceFlow::new(farm, a, food, FlowKind::Caravan, 40.0));
+            network.add_edge(ResourceFlow::new(farm, b, food, FlowKind::Caravan, 20.0));
+        }
+        solve(&mut world, 1.0);
+
+        assert_eq!(amount(&world, farm, food), Quantity::from_units(0));
+        assert_eq!(amount(&world, a, food), Quantity::from_units(20));
+        assert_eq!(amount(&world, b, food), Quantity::from_units(10));
+    }
+
+    #[test]
+    fn test_transfer_edge_is_removed_when_done() {
+        let metal = ResourceRegistry::default().id("metal").unwrap();
+        let mut world = World::new();
+        world.init_resource::<ResourceNetwork>();
+        world.init_resource::<ResourceLedger>();
+        let mine = holder(&mut world, metal, 50);
+        let forge = holder(&mut world, metal, 0);
+
+        world.resource_mut::<ResourceNetwork>().add_edge(ResourceFlow::transfer(mine, forge, metal, Quantity::from_units(30)));
+        solve(&mut world, 0.1);
+
+        assert_eq!(amount(&world, mine, metal), Quantity::from_units(20));
+        assert_eq!(amount(&world, forge, metal), Quantity::from_units(30));
+        assert!(world.resource::<ResourceNetwork>().is_empty());
+    }
+
+    #[test]
+    fn test_hauls_run_as_caravans_of_the_agent() {
+        let food = ResourceRegistry::default().id("food").unwrap();
+        let mut world = World::new();
+        world.init_resource::<ResourceRegistry>();
+        world.init_resource::<ResourceNetwork>();
+        world.init_resource::<ResourceLedger>();
+        let farm = holder(&mut world, food, 30);
+        let village = holder(&mut world, food, 0);
+        let haul = Job::Haul { from: farm, to: village, resource_type: "food".into(), amount: Quantity::from_units(5) };
+        let hauler = world.spawn(Agent { current_job: Some(haul.clone()), ..default() }).id();
+        let stranger = world.spawn(Agent { current_job: Some(haul), ..default() }).id();
+        world.entity_mut(farm).insert(Stockpile::new(Owner::Agent(hauler), AccessPolicy::Private));
+
+        world.run_system_once(haul_job_system).unwrap();
+        assert_eq!(world.get::<Agent>(hauler).unwrap().current_job, Some(Job::Idle));
+        assert_eq!(world.get::<Agent>(stranger).unwrap().current_job, Some(Job::Idle));
+        solve(&mut world, 2.0);
+        assert_eq!(amount(&world, village, food), Quantity::from_units(4));
+
+        // A second haul on the same route joins the caravan on its way
+        world.get_mut::<Agent>(hauler).unwrap().current_job =
+            Some(Job::Haul { from: farm, to: village, resource_type: "food".into(), amount: Quantity::from_units(3) });
+        world.run_system_once(haul_job_system).unwrap();
+        assert_eq!(world.resource::<ResourceNetwork>().edges_from(farm).count(), 1);
+        solve(&mut world, 2.0);
+        solve(&mut world, 2.0);
+
+        assert_eq!(amount(&world, farm, food), Quantity::from_units(22));
+        assert_eq!(amount(&world, village, food), Quantity::from_units(8));
+        assert!(world.resource::<ResourceNetwork>().is_empty());
+        let ledger = world.resource::<ResourceLedger>();
+        assert!(ledger.entries_since(0).iter().all(|entry| entry.source == Some(hauler)));
+    }
+}
@@ -0,0 +1,488 @@
+use bevy::prelude::*;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use crate::engine::time::SimClock;
+use crate::world::ledger::{Holder, ResourceLedger};
+use crate::world::quantity::Quantity;
+use crate::world::resources::{ResourceId, ResourceManager, ResourceRegistry, ResourceStore, ResourceSystem};
+
+/// What a series of samples describes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum SeriesScope {
+    /// A single holder; environment holders are not tracked
+    Holder(Holder),
+    /// The sum over all holders, with flows to and from the environment
+    Total,
+}
+
+/// Stock and flows of one resource over a span of simulation time
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HistorySample {
+    /// Elapsed simulation seconds at the start of the span
+    pub start: f32,
+    /// Elapsed simulation seconds at the end of the span
+    pub end: f32,
+    pub stock_min: Quantity,
+    pub stock_max: Quantity,
+    /// Stock averaged over the span
+    pub stock_mean: Quantity,
+    /// Stock at the end of the span
+    pub stock_last: Quantity,
+    /// Amount received during the span
+    pub inflow: Quantity,
+    /// Amount sent away, consumed or lost during the span
+    pub outflow: Quantity,
+}
+
+impl HistorySample {
+    pub fn duration(&self) -> f32 {
+        self.end - self.start
+    }
+
+    /// Net change in stock from flows
+    pub fn net_flow(&self) -> Quantity {
+        self.inflow - self.outflow
+    }
+
+    /// Extends the sample with a later one
+    pub fn merge(&mut self, later: &HistorySample) {
+        let (own, theirs) = (self.duration().max(0.0) as f64, later.duration().max(0.0) as f64);
+        if own + theirs > 0.0 {
+            let mean = (self.stock_mean.millis() as f64 * own + later.stock_mean.millis() as f64 * theirs) / (own + theirs);
+            self.stock_mean = Quantity::from_millis(mean.round() as i64);
+        }
+        self.start = self.start.min(later.start);
+        self.end = self.end.max(later.end);
+        self.stock_min = self.stock_min.min(later.stock_min);
+        self.stock_max = self.stock_max.max(later.stock_max);
+        self.stock_last = later.stock_last;
+        self.inflow += later.inflow;
+        self.outflow += later.outflow;
+    }
+}
+
+/// How often and how long history is kept
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HistoryConfig {
+    /// Simulation seconds between samples
+    pub sample_interval: f32,
+    /// Samples kept at each resolution before the oldest are merged
+    pub samples_per_level: usize,
+    /// Number of samples merged into one at the next coarser level
+    pub downsample_factor: usize,
+    /// Number of resolutions; the oldest samples of the coarsest are dropped
+    pub levels: usize,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            sample_interval: 60.0,
+            samples_per_level: 48,
+            downsample_factor: 4,
+            levels: 4,
+        }
+    }
+}
+
+/// Bounded, downsampled time series of one resource
+///
+/// New samples enter the finest level. When a level is full, its oldest
+/// samples are merged into one sample of the next coarser level, so recent
+/// history is kept in detail and older history at decreasing resolution.
+#[derive(Debug, Clone, Default)]
+pub struct ResourceSeries {
+    /// Finest resolution first
+    levels: Vec<VecDeque<HistorySample>>,
+}
+
+impl ResourceSeries {
+    /// Appends a sample, downsampling older ones as needed
+    pub fn push(&mut self, sample: HistorySample, config: &HistoryConfig) {
+        let levels = config.levels.max(1);
+        let factor = config.downsample_factor.max(2);
+        let capacity = config.samples_per_level.max(factor);
+        self.levels.resize_with(levels, VecDeque::new);
+
+        self.levels[0].push_back(sample);
+        for level in 0..levels {
+            if self.levels[level].len() <= capacity {
+                break;
+            }
+            if level + 1 == levels {
+                self.levels[level].pop_front();
+                break;
+            }
+            let mut merged = self.levels[level].pop_front().unwrap();
+            for _ in 1..factor {
+                if let Some(next) = self.levels[level].pop_front() {
+                    merged.merge(&next);
+                }
+            }
+            self.levels[level + 1].push_back(merged);
+        }
+    }
+
+    /// Iterates over all samples, oldest first
+    pub fn samples(&self) -> impl Iterator<Item = &HistorySample> {
+        self.levels.iter().rev().flatten()
+    }
+
+    /// Iterates over the samples overlapping `[from, to)`, oldest first
+    pub fn range(&self, from: f32, to: f32) -> impl Iterator<Item = &HistorySample> {
+        self.samples().filter(move |sample| sample.end > from && sample.start < to)
+    }
+
+    /// Merges the samples overlapping `[from, to)` into one
+    ///
+    /// Coarse samples are included whole, so the result may cover a little
+    /// more than the requested span.
+    pub fn summary(&self, from: f32, to: f32) -> Option<HistorySample> {
+        merge_all(self.range(from, to))
+    }
+
+    /// Splits `[from, to)` into buckets of `width` seconds and summarizes each
+    ///
+    /// Samples are assigned to the bucket their midpoint falls in; buckets
+    /// without samples are `None`.
+    // For charts of a series; nothing draws them yet
+    #[allow(dead_code)]
+    pub fn buckets(&self, from: f32, to: f32, width: f32) -> Vec<Option<HistorySample>> {
+        if width <= 0.0 || to <= from {
+            return Vec::new();
+        }
+        let count = ((to - from) / width).ceil() as usize;
+        let mut buckets: Vec<Option<HistorySample>> = vec![None; count];
+        for sample in self.range(from, to) {
+            let midpoint = (sample.start + sample.end) / 2.0;
+            let index = (((midpoint - from) / width).floor().max(0.0) as usize).min(count - 1);
+            match &mut buckets[index] {
+                Some(bucket) => bucket.merge(sample),
+                bucket => *bucket = Some(*sample),
+            }
+        }
+        buckets
+    }
+
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.levels.iter().map(VecDeque::len).sum()
+    }
+}
+
+fn merge_all<'a>(mut samples: impl Iterator<Item = &'a HistorySample>) -> Option<HistorySample> {
+    let mut merged = *samples.next()?;
+    for sample in samples {
+        merged.merge(sample);
+    }
+    Some(merged)
+}
+
+/// Flows and stocks seen since the last sample
+#[derive(Debug, Clone, Copy, Default)]
+struct PendingSample {
+    inflow: Quantity,
+    outflow: Quantity,
+    /// Lowest and highest stock seen, once observed
+    stock_range: Option<(Quantity, Quantity)>,
+    /// Stock integrated over the time it was held, in milliunit-seconds
+    stock_time: f64,
+    /// Stock at the previous observation
+    stock_last: Quantity,
+}
+
+impl PendingSample {
+    /// Records the stock now, `elapsed` seconds after the previous observation
+    fn observe(&mut self, stock: Quantity, elapsed: f32) {
+        // Not observed before means nothing was held earlier in the span
+        let (min, max) = self.stock_range.get_or_insert((Quantity::ZERO, Quantity::ZERO));
+        *min = (*min).min(stock);
+        *max = (*max).max(stock);
+        self.stock_time += self.stock_last.millis() as f64 * elapsed.max(0.0) as f64;
+        self.stock_last = stock;
+    }
+}
+
+/// Resource keeping the stock and flow history of every holder and resource
+///
+/// Stocks are observed from the stores every tick and summarized into a
+/// sample every `sample_interval` seconds of simulation time; flows are
+/// summed from the `ResourceLedger` in between. History of despawned
+/// holders is dropped.
+#[derive(Debug, Default, Resource)]
+pub struct ResourceHistory {
+    pub config: HistoryConfig,
+    series: HashMap<(SeriesScope, ResourceId), ResourceSeries>,
+    /// Flows and stocks since the last sample
+    pending: HashMap<(SeriesScope, ResourceId), PendingSample>,
+    ledger_cursor: usize,
+    last_sample: Option<f32>,
+    last_observed: f32,
+}
+
+impl ResourceHistory {
+    #[cfg(test)]
+    pub fn new(config: HistoryConfig) -> Self {
+        Self { config, ..Default::default() }
+    }
+
+    pub fn series(&self, scope: SeriesScope, resource: ResourceId) -> Option<&ResourceSeries> {
+        self.series.get(&(scope, resource))
+    }
+
+    /// Summarizes a series over `[from, to)`
+    pub fn summary(&self, scope: SeriesScope, resource: ResourceId, from: f32, to: f32) -> Option<HistorySample> {
+        self.series(scope, resource)?.summary(from, to)
+    }
+
+    /// Summarizes a series over the last `duration` seconds
+    ///
+    /// E.g. the energy an agent used over the last N days is the
+    /// `outflow` of its energy series over N days.
+    pub fn over_last(&self, scope: SeriesScope, resource: ResourceId, duration: f32) -> Option<HistorySample> {
+        let now = self.last_sample?;
+        self.summary(scope, resource, now - duration, now)
+    }
+
+    /// Adds new ledger entries and the current stocks to the pending
+    /// sample and, once the sample interval has passed, completes it
+    ///
+    /// `stores` must list every live holder; series of holders missing
+    /// from it are dropped.
+    pub fn record<'a>(
+        &mut self,
+        now: f32,
+        stores: impl Iterator<Item = (Holder, &'a ResourceStore)>,
+        ledger: &ResourceLedger,
+    ) {
+        for entry in ledger.entries_since(self.ledger_cursor) {
+            let (from_environment, to_environment) = (
+                matches!(entry.from, Holder::Environment(_)),
+                matches!(entry.to, Holder::Environment(_)),
+            );
+            if !from_environment {
+                self.pending.entry((SeriesScope::Holder(entry.from), entry.resource)).or_default().outflow += entry.amount;
+            }
+            if !to_environment {
+                self.pending.entry((SeriesScope::Holder(entry.to), entry.resource)).or_default().inflow += entry.amount;
+            }
+            if from_environment && !to_environment {
+                self.pending.entry((SeriesScope::Total, entry.resource)).or_default().inflow += entry.amount;
+            } else if to_environment && !from_environment {
+                self.pending.entry((SeriesScope::Total, entry.resource)).or_default().outflow += entry.amount;
+            }
+        }
+        self.ledger_cursor = ledger.end();
+
+        let mut stocks: BTreeMap<(SeriesScope, ResourceId), Quantity> = BTreeMap::new();
+        let mut live = HashSet::new();
+        for (holder, store) in stores {
+            live.insert(SeriesScope::Holder(holder));
+            for (resource, amount) in store.totals() {
+                *stocks.entry((SeriesScope::Holder(holder), resource)).or_default() += amount;
+                *stocks.entry((SeriesScope::Total, resource)).or_default() += amount;
+            }
+        }
+        live.insert(SeriesScope::Total);
+
+        let Some(start) = self.last_sample else {
+            self.last_sample = Some(now);
+            self.start_span(now, &stocks);
+            return;
+        };
+
+        let elapsed = now - self.last_observed;
+        self.last_observed = now;
+        let mut keys: Vec<(SeriesScope, ResourceId)> = self.series.keys().copied().collect();
+        keys.extend(stocks.keys().copied());
+        keys.extend(self.pending.keys().copied());
+        keys.sort();
+        keys.dedup();
+        for &key in &keys {
+            let stock = stocks.get(&key).copied().unwrap_or_default();
+            self.pending.entry(key).or_default().observe(stock, elapsed);
+        }
+
+        if now - start < self.config.sample_interval {
+            return;
+        }
+        self.last_sample = Some(now);
+        self.series.retain(|(scope, _), _| live.contains(scope));
+
+        let duration = now - start;
+        for key in keys.into_iter().filter(|(scope, _)| live.contains(scope)) {
+            let stock = stocks.get(&key).copied().unwrap_or_default();
+            let pending = self.pending.get(&key).copied().unwrap_or_default();
+            let (stock_min, stock_max) = pending.stock_range.unwrap_or((stock, stock));
+            if !self.series.contains_key(&key) && stock_max.is_zero() && pending.inflow.is_zero() && pending.outflow.is_zero() {
+                continue;
+            }
+            let stock_mean = if duration > 0.0 {
+                Quantity::from_millis((pending.stock_time / duration as f64).round() as i64)
+            } else {
+                stock
+            };
+            let sample = HistorySample {
+                start,
+                end: now,
+                stock_min,
+                stock_max,
+                stock_mean,
+                stock_last: stock,
+                inflow: pending.inflow,
+                outflow: pending.outflow,
+            };
+            self.series.entry(key).or_default().push(sample, &self.config);
+        }
+        self.start_span(now, &stocks);
+    }
+
+    /// Clears the pending sample, starting it from the given stocks
+    fn start_span(&mut self, now: f32, stocks: &BTreeMap<(SeriesScope, ResourceId), Quantity>) {
+        self.last_observed = now;
+        self.pending.clear();
+        for (&key, &stock) in stocks {
+            self.pending.insert(key, PendingSample {
+                stock_range: Some((stock, stock)),
+                stock_last: stock,
+                ..Default::default()
+            });
+        }
+    }
+}
+
+/// System sampling every resource holder into the `ResourceHistory`
+pub fn record_resource_history(
+    clock: Res<SimClock>,
+    ledger: Res<ResourceLedger>,
+    mut history: ResMut<ResourceHistory>,
+    systems: Query<(Entity, &ResourceSystem)>,
+    global: Option<Res<ResourceManager>>,
+) {
+    let stores = systems
+        .iter()
+        .map(|(entity, system)| (Holder::Entity(entity), &system.store))
+        .chain(global.as_deref().map(|manager| (Holder::Global, &manager.store)));
+    history.record(clock.elapsed() as f32, stores, &ledger);
+}
+
+/// System logging the day's total stock and flows of every resource once
+/// the day is over
+pub fn report_resource_history(
+    clock: Res<SimClock>,
+    registry: Res<ResourceRegistry>,
+    history: Res<ResourceHistory>,
+    mut reported_day: Local<u64>,
+) {
+    let day = clock.day();
+    if day <= *reported_day {
+        return;
+    }
+    *reported_day = day;
+
+    for (resource, definition) in registry.iter() {
+        let Some(summary) = history.over_last(SeriesScope::Total, resource, clock.day_length) else {
+            continue;
+        };
+        info!(
+            "Day {}: {} stock {} (min {}, max {}), net flow {}",
+            day, definition.id, summary.stock_last, summary.stock_min, summary.stock_max, summary.net_flow()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::ledger::{LedgerReason, ResourceTransaction, SingleHolder};
+    use crate::world::resources::ResourceRegistry;
+
+    fn sample(start: f32, stock: i64, inflow: i64) -> HistorySample {
+        let stock = Quantity::from_units(stock);
+        HistorySample {
+            start,
+            end: start + 1.0,
+            stock_min: stock,
+            stock_max: stock,
+            stock_mean: stock,
+            stock_last: stock,
+            inflow: Quantity::from_units(inflow),
+            outflow: Quantity::ZERO,
+        }
+    }
+
+    #[test]
+    fn test_series_is_bounded_and_downsampled() {
+        let config = HistoryConfig {
+            sample_interval: 1.0,
+            samples_per_level: 4,
+            downsample_factor: 2,
+            levels: 2,
+        };
+        let mut series = ResourceSeries::default();
+        for second in 0..20 {
+            series.push(sample(second as f32, second, 1), &config);
+        }
+
+        // Four fine samples and at most five coarse ones
+        assert!(series.len() <= 9);
+        let samples: Vec<&HistorySample> = series.samples().collect();
+        assert!(samples.windows(2).all(|pair| pair[0].end <= pair[1].start));
+        assert_eq!(samples.last().unwrap().stock_last, Quantity::from_units(19));
+        assert_eq!(samples[0].duration(), 2.0);
+
+        // Flows survive downsampling within the kept span
+        let kept = series.summary(0.0, 20.0).unwrap();
+        assert_eq!(kept.inflow, Quantity::from_units((20.0 - kept.start) as i64));
+        assert_eq!(kept.stock_max, Quantity::from_units(19));
+
+        let buckets = series.buckets(10.0, 20.0, 5.0);
+        assert_eq!(buckets.len(), 2);
+        assert_eq!(buckets[1].unwrap().stock_last, Quantity::from_units(19));
+    }
+
+    #[test]
+    fn test_history_tracks_holders_and_totals() {
+        let food = ResourceRegistry::default().id("food").unwrap();
+        let agent = Entity::from_raw(1);
+        let mut store = ResourceStore::with_capacity(Quantity::from_units(100));
+        let mut ledger = ResourceLedger::default();
+        let mut history = ResourceHistory::new(HistoryConfig {
+            sample_interval: 10.0,
+            ..Default::default()
+        });
+
+        history.record(0.0, [(Holder::Entity(agent), &store)].into_iter(), &ledger);
+        for (second, gathered) in [(5.0, 4), (10.0, 6), (15.0, 0), (20.0, 2)] {
+            if gathered > 0 {
+                let mut transaction = ResourceTransaction::new(LedgerReason::Gather);
+                transaction.credit(Holder::Entity(agent), food, Quantity::from_units(gathered));
+                let mut holders = SingleHolder { entity: agent, store: &mut store };
+                transaction.commit(0, &mut holders, &mut ledger, None).unwrap();
+            }
+            history.record(second, [(Holder::Entity(agent), &store)].into_iter(), &ledger);
+        }
+
+        let own = history.series(SeriesScope::Holder(Holder::Entity(agent)), food).unwrap();
+        assert_eq!(own.len(), 2);
+        let last = history.over_last(SeriesScope::Holder(Holder::Entity(agent)), food, 10.0).unwrap();
+        assert_eq!(last.inflow, Quantity::from_units(2));
+        assert_eq!(last.stock_last, Quantity::from_units(12));
+        let all = history.summary(SeriesScope::Total, food, 0.0, 20.0).unwrap();
+        assert_eq!(all.inflow, Quantity::from_units(12));
+        assert_eq!(all.stock_min, Quantity::ZERO);
+        assert_eq!(all.stock_max, Quantity::from_units(12));
+
+        // Stock statistics cover every observation in the span, not just its end
+        let first = history.summary(SeriesScope::Total, food, 0.0, 10.0).unwrap();
+        assert_eq!((first.stock_min, first.stock_max), (Quantity::ZERO, Quantity::from_units(10)));
+        // Nothing for five seconds, then four units for five
+        assert_eq!(first.stock_mean, Quantity::from_units(2));
+        assert_eq!(last.stock_min, Quantity::from_units(10));
+        assert_eq!(last.stock_mean, Quantity::from_units(10));
+
+        // Despawned holders are forgotten
+        history.record(30.0, std::iter::empty(), &ledger);
+        assert!(history.series(SeriesScope::Holder(Holder::Entity(agent)), food).is_none());
+        assert_eq!(history.series.keys().collect::<Vec<_>>(), vec![&(SeriesScope::Total, food)]);
+    }
+}
@@ -1,7 +1,28 @@
 use bevy::prelude::*;
+use serde::Deserialize;
 use std::collections::HashMap;
+use std::ops::{Deref, DerefMut};
+use crate::world::chunk::{Biome, Stratum};
+use crate::world::deposits::{attach_tile_deposits, regrow_deposits, gather_job_system};
+use crate::world::recipes::{load_recipe_book, production_job_system, remember_completed_recipes, RecipeBook, RecipeCompleted};
+use crate::world::resource_flow::{haul_job_system, solve_resource_flows, ResourceNetwork};
+use crate::world::market::{configure_market_currency, market_clearing_system, post_agent_orders, remember_trades, Market, TradeExecuted, TradeLedger};
+use crate::world::ownership::{stockpile_job_system, TheftAttempt};
+use crate::world::resource_history::{record_resource_history, report_resource_history, ResourceHistory};
+use crate::world::storage::{apply_storage_tiers, load_storage_tiers, Storage, StorageTiers};
+use crate::world::ledger::{trim_resource_ledger, Holder, LedgerEntry, LedgerReason, ResourceLedger, ResourceTransaction, SingleHolder};
+use crate::world::items::ItemStack;
+use crate::world::quantity::{Quantity, Remainder};
+use crate::engine::pipeline::{Act, AdvanceTime, Decide, PostTick, PreTick, Resolve};
+use crate::engine::time::SimClock;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// Path of the resource definitions loaded at startup
+pub const RESOURCE_DEFINITIONS_PATH: &str = "assets/data/resources.ron";
+
+/// Built-in copy of the resource definitions, used when the file can't be read
+const DEFAULT_RESOURCE_DEFINITIONS: &str = include_str!("../../assets/data/resources.ron");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
 pub enum ResourceCategory {
     Basic,      // Food, Water, Oxygen
     Energy,     // Energy, Fuel
@@ -9,103 +30,219 @@ pub enum ResourceCategory {
     Special,    // Rare resources, artifacts
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub struct ResourceMetadata {
+/// Identifier of a resource registered in the `ResourceRegistry`
+///
+/// IDs are only meaningful for the registry that issued them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ResourceId(pub u16);
+
+/// Rule describing where a resource occurs as a tile deposit
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct DepositRule {
+    /// Biomes the rule applies to, empty for any biome
+    #[serde(default)]
+    pub biomes: Vec<Biome>,
+    /// Strata the rule applies to, empty for any stratum
+    #[serde(default)]
+    pub strata: Vec<Stratum>,
+    pub amount: f32,
+    #[serde(default)]
+    pub regrowth_rate: f32,
+}
+
+impl DepositRule {
+    pub fn matches(&self, biome: Biome, stratum: Stratum) -> bool {
+        (self.biomes.is_empty() || self.biomes.contains(&biome))
+            && (self.strata.is_empty() || self.strata.contains(&stratum))
+    }
+}
+
+/// How stacks of a resource lose quality and amount as they age
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+pub struct Spoilage {
+    /// Seconds a stack keeps before it starts to spoil
+    #[serde(default)]
+    pub fresh_for: f32,
+    /// Quality lost per second once spoiling; the stack is gone at zero
+    #[serde(default)]
+    pub quality_loss: f32,
+    /// Fraction of the amount lost per second once spoiling
+    #[serde(default)]
+    pub amount_loss: f32,
+}
+
+/// Data-driven definition of a resource
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ResourceDefinition {
+    /// Unique lowercase key, e.g. "food"
+    pub id: String,
+    /// Display name
+    pub name: String,
     pub category: ResourceCategory,
+    #[serde(default)]
     pub is_renewable: bool,
-    pub decay_rate: f32,
+    /// How stacks deteriorate with age, `None` for resources that keep
+    #[serde(default)]
+    pub spoilage: Option<Spoilage>,
+    /// Maximum amount held in a single stack
+    #[serde(default = "default_stack_size")]
+    pub stack_size: u32,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub deposits: Vec<DepositRule>,
+}
+
+fn default_stack_size() -> u32 {
+    100
+}
+
+impl ResourceDefinition {
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub enum ResourceType {
-    Food,
-    Water,
-    Energy,
-    Metal,
-    Oxygen,
+/// Registry of all resource definitions known to the simulation
+#[derive(Debug, Clone, Resource)]
+pub struct ResourceRegistry {
+    definitions: Vec<ResourceDefinition>,
+    ids: HashMap<String, ResourceId>,
 }
 
-impl ResourceType {
-    pub fn metadata(&self) -> ResourceMetadata {
-        match self {
-            ResourceType::Food => ResourceMetadata {
-                category: ResourceCategory::Basic,
-                is_renewable: true,
-                decay_rate: 0.1,
-            },
-            ResourceType::Water => ResourceMetadata {
-                category: ResourceCategory::Basic,
-                is_renewable: true,
-                decay_rate: 0.0,
-            },
-            ResourceType::Energy => ResourceMetadata {
-                category: ResourceCategory::Energy,
-                is_renewable: true,
-                decay_rate: 0.2,
-            },
-            ResourceType::Metal => ResourceMetadata {
-                category: ResourceCategory::Material,
-                is_renewable: false,
-                decay_rate: 0.0,
-            },
-            ResourceType::Oxygen => ResourceMetadata {
-                category: ResourceCategory::Basic,
-                is_renewable: true,
-                decay_rate: 0.0,
-            },
+impl Default for ResourceRegistry {
+    fn default() -> Self {
+        Self::from_ron_str(DEFAULT_RESOURCE_DEFINITIONS)
+            .expect("built-in resource definitions are valid")
+    }
+}
+
+impl ResourceRegistry {
+    /// Builds a registry from a list of definitions, rejecting duplicate IDs
+    pub fn from_definitions(definitions: Vec<ResourceDefinition>) -> Result<Self, String> {
+        let mut ids = HashMap::new();
+        for (index, definition) in definitions.iter().enumerate() {
+            let key = definition.id.to_lowercase();
+            if ids.insert(key, ResourceId(index as u16)).is_some() {
+                return Err(format!("duplicate resource id '{}'", definition.id));
+            }
+        }
+        Ok(Self { definitions, ids })
+    }
+
+    /// Parses a RON list of resource definitions
+    pub fn from_ron_str(source: &str) -> Result<Self, String> {
+        let definitions: Vec<ResourceDefinition> = ron::from_str(source)
+            .map_err(|err| format!("invalid resource definitions: {}", err))?;
+        Self::from_definitions(definitions)
+    }
+
+    /// Loads resource definitions from a RON file
+    pub fn load(path: &str) -> Result<Self, String> {
+        let source = std::fs::read_to_string(path)
+            .map_err(|err| format!("failed to read {}: {}", path, err))?;
+        Self::from_ron_str(&source)
+    }
+
+    /// Looks up a resource by its key, ignoring case
+    pub fn id(&self, key: &str) -> Option<ResourceId> {
+        self.ids.get(&key.to_lowercase()).copied()
+    }
+
+    pub fn get(&self, id: ResourceId) -> Option<&ResourceDefinition> {
+        self.definitions.get(id.0 as usize)
+    }
+
+    /// Returns the key of a resource, or "unknown" for foreign IDs
+    pub fn key(&self, id: ResourceId) -> &str {
+        self.get(id).map(|definition| definition.id.as_str()).unwrap_or("unknown")
+    }
+
+    /// Iterates over all registered resources in definition order
+    pub fn iter(&self) -> impl Iterator<Item = (ResourceId, &ResourceDefinition)> {
+        self.definitions
+            .iter()
+            .enumerate()
+            .map(|(index, definition)| (ResourceId(index as u16), definition))
+    }
+
+    /// Iterates over all resources carrying the given tag
+    pub fn with_tag<'a>(&'a self, tag: &'a str) -> impl Iterator<Item = ResourceId> + 'a {
+        self.iter()
+            .filter(move |(_, definition)| definition.has_tag(tag))
+            .map(|(id, _)| id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.definitions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.definitions.is_empty()
+    }
+}
+
+/// Replaces the built-in registry with the definitions file, if present
+pub fn load_resource_registry(mut registry: ResMut<ResourceRegistry>) {
+    match ResourceRegistry::load(RESOURCE_DEFINITIONS_PATH) {
+        Ok(loaded) if loaded.is_empty() => {
+            warn!("Using built-in resource definitions: {} defines no resources", RESOURCE_DEFINITIONS_PATH);
+        }
+        Ok(loaded) => {
+            info!("Loaded {} resource definitions from {}", loaded.len(), RESOURCE_DEFINITIONS_PATH);
+            *registry = loaded;
+        }
+        Err(err) => {
+            warn!("Using built-in resource definitions: {}", err);
         }
     }
 }
 
 #[derive(Event, Debug)]
 pub struct ResourceChanged {
-    pub resource_type: ResourceType,
-    pub old_amount: f32,
-    pub new_amount: f32,
+    pub resource_type: ResourceId,
+    pub old_amount: Quantity,
+    pub new_amount: Quantity,
     pub source: Option<Entity>,
 }
 
-#[derive(Debug, Clone, Resource)]
-pub struct ResourceManager {
-    pub resources: HashMap<ResourceType, f32>,
-    pub max_capacity: HashMap<ResourceType, f32>,
-    pub regeneration_rate: f32,
-    pub depletion_rate: f32,
+/// Stacks and capacities of resources held by a single holder
+///
+/// Both the global `ResourceManager` and per-entity `ResourceSystem` keep
+/// their resources in a store. Each resource is held as `ItemStack`s
+/// carrying quality, age and provenance; amounts are the sum of the
+/// stacks, counted as exact `Quantity`s. The methods here change amounts directly and aren't recorded
+/// anywhere; use a `ResourceTransaction` for movements that should be
+/// atomic and appear in the `ResourceLedger`.
+#[derive(Debug, Clone, Default)]
+pub struct ResourceStore {
+    pub stacks: HashMap<ResourceId, Vec<ItemStack>>,
+    pub max_capacity: HashMap<ResourceId, Quantity>,
+    /// Capacity for resources without an entry in `max_capacity`
+    pub default_capacity: Quantity,
 }
 
-impl ResourceManager {
-    pub fn new() -> Self {
-        let mut max_capacity = HashMap::new();
-        max_capacity.insert(ResourceType::Food, 1000.0);
-        max_capacity.insert(ResourceType::Water, 1000.0);
-        max_capacity.insert(ResourceType::Energy, 1000.0);
-        max_capacity.insert(ResourceType::Metal, 1000.0);
-        max_capacity.insert(ResourceType::Oxygen, 1000.0);
-        
+impl ResourceStore {
+    pub fn with_capacity(default_capacity: Quantity) -> Self {
         Self {
-            resources: HashMap::new(),
-            max_capacity,
-            regeneration_rate: 1.0,
-            depletion_rate: 1.0,
+            stacks: HashMap::new(),
+            max_capacity: HashMap::new(),
+            default_capacity,
         }
     }
 
-    pub fn add(&mut self, resource: ResourceType, amount: f32, event_writer: Option<&mut EventWriter<ResourceChanged>>) -> f32 {
-        let max = *self.max_capacity.get(&resource).unwrap_or(&f32::INFINITY);
-        let current = *self.resources.entry(resource).or_insert(0.0);
-        let new_amount = (current + amount).min(max);
-        let added = new_amount - current;
+    /// Adds a fresh stack of unknown provenance, up to capacity
+    pub fn add(&mut self, resource: ResourceId, amount: Quantity, event_writer: Option<&mut EventWriter<ResourceChanged>>, source: Option<Entity>) -> Quantity {
+        let old = self.get(resource);
+        let added = self.insert_stack(resource, ItemStack::new(amount));
         
-        if added > 0.0 {
-            let old = current;
-            *self.resources.entry(resource).or_insert(0.0) = new_amount;
-            
+        if added.is_positive() {
             if let Some(events) = event_writer {
                 events.send(ResourceChanged {
                     resource_type: resource,
                     old_amount: old,
-                    new_amount,
-                    source: None,
+                    new_amount: old + added,
+                    source,
                 });
             }
         }
@@ -113,19 +250,23 @@ impl ResourceManager {
         added
     }
 
-    pub fn consume(&mut self, resource: ResourceType, amount: f32, event_writer: Option<&mut EventWriter<ResourceChanged>>) -> bool {
-        let entry = self.resources.entry(resource).or_insert(0.0);
-        let old = *entry;
-        
-        if *entry >= amount {
-            *entry -= amount;
+    /// Removes an amount, oldest stacks first, if enough is held
+    ///
+    /// Unchecked, so only tests use it; systems debit through a
+    /// `ResourceTransaction`, which enforces access.
+    #[cfg(test)]
+    pub fn consume(&mut self, resource: ResourceId, amount: Quantity, event_writer: Option<&mut EventWriter<ResourceChanged>>, source: Option<Entity>) -> bool {
+        let old = self.get(resource);
+        
+        if old >= amount {
+            self.take(resource, amount);
             
             if let Some(events) = event_writer {
                 events.send(ResourceChanged {
                     resource_type: resource,
                     old_amount: old,
-                    new_amount: *entry,
-                    source: None,
+                    new_amount: self.get(resource),
+                    source,
                 });
             }
             
@@ -135,260 +276,416 @@ impl ResourceManager {
         }
     }
 
-    pub fn get(&self, resource: ResourceType) -> f32 {
-        *self.resources.get(&resource).unwrap_or(&0.0)
+    pub fn get(&self, resource: ResourceId) -> Quantity {
+        self.stacks(resource).iter().map(|stack| stack.amount).sum()
+    }
+
+    /// Returns the stacks held of a resource
+    pub fn stacks(&self, resource: ResourceId) -> &[ItemStack] {
+        self.stacks.get(&resource).map_or(&[], |stacks| stacks.as_slice())
+    }
+
+    /// Iterates over the total amount of every resource held
+    pub fn totals(&self) -> impl Iterator<Item = (ResourceId, Quantity)> + '_ {
+        self.stacks
+            .iter()
+            .map(|(resource, stacks)| (*resource, stacks.iter().map(|stack| stack.amount).sum()))
+    }
+
+    /// Returns the average quality of a resource weighted by amount
+    pub fn quality(&self, resource: ResourceId) -> Option<f32> {
+        let total = self.get(resource).to_f32();
+        (total > 0.0).then(|| {
+            self.stacks(resource)
+                .iter()
+                .map(|stack| stack.quality * stack.amount.to_f32())
+                .sum::<f32>()
+                / total
+        })
+    }
+
+    /// Adds a stack up to capacity, merging it with a similar one
+    ///
+    /// Returns the amount accepted.
+    pub fn insert_stack(&mut self, resource: ResourceId, mut stack: ItemStack) -> Quantity {
+        stack.amount = stack.amount.min(self.get_available_space(resource));
+        if !stack.amount.is_positive() {
+            return Quantity::ZERO;
+        }
+        let added = stack.amount;
+        self.merge_stack(resource, stack);
+        added
+    }
+
+    /// Adds a stack regardless of capacity, merging it with a similar one
+    pub fn merge_stack(&mut self, resource: ResourceId, stack: ItemStack) {
+        if !stack.amount.is_positive() {
+            return;
+        }
+        let stacks = self.stacks.entry(resource).or_default();
+        match stacks.iter_mut().find(|held| held.can_merge(&stack)) {
+            Some(held) => held.merge(stack),
+            None => stacks.push(stack),
+        }
+    }
+
+    /// Removes up to `amount` of a resource, oldest stacks first so nothing
+    /// is left to spoil
+    ///
+    /// Returns the removed stacks with their provenance.
+    pub fn take(&mut self, resource: ResourceId, amount: Quantity) -> Vec<ItemStack> {
+        let Some(stacks) = self.stacks.get_mut(&resource) else {
+            return Vec::new();
+        };
+        stacks.sort_by(|a, b| b.age.total_cmp(&a.age));
+
+        let mut taken = Vec::new();
+        let mut remaining = amount;
+        for stack in stacks.iter_mut() {
+            if !remaining.is_positive() {
+                break;
+            }
+            let part = stack.amount.min(remaining);
+            stack.amount -= part;
+            remaining -= part;
+            taken.push(stack.split(part));
+        }
+        stacks.retain(|stack| stack.amount.is_positive());
+        taken
+    }
+
+    /// Ages every stack of a resource and returns the amount that spoiled
+    pub fn spoil(&mut self, resource: ResourceId, delta: f32, spoilage: &Spoilage) -> Quantity {
+        let Some(stacks) = self.stacks.get_mut(&resource) else {
+            return Quantity::ZERO;
+        };
+        let lost = stacks.iter_mut().map(|stack| stack.spoil(delta, spoilage)).sum();
+        stacks.retain(|stack| stack.amount.is_positive());
+        lost
     }
     
-    pub fn get_capacity(&self, resource: ResourceType) -> f32 {
-        *self.max_capacity.get(&resource).unwrap_or(&f32::INFINITY)
+    pub fn get_capacity(&self, resource: ResourceId) -> Quantity {
+        *self.max_capacity.get(&resource).unwrap_or(&self.default_capacity)
     }
     
-    pub fn get_available_space(&self, resource: ResourceType) -> f32 {
+    pub fn get_available_space(&self, resource: ResourceId) -> Quantity {
         let current = self.get(resource);
         let max = self.get_capacity(resource);
-        (max - current).max(0.0)
+        (max - current).non_negative()
     }
     
     /// Sets the resource amount directly, bypassing capacity limits
-    /// Primarily for debugging and development tools
-    pub fn set(&mut self, resource: ResourceType, amount: f32, event_writer: Option<&mut EventWriter<ResourceChanged>>) {
-        let old = *self.resources.entry(resource).or_insert(0.0);
-        *self.resources.entry(resource).or_insert(0.0) = amount;
+    /// Only for tests, e.g. to mint resources behind the ledger's back
+    #[cfg(test)]
+    pub fn set(&mut self, resource: ResourceId, amount: Quantity, event_writer: Option<&mut EventWriter<ResourceChanged>>, source: Option<Entity>) {
+        let amount = amount.non_negative();
+        let old = self.get(resource);
+        if amount > old {
+            self.merge_stack(resource, ItemStack::new(amount - old));
+        } else {
+            self.take(resource, old - amount);
+        }
         
         if let Some(events) = event_writer {
             events.send(ResourceChanged {
                 resource_type: resource,
                 old_amount: old,
                 new_amount: amount,
-                source: None,
+                source,
             });
         }
     }
     
     /// Sets the maximum capacity for a resource
-    pub fn set_capacity(&mut self, resource: ResourceType, capacity: f32) {
+    pub fn set_capacity(&mut self, resource: ResourceId, capacity: Quantity) {
         self.max_capacity.insert(resource, capacity);
     }
 }
 
+/// Global resource pool shared by the whole simulation
+#[derive(Debug, Clone, Resource)]
+pub struct ResourceManager {
+    pub store: ResourceStore,
+    pub regeneration_rate: f32,
+    pub depletion_rate: f32,
+}
+
+impl ResourceManager {
+    pub fn new() -> Self {
+        Self {
+            store: ResourceStore::with_capacity(Quantity::from_units(1000)),
+            regeneration_rate: 1.0,
+            depletion_rate: 1.0,
+        }
+    }
+}
+
+impl Deref for ResourceManager {
+    type Target = ResourceStore;
+
+    fn deref(&self) -> &ResourceStore {
+        &self.store
+    }
+}
+
+impl DerefMut for ResourceManager {
+    fn deref_mut(&mut self) -> &mut ResourceStore {
+        &mut self.store
+    }
+}
+
+/// Resources held by an individual entity
 #[derive(Debug, Clone, Component)]
 pub struct ResourceSystem {
-    pub resources: HashMap<ResourceType, f32>,
-    pub max_capacity: HashMap<ResourceType, f32>,
+    pub store: ResourceStore,
     pub regeneration_rate: f32,
     pub consumption_rate: f32,
+    /// Regeneration of each resource too small to show up in a single tick
+    pub regeneration_remainders: HashMap<ResourceId, Remainder>,
 }
 
 impl ResourceSystem {
     pub fn new() -> Self {
-        let mut max_capacity = HashMap::new();
-        max_capacity.insert(ResourceType::Food, 100.0);
-        max_capacity.insert(ResourceType::Water, 100.0);
-        max_capacity.insert(ResourceType::Energy, 100.0);
-        max_capacity.insert(ResourceType::Metal, 100.0);
-        max_capacity.insert(ResourceType::Oxygen, 100.0);
-        
         Self {
-            resources: HashMap::new(),
-            max_capacity,
+            store: ResourceStore::with_capacity(Quantity::from_units(100)),
             regeneration_rate: 1.0,
             consumption_rate: 1.0,
+            regeneration_remainders: HashMap::new(),
         }
     }
+}
 
-    pub fn add(&mut self, resource: ResourceType, amount: f32, event_writer: Option<&mut EventWriter<ResourceChanged>>, source: Option<Entity>) -> f32 {
-        let max = *self.max_capacity.get(&resource).unwrap_or(&f32::INFINITY);
-        let current = *self.resources.entry(resource).or_insert(0.0);
-        let new_amount = (current + amount).min(max);
-        let added = new_amount - current;
-        
-        if added > 0.0 {
-            let old = current;
-            *self.resources.entry(resource).or_insert(0.0) = new_amount;
-            
-            if let Some(events) = event_writer {
-                events.send(ResourceChanged {
-                    resource_type: resource,
-                    old_amount: old,
-                    new_amount,
-                    source,
-                });
-            }
-        }
-        
-        added
-    }
+impl Deref for ResourceSystem {
+    type Target = ResourceStore;
 
-    pub fn consume(&mut self, resource: ResourceType, amount: f32, event_writer: Option<&mut EventWriter<ResourceChanged>>, source: Option<Entity>) -> bool {
-        let entry = self.resources.entry(resource).or_insert(0.0);
-        let old = *entry;
-        
-        if *entry >= amount {
-            *entry -= amount;
-            
-            if let Some(events) = event_writer {
-                events.send(ResourceChanged {
-                    resource_type: resource,
-                    old_amount: old,
-                    new_amount: *entry,
-                    source,
-                });
-            }
-            
-            true
-        } else {
-            false
-        }
+    fn deref(&self) -> &ResourceStore {
+        &self.store
     }
+}
 
-    pub fn get(&self, resource: ResourceType) -> f32 {
-        *self.resources.get(&resource).unwrap_or(&0.0)
-    }
-    
-    pub fn get_capacity(&self, resource: ResourceType) -> f32 {
-        *self.max_capacity.get(&resource).unwrap_or(&f32::INFINITY)
-    }
-    
-    pub fn get_available_space(&self, resource: ResourceType) -> f32 {
-        let current = self.get(resource);
-        let max = self.get_capacity(resource);
-        (max - current).max(0.0)
-    }
-    
-    /// Sets the resource amount directly, bypassing capacity limits
-    /// Primarily for debugging and development tools
-    pub fn set(&mut self, resource: ResourceType, amount: f32, event_writer: Option<&mut EventWriter<ResourceChanged>>, source: Option<Entity>) {
-        let old = *self.resources.entry(resource).or_insert(0.0);
-        *self.resources.entry(resource).or_insert(0.0) = amount;
-        
-        if let Some(events) = event_writer {
-            events.send(ResourceChanged {
-                resource_type: resource,
-                old_amount: old,
-                new_amount: amount,
-                source,
-            });
-        }
-    }
-    
-    /// Sets the maximum capacity for a resource
-    pub fn set_capacity(&mut self, resource: ResourceType, capacity: f32) {
-        self.max_capacity.insert(resource, capacity);
+impl DerefMut for ResourceSystem {
+    fn deref_mut(&mut self) -> &mut ResourceStore {
+        &mut self.store
     }
 }
 
-/// Transfers resources from one system to another
+/// Transfers resources from one store to another
+///
+/// Moves as much of the amount as both stores allow, oldest stacks first,
+/// immediately and without a ledger entry or access check, so only tests
+/// use it. Systems use a `ResourceTransaction` to check and record the
+/// movement, or a `ResourceFlow::transfer` edge to solve it together with
+/// the other flows of the `ResourceNetwork`.
+#[cfg(test)]
 pub fn transfer_resources(
-    from: &mut ResourceSystem,
-    to: &mut ResourceSystem,
-    resource: ResourceType,
-    amount: f32,
+    from: &mut ResourceStore,
+    to: &mut ResourceStore,
+    resource: ResourceId,
+    amount: Quantity,
     event_writer: Option<&mut EventWriter<ResourceChanged>>,
     source: Option<Entity>,
-) -> f32 {
+) -> Quantity {
     // Calculate how much can actually be transferred
     let available_from = from.get(resource);
     let available_to = to.get_available_space(resource);
     let transfer_amount = amount.min(available_from).min(available_to);
     
-    if transfer_amount > 0.0 {
-        // Consume from source
-        from.consume(resource, transfer_amount, event_writer, source);
-        // Add to destination
-        to.add(resource, transfer_amount, event_writer, source);
+    if transfer_amount.is_positive() {
+        // Move the stacks themselves so quality and provenance are kept
+        let old_to = to.get(resource);
+        for stack in from.take(resource, transfer_amount) {
+            to.merge_stack(resource, stack);
+        }
+
+        if let Some(events) = event_writer {
+            events.send(ResourceChanged {
+                resource_type: resource,
+                old_amount: available_from,
+                new_amount: from.get(resource),
+                source,
+            });
+            events.send(ResourceChanged {
+                resource_type: resource,
+                old_amount: old_to,
+                new_amount: to.get(resource),
+                source,
+            });
+        }
     }
     
     transfer_amount
 }
 
-/// System that updates resource regeneration over time
+/// System that updates resource regeneration and spoilage over time
+///
+/// Regeneration is recorded in the ledger as a movement from the
+/// environment. Stacks spoil in place following their resource's
+/// `Spoilage` curve, slowed or sped up by the holder's storage tier, and
+/// the amount lost is recorded as `Decay`. Both carry fractions of a
+/// milliunit over to the next tick, so slow rates aren't rounded away.
 pub fn update_resources(
-    time: Res<Time>,
-    mut query: Query<&mut ResourceSystem>,
+    clock: Res<SimClock>,
+    registry: Res<ResourceRegistry>,
+    tiers: Res<StorageTiers>,
+    mut ledger: ResMut<ResourceLedger>,
+    mut query: Query<(Entity, &mut ResourceSystem, Option<&Storage>)>,
     mut events: EventWriter<ResourceChanged>,
 ) {
-    let delta = time.delta_seconds();
+    let delta = clock.tick_length as f32;
     
-    for mut system in query.iter_mut() {
-        for resource_type in [ResourceType::Food, ResourceType::Water, ResourceType::Energy, ResourceType::Metal, ResourceType::Oxygen] {
-            let metadata = resource_type.metadata();
-            
-            // Handle regeneration for renewable resources
-            if metadata.is_renewable {
-                let regeneration = system.regeneration_rate * delta;
-                system.add(resource_type, regeneration, Some(&mut events), None);
+    for (entity, mut system, storage) in query.iter_mut() {
+        let tier = storage.and_then(|storage| tiers.get(&storage.tier));
+        let regeneration_rate = system.regeneration_rate;
+        let mut regeneration = ResourceTransaction::new(LedgerReason::Regeneration);
+        
+        for (resource_type, metadata) in registry.iter() {
+            // Handle spoilage of what is held before regenerating fresh stock
+            if let Some(spoilage) = &metadata.spoilage {
+                let old_amount = system.get(resource_type);
+                let modifier = tier.map_or(1.0, |tier| tier.spoilage_modifier(resource_type));
+                let lost = system.store.spoil(resource_type, delta * modifier, spoilage);
+                if lost.is_positive() {
+                    ledger.record(LedgerEntry {
+                        tick: clock.tick(),
+                        from: Holder::Entity(entity),
+                        to: Holder::Environment(None),
+                        resource: resource_type,
+                        amount: lost,
+                        reason: LedgerReason::Decay,
+                        source: Some(entity),
+                    });
+                    events.send(ResourceChanged {
+                        resource_type,
+                        old_amount,
+                        new_amount: old_amount - lost,
+                        source: Some(entity),
+                    });
+                }
             }
-            
-            // Handle decay
-            if metadata.decay_rate > 0.0 {
-                let decay = metadata.decay_rate * delta;
-                let current = system.get(resource_type);
-                if current > 0.0 {
-                    system.consume(resource_type, decay.min(current), Some(&mut events), None);
+
+            // Handle regeneration for renewable resources, up to capacity
+            if metadata.is_renewable {
+                let regenerated = system
+                    .regeneration_remainders
+                    .entry(resource_type)
+                    .or_default()
+                    .accrue(regeneration_rate as f64 * delta as f64);
+                let amount = regenerated.min(system.get_available_space(resource_type));
+                if amount.is_positive() {
+                    regeneration.credit(Holder::Entity(entity), resource_type, amount);
                 }
             }
         }
+        
+        if regeneration.is_empty() {
+            continue;
+        }
+        let mut holders = SingleHolder { entity, store: &mut system.store };
+        if let Err(err) = regeneration.commit(clock.tick(), &mut holders, &mut ledger, Some(&mut events)) {
+            warn!("Resource update for {:?} failed: {:?}", entity, err);
+        }
     }
 }
 
-/// System that processes resource changes and updates UI or other systems
-pub fn process_resource_changes(
-    mut events: EventReader<ResourceChanged>,
-) {
-    for event in events.read() {
-        // This is a placeholder for future UI updates, logging, or other systems
-        // that need to react to resource changes
-        info!(
-            "Resource changed: {:?} from {} to {} (source: {:?})",
-            event.resource_type, event.old_amount, event.new_amount, event.source
-        );
+/// Plugin for resources, the tile deposits they are gathered from, the
+/// recipes that convert them, the network they flow through, the
+/// market they are traded on and the history of their stocks
+pub struct ResourcePlugin;
+
+impl Plugin for ResourcePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<ResourceRegistry>()
+            .init_resource::<RecipeBook>()
+            .init_resource::<StorageTiers>()
+            .init_resource::<ResourceNetwork>()
+            .init_resource::<Market>()
+            .init_resource::<TradeLedger>()
+            .init_resource::<ResourceLedger>()
+            .init_resource::<ResourceHistory>()
+            .init_resource::<SimClock>()
+            .add_event::<ResourceChanged>()
+            .add_event::<RecipeCompleted>()
+            .add_event::<TradeExecuted>()
+            .add_event::<TheftAttempt>()
+            .add_systems(PreStartup, (
+                load_resource_registry,
+                (load_recipe_book, load_storage_tiers, configure_market_currency),
+            ).chain())
+            .add_systems(PreTick, attach_tile_deposits.after(AdvanceTime))
+            .add_systems(Decide, post_agent_orders)
+            .add_systems(Act, (
+                update_resources,
+                apply_storage_tiers,
+                gather_job_system,
+                production_job_system,
+                remember_completed_recipes,
+                stockpile_job_system,
+                haul_job_system,
+            ).chain())
+            .add_systems(Resolve, (
+                regrow_deposits,
+                solve_resource_flows,
+                market_clearing_system,
+                remember_trades,
+            ).chain())
+            .add_systems(PostTick, (record_resource_history, report_resource_history, trim_resource_ledger).chain());
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use bevy::prelude::*;
+    use bevy::ecs::system::RunSystemOnce;
+    use crate::engine::pipeline::TickPipelinePlugin;
+    use crate::world::conservation::{ConservationChecker, ConservationPlugin};
+
+    fn resource_id(key: &str) -> ResourceId {
+        ResourceRegistry::default().id(key).unwrap()
+    }
 
     #[test]
     fn test_resource_manager() {
         let mut app = App::new();
         app.add_event::<ResourceChanged>();
         
-        let mut manager = ResourceManager::new();
-        let mut events = app.world.resource_mut::<Events<ResourceChanged>>();
-        let mut writer = EventWriter::new(&mut events);
+        app.world_mut().run_system_once(|mut writer: EventWriter<ResourceChanged>| {
+            let mut manager = ResourceManager::new();
+            let food = resource_id("food");
+            let water = resource_id("water");
         
-        // Test adding resources
-        manager.add(ResourceType::Food, 10.0, Some(&mut writer));
-        manager.add(ResourceType::Water, 5.0, Some(&mut writer));
+            // Test adding resources
+            manager.add(food, Quantity::from_units(10), Some(&mut writer), None);
+            manager.add(water, Quantity::from_units(5), Some(&mut writer), None);
         
-        assert_eq!(manager.get(ResourceType::Food), 10.0);
-        assert_eq!(manager.get(ResourceType::Water), 5.0);
+            assert_eq!(manager.get(food), Quantity::from_units(10));
+            assert_eq!(manager.get(water), Quantity::from_units(5));
         
-        // Test consuming resources
-        assert!(manager.consume(ResourceType::Food, 5.0, Some(&mut writer)));
-        assert_eq!(manager.get(ResourceType::Food), 5.0);
+            // Test consuming resources
+            assert!(manager.consume(food, Quantity::from_units(5), Some(&mut writer), None));
+            assert_eq!(manager.get(food), Quantity::from_units(5));
         
-        // Test consuming more than available
-        assert!(!manager.consume(ResourceType::Food, 10.0, Some(&mut writer)));
-        assert_eq!(manager.get(ResourceType::Food), 5.0);
+            // Test consuming more than available
+            assert!(!manager.consume(food, Quantity::from_units(10), Some(&mut writer), None));
+            assert_eq!(manager.get(food), Quantity::from_units(5));
         
-        // Test capacity limits
-        assert_eq!(manager.get_capacity(ResourceType::Food), 1000.0);
-        assert_eq!(manager.get_available_space(ResourceType::Food), 995.0);
+            // Test capacity limits
+            assert_eq!(manager.get_capacity(food), Quantity::from_units(1000));
+            assert_eq!(manager.get_available_space(food), Quantity::from_units(995));
         
-        // Test set method
-        manager.set(ResourceType::Food, 20.0, Some(&mut writer));
-        assert_eq!(manager.get(ResourceType::Food), 20.0);
+            // Test set method
+            manager.set(food, Quantity::from_units(20), Some(&mut writer), None);
+            assert_eq!(manager.get(food), Quantity::from_units(20));
         
-        // Test set_capacity method
-        manager.set_capacity(ResourceType::Food, 50.0);
-        assert_eq!(manager.get_capacity(ResourceType::Food), 50.0);
+            // Test set_capacity method
+            manager.set_capacity(food, Quantity::from_units(50));
+            assert_eq!(manager.get_capacity(food), Quantity::from_units(50));
+        }).unwrap();
         
         // Verify events were sent
-        let mut reader = events.get_reader();
-        let events: Vec<&ResourceChanged> = reader.read(&events).collect();
+        let events = app.world().resource::<Events<ResourceChanged>>();
+        let mut reader = events.get_cursor();
+        let events: Vec<&ResourceChanged> = reader.read(events).collect();
         assert!(events.len() >= 4); // At least 4 events (2 adds, 1 consume, 1 set)
     }
 
@@ -397,40 +694,43 @@ mod tests {
         let mut app = App::new();
         app.add_event::<ResourceChanged>();
         
-        let mut system = ResourceSystem::new();
-        let mut events = app.world.resource_mut::<Events<ResourceChanged>>();
-        let mut writer = EventWriter::new(&mut events);
+        app.world_mut().run_system_once(|mut writer: EventWriter<ResourceChanged>| {
+            let mut system = ResourceSystem::new();
+            let energy = resource_id("energy");
+            let metal = resource_id("metal");
         
-        // Test adding resources
-        system.add(ResourceType::Energy, 20.0, Some(&mut writer), None);
-        system.add(ResourceType::Metal, 15.0, Some(&mut writer), None);
+            // Test adding resources
+            system.add(energy, Quantity::from_units(20), Some(&mut writer), None);
+            system.add(metal, Quantity::from_units(15), Some(&mut writer), None);
         
-        assert_eq!(system.get(ResourceType::Energy), 20.0);
-        assert_eq!(system.get(ResourceType::Metal), 15.0);
+            assert_eq!(system.get(energy), Quantity::from_units(20));
+            assert_eq!(system.get(metal), Quantity::from_units(15));
         
-        // Test consuming resources
-        assert!(system.consume(ResourceType::Energy, 10.0, Some(&mut writer), None));
-        assert_eq!(system.get(ResourceType::Energy), 10.0);
+            // Test consuming resources
+            assert!(system.consume(energy, Quantity::from_units(10), Some(&mut writer), None));
+            assert_eq!(system.get(energy), Quantity::from_units(10));
         
-        // Test consuming more than available
-        assert!(!system.consume(ResourceType::Energy, 15.0, Some(&mut writer), None));
-        assert_eq!(system.get(ResourceType::Energy), 10.0);
+            // Test consuming more than available
+            assert!(!system.consume(energy, Quantity::from_units(15), Some(&mut writer), None));
+            assert_eq!(system.get(energy), Quantity::from_units(10));
         
-        // Test capacity limits
-        assert_eq!(system.get_capacity(ResourceType::Energy), 100.0);
-        assert_eq!(system.get_available_space(ResourceType::Energy), 90.0);
+            // Test capacity limits
+            assert_eq!(system.get_capacity(energy), Quantity::from_units(100));
+            assert_eq!(system.get_available_space(energy), Quantity::from_units(90));
         
-        // Test set method
-        system.set(ResourceType::Energy, 30.0, Some(&mut writer), None);
-        assert_eq!(system.get(ResourceType::Energy), 30.0);
+            // Test set method
+            system.set(energy, Quantity::from_units(30), Some(&mut writer), None);
+            assert_eq!(system.get(energy), Quantity::from_units(30));
         
-        // Test set_capacity method
-        system.set_capacity(ResourceType::Energy, 50.0);
-        assert_eq!(system.get_capacity(ResourceType::Energy), 50.0);
+            // Test set_capacity method
+            system.set_capacity(energy, Quantity::from_units(50));
+            assert_eq!(system.get_capacity(energy), Quantity::from_units(50));
+        }).unwrap();
         
         // Verify events were sent
-        let mut reader = events.get_reader();
-        let events: Vec<&ResourceChanged> = reader.read(&events).collect();
+        let events = app.world().resource::<Events<ResourceChanged>>();
+        let mut reader = events.get_cursor();
+        let events: Vec<&ResourceChanged> = reader.read(events).collect();
         assert!(events.len() >= 4); // At least 4 events (2 adds, 1 consume, 1 set)
     }
     
@@ -439,45 +739,108 @@ mod tests {
         let mut app = App::new();
         app.add_event::<ResourceChanged>();
         
-        let mut system1 = ResourceSystem::new();
-        let mut system2 = ResourceSystem::new();
-        let mut events = app.world.resource_mut::<Events<ResourceChanged>>();
-        let mut writer = EventWriter::new(&mut events);
+        app.world_mut().run_system_once(|mut writer: EventWriter<ResourceChanged>| {
+            let mut system1 = ResourceSystem::new();
+            let mut system2 = ResourceSystem::new();
+            let food = resource_id("food");
         
-        // Add resources to system1
-        system1.add(ResourceType::Food, 50.0, Some(&mut writer), None);
+            // Add resources to system1
+            system1.add(food, Quantity::from_units(50), Some(&mut writer), None);
         
-        // Transfer resources
-        let transferred = transfer_resources(&mut system1, &mut system2, ResourceType::Food, 30.0, Some(&mut writer), None);
+            // Transfer resources
+            let transferred = transfer_resources(&mut system1, &mut system2, food, Quantity::from_units(30), Some(&mut writer), None);
         
-        assert_eq!(transferred, 30.0);
-        assert_eq!(system1.get(ResourceType::Food), 20.0);
-        assert_eq!(system2.get(ResourceType::Food), 30.0);
+            assert_eq!(transferred, Quantity::from_units(30));
+            assert_eq!(system1.get(food), Quantity::from_units(20));
+            assert_eq!(system2.get(food), Quantity::from_units(30));
         
-        // Test transfer with capacity limits
-        let transferred2 = transfer_resources(&mut system1, &mut system2, ResourceType::Food, 100.0, Some(&mut writer), None);
+            // Test transfer with capacity limits
+            let transferred2 = transfer_resources(&mut system1, &mut system2, food, Quantity::from_units(100), Some(&mut writer), None);
         
-        // Should only transfer what's available in system1
-        assert_eq!(transferred2, 20.0);
-        assert_eq!(system1.get(ResourceType::Food), 0.0);
-        assert_eq!(system2.get(ResourceType::Food), 50.0);
+            // Should only transfer what's available in system1
+            assert_eq!(transferred2, Quantity::from_units(20));
+            assert_eq!(system1.get(food), Quantity::from_units(0));
+            assert_eq!(system2.get(food), Quantity::from_units(50));
+        }).unwrap();
         
         // Verify events were sent
-        let mut reader = events.get_reader();
-        let events: Vec<&ResourceChanged> = reader.read(&events).collect();
+        let events = app.world().resource::<Events<ResourceChanged>>();
+        let mut reader = events.get_cursor();
+        let events: Vec<&ResourceChanged> = reader.read(events).collect();
         assert!(events.len() >= 4); // At least 4 events (1 add, 2 transfers)
     }
     
     #[test]
-    fn test_resource_metadata() {
-        let food_metadata = ResourceType::Food.metadata();
-        assert_eq!(food_metadata.category, ResourceCategory::Basic);
-        assert!(food_metadata.is_renewable);
-        assert_eq!(food_metadata.decay_rate, 0.1);
-        
-        let metal_metadata = ResourceType::Metal.metadata();
-        assert_eq!(metal_metadata.category, ResourceCategory::Material);
-        assert!(!metal_metadata.is_renewable);
-        assert_eq!(metal_metadata.decay_rate, 0.0);
+    fn test_resource_registry() {
+        let registry = ResourceRegistry::default();
+
+        let food = registry.get(resource_id("food")).unwrap();
+        assert_eq!(food.category, ResourceCategory::Basic);
+        assert!(food.is_renewable);
+        assert_eq!(food.spoilage.unwrap().fresh_for, 60.0);
+
+        let metal = registry.get(resource_id("metal")).unwrap();
+        assert_eq!(metal.category, ResourceCategory::Material);
+        assert!(!metal.is_renewable);
+        assert!(metal.spoilage.is_none());
+
+        // Keys are case-insensitive
+        assert_eq!(registry.id("Wood"), registry.id("wood"));
+        assert!(registry.id("unobtainium").is_none());
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn test_resource_registry_from_data() {
+        let registry = ResourceRegistry::from_ron_str(r#"[
+            (id: "clay", name: "Clay", category: Material, tags: ["mineral"]),
+            (id: "herbs", name: "Herbs", category: Special, is_renewable: true, stack_size: 5),
+        ]"#).unwrap();
+
+        assert_eq!(registry.len(), 2);
+        let herbs = registry.get(registry.id("herbs").unwrap()).unwrap();
+        assert!(herbs.is_renewable);
+        assert_eq!(herbs.stack_size, 5);
+        assert_eq!(registry.with_tag("mineral").collect::<Vec<_>>(), vec![registry.id("clay").unwrap()]);
+
+        // Duplicate IDs are rejected
+        assert!(ResourceRegistry::from_ron_str(r#"[
+            (id: "clay", name: "Clay", category: Material),
+            (id: "Clay", name: "Clay", category: Material),
+        ]"#).is_err());
+    }
+
+    #[test]
+    fn test_food_spoils_during_ticks() {
+        let mut app = App::new();
+        app.add_plugins((TickPipelinePlugin, ResourcePlugin, ConservationPlugin))
+            .insert_resource(SimClock::with_tick_length(10.0));
+        app.world_mut().run_schedule(PreStartup);
+
+        let food = resource_id("food");
+        let mut system = ResourceSystem::new();
+        system.regeneration_rate = 0.0;
+        system.add(food, Quantity::from_units(10), None, None);
+        let pantry = app.world_mut().spawn(system).id();
+
+        // Food stays fresh for a minute, then loses quality until it rots away
+        for _ in 0..10 {
+            app.world_mut().run_schedule(FixedUpdate);
+        }
+        let quality = app.world().get::<ResourceSystem>(pantry).unwrap().quality(food).unwrap();
+        assert!(quality < 1.0 && quality > 0.0);
+
+        for _ in 0..20 {
+            app.world_mut().run_schedule(FixedUpdate);
+        }
+        assert_eq!(app.world().get::<ResourceSystem>(pantry).unwrap().get(food), Quantity::ZERO);
+        let decay: Vec<&LedgerEntry> = app
+            .world()
+            .resource::<ResourceLedger>()
+            .sent_by(Holder::Entity(pantry), food)
+            .collect();
+        assert_eq!(decay.len(), 1);
+        assert_eq!(decay[0].reason, LedgerReason::Decay);
+        assert_eq!(decay[0].amount, Quantity::from_units(10));
+        assert!(app.world().resource::<ConservationChecker>().violations().is_empty());
+    }
+}
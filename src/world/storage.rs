@@ -0,0 +1,337 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+use std::collections::HashMap;
+use crate::world::quantity::Quantity;
+use crate::world::resources::{ResourceId, ResourceRegistry, ResourceStore, ResourceSystem};
+
+/// Path of the storage tier definitions loaded at startup
+pub const STORAGE_DEFINITIONS_PATH: &str = "assets/data/storage.ron";
+
+/// Built-in copy of the storage tier definitions, used when the file can't be read
+const DEFAULT_STORAGE_DEFINITIONS: &str = include_str!("../../assets/data/storage.ron");
+
+/// Storage tier as written in data files, naming resources by registry key
+#[derive(Debug, Clone, Deserialize)]
+pub struct StorageTierDefinition {
+    pub id: String,
+    pub name: String,
+    pub default_capacity: f32,
+    #[serde(default)]
+    pub capacity: Vec<(String, f32)>,
+    #[serde(default)]
+    pub spoilage: Vec<(String, f32)>,
+    #[serde(default)]
+    pub upgrades_to: Option<String>,
+}
+
+/// Storage tier with its resources resolved against the registry
+#[derive(Debug, Clone, PartialEq)]
+pub struct StorageTier {
+    pub id: String,
+    pub name: String,
+    /// Capacity for resources without an entry in `capacity`
+    pub default_capacity: Quantity,
+    pub capacity: HashMap<ResourceId, Quantity>,
+    /// Multipliers on how fast stored stacks spoil, 1.0 if missing
+    pub spoilage: HashMap<ResourceId, f32>,
+    /// Tier reached by the next upgrade
+    pub upgrades_to: Option<String>,
+}
+
+impl StorageTier {
+    /// Resolves a tier definition against the resource registry
+    pub fn resolve(definition: StorageTierDefinition, registry: &ResourceRegistry) -> Result<Self, String> {
+        let lookup = |key: &str| {
+            registry
+                .id(key)
+                .ok_or_else(|| format!("storage tier '{}' uses unknown resource '{}'", definition.id, key))
+        };
+
+        let capacity = definition
+            .capacity
+            .iter()
+            .map(|(key, amount)| lookup(key).map(|id| (id, Quantity::from_f32(*amount))))
+            .collect::<Result<HashMap<_, _>, String>>()?;
+        let spoilage = definition
+            .spoilage
+            .iter()
+            .map(|(key, modifier)| lookup(key).map(|id| (id, modifier.max(0.0))))
+            .collect::<Result<HashMap<_, _>, String>>()?;
+
+        Ok(Self {
+            id: definition.id,
+            name: definition.name,
+            default_capacity: Quantity::from_f32(definition.default_capacity),
+            capacity,
+            spoilage,
+            upgrades_to: definition.upgrades_to,
+        })
+    }
+
+    #[cfg(test)]
+    pub fn get_capacity(&self, resource: ResourceId) -> Quantity {
+        *self.capacity.get(&resource).unwrap_or(&self.default_capacity)
+    }
+
+    /// Returns how fast stacks of a resource spoil in this tier
+    pub fn spoilage_modifier(&self, resource: ResourceId) -> f32 {
+        self.spoilage.get(&resource).copied().unwrap_or(1.0)
+    }
+
+    /// Replaces the capacities of a store with the tier's
+    ///
+    /// Stock above a lowered capacity is kept; it only blocks new deposits.
+    pub fn apply(&self, store: &mut ResourceStore) {
+        store.default_capacity = self.default_capacity;
+        store.max_capacity.clone_from(&self.capacity);
+    }
+}
+
+/// Resource holding all known storage tiers by ID
+#[derive(Debug, Clone, Default, Resource)]
+pub struct StorageTiers {
+    tiers: HashMap<String, StorageTier>,
+}
+
+impl StorageTiers {
+    /// Parses a RON list of storage tier definitions
+    ///
+    /// Every `upgrades_to` must name another tier.
+    pub fn from_ron_str(source: &str, registry: &ResourceRegistry) -> Result<Self, String> {
+        let definitions: Vec<StorageTierDefinition> = ron::from_str(source)
+            .map_err(|err| format!("invalid storage definitions: {}", err))?;
+
+        let mut tiers = HashMap::new();
+        for definition in definitions {
+            let tier = StorageTier::resolve(definition, registry)?;
+            if tiers.contains_key(&tier.id) {
+                return Err(format!("duplicate storage tier id '{}'", tier.id));
+            }
+            tiers.insert(tier.id.clone(), tier);
+        }
+        for tier in tiers.values() {
+            if let Some(next) = &tier.upgrades_to {
+                if !tiers.contains_key(next) {
+                    return Err(format!("storage tier '{}' upgrades to unknown tier '{}'", tier.id, next));
+                }
+            }
+        }
+        Ok(Self { tiers })
+    }
+
+    /// Loads storage tier definitions from a RON file
+    pub fn load(path: &str, registry: &ResourceRegistry) -> Result<Self, String> {
+        let source = std::fs::read_to_string(path)
+            .map_err(|err| format!("failed to read {}: {}", path, err))?;
+        Self::from_ron_str(&source, registry)
+    }
+
+    pub fn get(&self, id: &str) -> Option<&StorageTier> {
+        self.tiers.get(id)
+    }
+
+    /// Returns the tier a storage of the given tier upgrades to
+    pub fn next(&self, id: &str) -> Option<&StorageTier> {
+        self.get(id)?.upgrades_to.as_deref().and_then(|next| self.get(next))
+    }
+
+    #[cfg(test)]
+    pub fn iter(&self) -> impl Iterator<Item = &StorageTier> {
+        self.tiers.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.tiers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tiers.is_empty()
+    }
+}
+
+/// Component giving a `ResourceSystem` the capacities and spoilage of a
+/// storage tier
+#[derive(Component, Debug, Clone, PartialEq, Eq)]
+pub struct Storage {
+    pub tier: String,
+}
+
+impl Storage {
+    pub fn new(tier: impl Into<String>) -> Self {
+        Self { tier: tier.into() }
+    }
+}
+
+/// Loads storage tiers from the definitions file, falling back to the built-in set
+pub fn load_storage_tiers(
+    registry: Res<ResourceRegistry>,
+    mut tiers: ResMut<StorageTiers>,
+) {
+    let loaded = StorageTiers::load(STORAGE_DEFINITIONS_PATH, &registry).or_else(|err| {
+        warn!("Using built-in storage definitions: {}", err);
+        StorageTiers::from_ron_str(DEFAULT_STORAGE_DEFINITIONS, &registry)
+    });
+
+    match loaded {
+        Ok(loaded) => {
+            if loaded.is_empty() {
+                warn!("No storage tiers defined; storages keep their default capacities");
+            } else {
+                info!("Loaded {} storage tiers", loaded.len());
+            }
+            *tiers = loaded;
+        }
+        Err(err) => {
+            error!("No usable storage definitions: {}", err);
+            *tiers = StorageTiers::default();
+        }
+    }
+}
+
+/// System applying a storage's tier capacities when it is placed or upgraded
+pub fn apply_storage_tiers(
+    tiers: Res<StorageTiers>,
+    mut storages: Query<(&Storage, &mut ResourceSystem), Changed<Storage>>,
+) {
+    for (storage, mut system) in storages.iter_mut() {
+        match tiers.get(&storage.tier) {
+            Some(tier) => tier.apply(&mut system.store),
+            None => warn!("Unknown storage tier '{}'", storage.tier),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+    use crate::agents::agent::Agent;
+    use crate::agents::job::Job;
+    use crate::engine::pipeline::TickPipelinePlugin;
+    use crate::engine::time::SimClock;
+    use crate::world::ledger::ResourceLedger;
+    use crate::world::ownership::{AccessPolicy, Owner, Stockpile};
+    use crate::world::recipes::{production_job_system, CraftingProgress, RecipeBook, RecipeCompleted};
+    use crate::world::resources::{ResourceChanged, ResourcePlugin};
+
+    #[test]
+    fn test_builtin_tiers_form_an_upgrade_path() {
+        let registry = ResourceRegistry::default();
+        let tiers = StorageTiers::from_ron_str(DEFAULT_STORAGE_DEFINITIONS, &registry).unwrap();
+        let food = registry.id("food").unwrap();
+
+        let basket = tiers.get("basket").unwrap();
+        let granary = tiers.next("basket").unwrap();
+        assert_eq!(granary.id, "granary");
+        assert_eq!(tiers.next("granary").unwrap().id, "warehouse");
+        assert!(tiers.next("warehouse").is_none());
+        assert!(granary.get_capacity(food) > basket.get_capacity(food));
+        assert_eq!(basket.spoilage_modifier(food), 1.0);
+        assert_eq!(granary.spoilage_modifier(food), 0.5);
+
+        // Every upgrade can be built
+        let book = RecipeBook::from_ron_str(include_str!("../../assets/data/recipes.ron"), &registry).unwrap();
+        for tier in tiers.iter().filter_map(|tier| tier.upgrades_to.as_deref()) {
+            assert!(book.for_storage(tier).is_some(), "no recipe builds {}", tier);
+        }
+
+        let dangling = r#"[(id: "crate", name: "Crate", default_capacity: 5.0, upgrades_to: Some("vault"))]"#;
+        assert!(StorageTiers::from_ron_str(dangling, &registry).is_err());
+    }
+
+    #[test]
+    fn test_upgrade_job_raises_the_tier() {
+        let registry = ResourceRegistry::default();
+        let tiers = StorageTiers::from_ron_str(DEFAULT_STORAGE_DEFINITIONS, &registry).unwrap();
+        let book = RecipeBook::from_ron_str(include_str!("../../assets/data/recipes.ron"), &registry).unwrap();
+        let food = registry.id("food").unwrap();
+        let wood = registry.id("wood").unwrap();
+        let stone = registry.id("stone").unwrap();
+
+        let mut world = World::new();
+        world.insert_resource(tiers);
+        world.insert_resource(book);
+        world.init_resource::<Time>();
+        world.init_resource::<SimClock>();
+        world.init_resource::<ResourceLedger>();
+        world.init_resource::<Events<ResourceChanged>>();
+        world.init_resource::<Events<RecipeCompleted>>();
+
+        let stockpile = world.spawn((ResourceSystem::new(), Storage::new("basket"))).id();
+        world.run_system_once(apply_storage_tiers).unwrap();
+        assert_eq!(world.get::<ResourceSystem>(stockpile).unwrap().get_capacity(food), Quantity::from_units(25));
+
+        let mut inventory = ResourceSystem::new();
+        inventory.add(wood, Quantity::from_units(15), None, None);
+        inventory.add(stone, Quantity::from_units(10), None, None);
+        let agent = Agent {
+            current_job: Some(Job::UpgradeStorage { stockpile }),
+            ..Default::default()
+        };
+        // Work on the upgrade has already been done
+        let progress = CraftingProgress {
+            recipe_id: "build_granary".to_string(),
+            elapsed: 90.0,
+        };
+        let builder = world.spawn((agent, inventory, progress)).id();
+        let stranger = world.spawn((
+            Agent {
+                current_job: Some(Job::UpgradeStorage { stockpile }),
+                ..Default::default()
+            },
+            ResourceSystem::new(),
+        )).id();
+
+        // Nobody can upgrade a storage out of reach
+        world.run_system_once(production_job_system).unwrap();
+        assert_eq!(world.get::<Storage>(stockpile).unwrap().tier, "basket");
+        assert_eq!(world.get::<Agent>(builder).unwrap().current_job, Some(Job::Idle));
+
+        world.entity_mut(stockpile).insert((
+            Transform::default(),
+            Stockpile::new(Owner::Agent(builder), AccessPolicy::Private),
+        ));
+        world.get_mut::<Agent>(builder).unwrap().current_job = Some(Job::UpgradeStorage { stockpile });
+        world.entity_mut(builder).insert(CraftingProgress {
+            recipe_id: "build_granary".to_string(),
+            elapsed: 90.0,
+        });
+        world.get_mut::<Agent>(stranger).unwrap().current_job = Some(Job::UpgradeStorage { stockpile });
+        world.run_system_once(production_job_system).unwrap();
+        world.run_system_once(apply_storage_tiers).unwrap();
+
+        assert_eq!(world.get::<Storage>(stockpile).unwrap().tier, "granary");
+        assert_eq!(world.get::<ResourceSystem>(stockpile).unwrap().get_capacity(food), Quantity::from_units(200));
+        assert_eq!(world.get::<ResourceSystem>(builder).unwrap().get(wood), Quantity::ZERO);
+        assert_eq!(world.get::<Agent>(builder).unwrap().current_job, Some(Job::Idle));
+        // Only the owner got to work on it
+        assert_eq!(world.get::<Agent>(stranger).unwrap().current_job, Some(Job::Idle));
+        assert!(world.get::<CraftingProgress>(stranger).is_none());
+    }
+
+    #[test]
+    fn test_better_storage_slows_spoilage() {
+        let mut app = App::new();
+        app.add_plugins((TickPipelinePlugin, ResourcePlugin))
+            .insert_resource(SimClock::with_tick_length(10.0));
+        app.world_mut().run_schedule(PreStartup);
+
+        let food = ResourceRegistry::default().id("food").unwrap();
+        let mut spawn = |tier: &str| {
+            let mut system = ResourceSystem::new();
+            system.regeneration_rate = 0.0;
+            system.add(food, Quantity::from_units(10), None, None);
+            app.world_mut().spawn((system, Storage::new(tier))).id()
+        };
+        let basket = spawn("basket");
+        let granary = spawn("granary");
+
+        for _ in 0..18 {
+            app.world_mut().run_schedule(FixedUpdate);
+        }
+        let quality = |entity| app.world().get::<ResourceSystem>(entity).unwrap().quality(food).unwrap();
+        // Food ages half as fast in the granary, so it stays fresh twice as long
+        assert!((quality(basket) - 0.4).abs() < 1e-4);
+        assert!((quality(granary) - 0.85).abs() < 1e-4);
+    }
+}
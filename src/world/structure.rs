@@ -0,0 +1,16 @@
+use bevy::prelude::*;
+
+/// Component for buildings placed in the world
+///
+/// Structures are placed by `Job::Build` and act as workstations for
+/// recipes that require a building nearby.
+#[derive(Component, Debug, Clone, PartialEq, Eq)]
+pub struct Structure {
+    pub kind: String,
+}
+
+impl Structure {
+    pub fn new(kind: impl Into<String>) -> Self {
+        Self { kind: kind.into() }
+    }
+}